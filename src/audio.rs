@@ -0,0 +1,345 @@
+//! Render morse to audio: a sine tone keyed on the marks, with a raised cosine envelope on
+//! every edge to avoid clicks, written as 16 bit mono WAV.
+//! Keying is timed by `timing::Timing`: PARIS speeds (a word is 50 units), optional
+//! Farnsworth spacing, and the dash and gap lengths.
+
+use crate::encode::word_codes;
+use crate::timing::Timing;
+use color_eyre::{eyre::eyre, Report};
+use std::f64::consts::PI;
+use std::path::Path;
+use tracing::{debug, info};
+
+pub const DEFAULT_TONE: f64 = 600.0;
+pub const DEFAULT_SAMPLE_RATE: u32 = 22050;
+/// Raised cosine rise and fall time, in seconds
+pub const DEFAULT_RAMP: f64 = 0.005;
+/// Peak amplitude, as a fraction of full scale
+const AMPLITUDE: f64 = 0.8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioConfig {
+    /// Speeds, and dash and gap lengths
+    pub timing: Timing,
+    /// Tone frequency, Hz
+    pub tone: f64,
+    pub sample_rate: u32,
+    /// Rise and fall time of the keying envelope, seconds
+    pub ramp: f64,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        AudioConfig {
+            timing: Timing::default(),
+            tone: DEFAULT_TONE,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            ramp: DEFAULT_RAMP,
+        }
+    }
+}
+
+impl AudioConfig {
+    pub fn validate(&self) -> Result<(), Report> {
+        self.timing.validate()?;
+        if self.sample_rate == 0 {
+            return Err(eyre!("Sample rate must be positive"));
+        }
+        if !(self.tone > 0.0 && self.tone < f64::from(self.sample_rate) / 2.0) {
+            return Err(eyre!(
+                "Tone must be between 0 and {} Hz (half the sample rate): {} Hz",
+                f64::from(self.sample_rate) / 2.0,
+                self.tone
+            ));
+        }
+        if !(self.ramp.is_finite() && self.ramp >= 0.0) {
+            return Err(eyre!("Ramp time can't be negative: {} s", self.ramp));
+        }
+        Ok(())
+    }
+}
+
+/// How letters of a word are separated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Spacing {
+    /// Letter gaps between letters
+    Standard,
+    /// No letter gaps, only the element gaps: the word sounds as its smooshed code
+    Smooshed,
+}
+
+/// Key down (tone) or up (silence), for a duration in seconds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Key {
+    pub on: bool,
+    pub duration: f64,
+}
+
+/// Keying of a sentence (words separated by whitespace)
+pub fn keying(sentence: &str, spacing: Spacing, config: &AudioConfig) -> Result<Vec<Key>, Report> {
+    config.validate()?;
+    let timing = &config.timing;
+    let mut keys: Vec<Key> = Vec::new();
+    let key = |keys: &mut Vec<Key>, on: bool, units: f64| {
+        keys.push(Key {
+            on,
+            duration: timing.seconds(units),
+        })
+    };
+    for (i, word) in sentence.split_whitespace().enumerate() {
+        let codes = word_codes(word)?;
+        if i > 0 {
+            key(&mut keys, false, timing.word_gap_units());
+        }
+        for (j, code) in codes.iter().enumerate() {
+            if j > 0 {
+                key(&mut keys, false, timing.letter_gap_units(spacing));
+            }
+            for (k, symbol) in code.chars().enumerate() {
+                if k > 0 {
+                    key(&mut keys, false, timing.element_gap);
+                }
+                key(&mut keys, true, timing.mark_units(symbol));
+            }
+        }
+    }
+    debug!("Keyed {} in {} periods", sentence, keys.len());
+    Ok(keys)
+}
+
+/// Total duration of the keying, seconds
+pub fn duration(keys: &[Key]) -> f64 {
+    keys.iter().map(|key| key.duration).sum()
+}
+
+/// Synthesize the keying as 16 bit samples
+pub fn render(keys: &[Key], config: &AudioConfig) -> Vec<i16> {
+    let sample_rate = f64::from(config.sample_rate);
+    let mut samples: Vec<i16> = Vec::new();
+    // sample boundaries from the running time, so that rounding errors don't add up
+    let mut time = 0.0;
+    for key in keys {
+        let start = (time * sample_rate).round() as usize;
+        time += key.duration;
+        let len = (time * sample_rate).round() as usize - start;
+        if !key.on {
+            samples.resize(samples.len() + len, 0);
+            continue;
+        }
+        let ramp = (config.ramp * sample_rate).min(len as f64 / 2.0);
+        samples.extend((0..len).map(|i| {
+            let t = i as f64 / sample_rate;
+            let envelope = if (i as f64) < ramp {
+                0.5 * (1.0 - (PI * i as f64 / ramp).cos())
+            } else if ((len - i) as f64) < ramp {
+                0.5 * (1.0 - (PI * (len - i) as f64 / ramp).cos())
+            } else {
+                1.0
+            };
+            let value = AMPLITUDE * envelope * (2.0 * PI * config.tone * t).sin();
+            (value * f64::from(i16::MAX)).round() as i16
+        }));
+    }
+    samples
+}
+
+pub fn write_wav<P: AsRef<Path>>(path: P, samples: &[i16], sample_rate: u32) -> Result<(), Report> {
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(path, spec)?;
+    for sample in samples {
+        writer.write_sample(*sample)?;
+    }
+    writer.finalize()?;
+    Ok(())
+}
+
+/// Render a sentence to a WAV file, returning its duration in seconds
+pub fn render_wav<P: AsRef<Path>>(
+    sentence: &str,
+    spacing: Spacing,
+    config: &AudioConfig,
+    path: P,
+) -> Result<f64, Report> {
+    let keys = keying(sentence, spacing, config)?;
+    let samples = render(&keys, config);
+    write_wav(&path, &samples, config.sample_rate)?;
+    let duration = duration(&keys);
+    info!(
+        "Wrote {:.2} s of audio to {}",
+        duration,
+        path.as_ref().display()
+    );
+    Ok(duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(AudioConfig::default().validate().is_ok());
+        let bad = [
+            AudioConfig {
+                timing: Timing {
+                    wpm: 0.0,
+                    ..Timing::default()
+                },
+                ..AudioConfig::default()
+            },
+            AudioConfig {
+                timing: Timing {
+                    dash: -3.0,
+                    ..Timing::default()
+                },
+                ..AudioConfig::default()
+            },
+            AudioConfig {
+                tone: 12000.0,
+                ..AudioConfig::default()
+            },
+            AudioConfig {
+                sample_rate: 0,
+                ..AudioConfig::default()
+            },
+            AudioConfig {
+                ramp: -1.0,
+                ..AudioConfig::default()
+            },
+        ];
+        for config in bad.iter() {
+            assert!(config.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_timing() {
+        let timing = Timing::default();
+        assert_close(timing.unit(), 0.06);
+        let gaps = |timing: &Timing| {
+            (
+                timing.seconds(timing.letter_gap_units(Spacing::Standard)),
+                timing.seconds(timing.word_gap_units()),
+            )
+        };
+        let (letter_gap, word_gap) = gaps(&timing);
+        assert_close(letter_gap, 0.18);
+        assert_close(word_gap, 0.42);
+        let farnsworth = AudioConfig {
+            timing: Timing {
+                farnsworth_wpm: Some(10.0),
+                ..Timing::default()
+            },
+            ..AudioConfig::default()
+        };
+        // the ARRL formula: 19 gap units of PARIS take (60 c - 37.2 s) / (s c) = 4.14 s
+        let (letter_gap, word_gap) = gaps(&farnsworth.timing);
+        assert_close(letter_gap, 3.0 * 4.14 / 19.0);
+        assert_close(word_gap, 7.0 * 4.14 / 19.0);
+        // PARIS plus its word gap lasts a minute divided by the overall speed
+        let keys = keying("paris", Spacing::Standard, &farnsworth).unwrap();
+        assert_close(duration(&keys) + word_gap, 6.0);
+    }
+
+    #[test]
+    fn test_keying() {
+        let config = AudioConfig::default();
+        let u = config.timing.unit();
+        let on = |units: f64| Key {
+            on: true,
+            duration: units * u,
+        };
+        let off = |units: f64| Key {
+            on: false,
+            duration: units * u,
+        };
+        assert_eq!(
+            keying("ne t", Spacing::Standard, &config).unwrap(),
+            vec![
+                on(3.0),
+                off(1.0),
+                on(1.0),
+                off(3.0),
+                on(1.0),
+                off(7.0),
+                on(3.0)
+            ]
+        );
+        assert_eq!(
+            keying("ne", Spacing::Smooshed, &config).unwrap(),
+            vec![on(3.0), off(1.0), on(1.0), off(1.0), on(1.0)]
+        );
+        assert!(keying("", Spacing::Standard, &config).unwrap().is_empty());
+        assert!(keying("n3", Spacing::Standard, &config).is_err());
+        // the dash and gap lengths apply
+        let long = AudioConfig {
+            timing: Timing {
+                dash: 4.0,
+                element_gap: 2.0,
+                letter_gap: 5.0,
+                word_gap: 9.0,
+                ..Timing::default()
+            },
+            ..AudioConfig::default()
+        };
+        assert_eq!(
+            keying("ne t", Spacing::Standard, &long).unwrap(),
+            vec![
+                on(4.0),
+                off(2.0),
+                on(1.0),
+                off(5.0),
+                on(1.0),
+                off(9.0),
+                on(4.0),
+            ]
+        );
+        assert_eq!(
+            keying("ne", Spacing::Smooshed, &long).unwrap(),
+            vec![on(4.0), off(2.0), on(1.0), off(2.0), on(1.0)]
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let config = AudioConfig {
+            sample_rate: 8000,
+            ..AudioConfig::default()
+        };
+        let keys = keying("e e", Spacing::Standard, &config).unwrap();
+        let samples = render(&keys, &config);
+        assert_eq!(samples.len(), (duration(&keys) * 8000.0).round() as usize);
+        let dot = (config.timing.unit() * 8000.0).round() as usize;
+        // soft edges, silent gap
+        assert!(samples[0].abs() < 100);
+        assert!(samples[dot - 1].abs() < 1000);
+        assert!(samples[dot..dot + 7 * dot].iter().all(|s| *s == 0));
+        let peak = samples.iter().map(|s| s.abs()).max().unwrap();
+        assert!(peak > 25000 && peak <= (AMPLITUDE * f64::from(i16::MAX)) as i16 + 1);
+    }
+
+    #[test]
+    fn test_render_wav() {
+        let path = std::env::temp_dir().join(format!("smooshedmorse-{}.wav", std::process::id()));
+        let config = AudioConfig::default();
+        let duration = render_wav("sos", Spacing::Smooshed, &config, &path).unwrap();
+        assert_close(duration, 23.0 * config.timing.unit());
+        let reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.spec().channels, 1);
+        assert_eq!(reader.spec().sample_rate, DEFAULT_SAMPLE_RATE);
+        assert_eq!(
+            reader.duration() as usize,
+            (duration * 22050.0).round() as usize
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,125 @@
+//! Line by line batch processing: one input per line, one result line (or record) per
+//! input line, written as soon as it is computed so that huge inputs can be streamed.
+
+use crate::records::{Format, Record, RecordWriter};
+use color_eyre::Report;
+use serde_json::Value;
+use std::io::{self, BufRead, Write};
+use tracing::debug;
+
+/// Positional argument value asking to read the inputs from stdin
+pub const STDIN_ARG: &str = "-";
+/// Text output of a line failing to process
+pub const ERROR_MARKER: &str = "?";
+
+/// Run `process` on every line of `input`, writing its outputs to `output`: space separated
+/// on one line in text format, else as a record `{input, outputs, error}` per line.
+/// A line failing to process is reported without stopping the batch: in text format by
+/// `ERROR_MARKER` and its error on stderr, else by a record with the error. Returns the
+/// number of lines processed.
+pub fn run_lines<R, W, F>(
+    input: R,
+    output: W,
+    format: Format,
+    mut process: F,
+) -> Result<usize, Report>
+where
+    R: BufRead,
+    W: Write,
+    F: FnMut(&str) -> Result<Vec<String>, Report>,
+{
+    let mut writer = RecordWriter::new(output, format, "text");
+    let mut count = 0;
+    for line in input.lines() {
+        let line = line?;
+        let line = line.trim();
+        let res = process(line);
+        if let Err(e) = &res {
+            // reported once, on stderr in text format, else in the record
+            debug!("Line {}: {}", count + 1, e);
+        }
+        let record = match (format, res) {
+            (Format::Text, Ok(outputs)) => Record::new().with("text", outputs.join(" ")),
+            (Format::Text, Err(e)) => {
+                // stdout stays aligned with the input, the error goes beside it
+                eprintln!("error: line {}: {}", count + 1, e);
+                Record::new().with("text", ERROR_MARKER)
+            }
+            (_, Ok(outputs)) => Record::new()
+                .with("input", line)
+                .with("outputs", outputs)
+                .with("error", Value::Null),
+            (_, Err(e)) => Record::new()
+                .with("input", line)
+                .with("outputs", Vec::<String>::new())
+                .with("error", e.to_string()),
+        };
+        match writer.write(&record) {
+            // the reader went away (e.g. `| head`), nothing left to do
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {
+                debug!("Output closed after {} lines", count);
+                return Ok(count);
+            }
+            written => written?,
+        }
+        count += 1;
+    }
+    writer.finish()?;
+    debug!("Processed {} lines", count);
+    Ok(count)
+}
+
+/// `run_lines` from stdin to stdout
+pub fn run_stdin<F>(format: Format, process: F) -> Result<usize, Report>
+where
+    F: FnMut(&str) -> Result<Vec<String>, Report>,
+{
+    run_lines(io::stdin().lock(), io::stdout().lock(), format, process)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use color_eyre::eyre::eyre;
+
+    fn upper(line: &str) -> Result<Vec<String>, Report> {
+        if line.contains('!') {
+            return Err(eyre!("No bang"));
+        }
+        Ok(vec![line.to_uppercase(), line.to_string()])
+    }
+
+    fn run(input: &str, format: Format) -> String {
+        let mut output: Vec<u8> = Vec::new();
+        run_lines(input.as_bytes(), &mut output, format, upper).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_run_lines() {
+        let mut output: Vec<u8> = Vec::new();
+        assert_eq!(
+            run_lines(
+                "ab\n cd \nno!\n".as_bytes(),
+                &mut output,
+                Format::Text,
+                upper
+            )
+            .unwrap(),
+            3
+        );
+        assert_eq!(String::from_utf8(output).unwrap(), "AB ab\nCD cd\n?\n");
+    }
+
+    #[test]
+    fn test_run_lines_records() {
+        assert_eq!(
+            run("ab\nno!", Format::Ndjson),
+            "{\"input\":\"ab\",\"outputs\":[\"AB\",\"ab\"],\"error\":null}\n{\"input\":\"no!\",\"outputs\":[],\"error\":\"No bang\"}\n"
+        );
+        assert_eq!(
+            run("ab\nno!", Format::Csv),
+            "input,outputs,error\nab,AB ab,\nno!,,No bang\n"
+        );
+    }
+}
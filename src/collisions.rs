@@ -0,0 +1,183 @@
+//! Collision groups: the words sharing a code, largest groups first, and the number of
+//! groups of each size, to study the ambiguity of smooshed morse beyond `extra1`.
+
+use crate::decode::DecodeIndex;
+use crate::merses::merse_to_morse;
+use crate::merses::Merse;
+use crate::records::Record;
+use crate::wordlist::get_all_words;
+use color_eyre::Report;
+use std::collections::BTreeMap;
+use tracing::info;
+
+pub const DEFAULT_TOP: usize = 10;
+
+/// Words and codes to consider, all by default
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Filter {
+    /// Number of letters of the words
+    pub word_length: Option<usize>,
+    /// Number of dots and dashes of the codes
+    pub code_length: Option<usize>,
+}
+
+impl Filter {
+    fn keeps_word(&self, word: &str) -> bool {
+        self.word_length
+            .is_none_or(|len| word.chars().count() == len)
+    }
+
+    fn keeps_code(&self, code: &Merse) -> bool {
+        self.code_length.is_none_or(|len| code.len() == len)
+    }
+}
+
+/// Words sharing a code, in word list order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Group {
+    pub code: Merse,
+    pub words: Vec<String>,
+}
+
+/// Groups of the words kept by the filter, largest first, then by code
+pub fn groups(all_words: &[String], filter: &Filter) -> Result<Vec<Group>, Report> {
+    let words: Vec<String> = all_words
+        .iter()
+        .filter(|word| filter.keeps_word(word))
+        .cloned()
+        .collect();
+    let index = DecodeIndex::new(&words)?;
+    let mut groups: Vec<Group> = index
+        .iter()
+        .filter(|(code, _)| filter.keeps_code(code))
+        .map(|(code, words)| Group {
+            code: code.clone(),
+            words: words.to_vec(),
+        })
+        .collect();
+    // the index iteration order is random
+    groups.sort_by(|a, b| {
+        b.words
+            .len()
+            .cmp(&a.words.len())
+            .then_with(|| a.code.cmp(&b.code))
+    });
+    Ok(groups)
+}
+
+/// Number of groups of each size, smallest size first
+pub fn histogram(groups: &[Group]) -> Vec<(usize, usize)> {
+    let mut sizes: BTreeMap<usize, usize> = BTreeMap::new();
+    for group in groups {
+        *sizes.entry(group.words.len()).or_insert(0) += 1;
+    }
+    sizes.into_iter().collect()
+}
+
+fn load_groups(words_file: Option<&str>, filter: &Filter) -> Result<Vec<Group>, Report> {
+    let all_words: Vec<String> = get_all_words(words_file)?;
+    info!("Grouping {} words by code...", all_words.len());
+    let groups = groups(&all_words, filter)?;
+    info!("Grouping {} words by code: done", all_words.len());
+    Ok(groups)
+}
+
+/// The `top` largest groups (all of them if 0), ranked
+pub fn run(words_file: Option<&str>, filter: &Filter, top: usize) -> Result<Vec<Record>, Report> {
+    let groups = load_groups(words_file, filter)?;
+    let top = if top == 0 { groups.len() } else { top };
+    Ok(groups
+        .into_iter()
+        .take(top)
+        .enumerate()
+        .map(|(i, group)| {
+            Record::new()
+                .with("rank", i + 1)
+                .with("code", merse_to_morse(&group.code))
+                .with("length", group.code.len())
+                .with("count", group.words.len())
+                .with("words", group.words)
+        })
+        .collect())
+}
+
+/// Histogram of the group sizes: size 1 are the unambiguous codes
+pub fn run_histogram(words_file: Option<&str>, filter: &Filter) -> Result<Vec<Record>, Report> {
+    let groups = load_groups(words_file, filter)?;
+    Ok(histogram(&groups)
+        .into_iter()
+        .map(|(size, count)| {
+            Record::new()
+                .with("size", size)
+                .with("groups", count)
+                .with("words", size * count)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::words;
+
+    #[test]
+    fn test_groups() {
+        // et, a and n, te share codes
+        let all_words = words(&["et", "a", "eee", "s", "n", "te", "ceca", "caret", "i"]);
+        let groups = groups(&all_words, &Filter::default()).unwrap();
+        assert_eq!(groups.len(), 5);
+        // ties by code, dot before dash, prefix first
+        assert_eq!(groups[0].words, words(&["eee", "s"]));
+        assert_eq!(groups[1].words, words(&["et", "a"]));
+        assert_eq!(groups[2].words, words(&["n", "te"]));
+        assert_eq!(groups[3].words, words(&["ceca", "caret"]));
+        assert_eq!(groups[4].words, words(&["i"]));
+        assert_eq!(histogram(&groups), vec![(1, 1), (2, 4)]);
+    }
+
+    #[test]
+    fn test_filter() {
+        let all_words = words(&["et", "a", "eee", "s", "n", "te", "ceca", "caret"]);
+        let filter = Filter {
+            word_length: Some(2),
+            ..Filter::default()
+        };
+        let two_letters = groups(&all_words, &filter).unwrap();
+        assert_eq!(two_letters.len(), 2);
+        assert!(two_letters.iter().all(|group| group.words.len() == 1));
+        let filter = Filter {
+            code_length: Some(3),
+            ..Filter::default()
+        };
+        let three_symbols = groups(&all_words, &filter).unwrap();
+        assert_eq!(three_symbols.len(), 1);
+        assert_eq!(three_symbols[0].words, words(&["eee", "s"]));
+    }
+
+    #[test]
+    fn test_run() {
+        let records = run(Some("tests/wordlist"), &Filter::default(), 3).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].get("rank").unwrap(), 1);
+        let counts: Vec<u64> = records
+            .iter()
+            .map(|r| r.get("count").unwrap().as_u64().unwrap())
+            .collect();
+        assert!(counts.windows(2).all(|pair| pair[0] >= pair[1]));
+        let histogram = run_histogram(Some("tests/wordlist"), &Filter::default()).unwrap();
+        let words: u64 = histogram
+            .iter()
+            .map(|r| r.get("words").unwrap().as_u64().unwrap())
+            .sum();
+        assert_eq!(
+            words,
+            get_all_words(Some("tests/wordlist")).unwrap().len() as u64
+        );
+        let all = run(Some("tests/wordlist"), &Filter::default(), 0).unwrap();
+        let groups: u64 = histogram
+            .iter()
+            .map(|r| r.get("groups").unwrap().as_u64().unwrap())
+            .sum();
+        assert_eq!(all.len() as u64, groups);
+    }
+}
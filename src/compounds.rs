@@ -0,0 +1,244 @@
+//! Compound collisions: words whose code is also the code of a sequence of two or more
+//! shorter words of the list (a long word smooshing like `word1 word2`). Words without
+//! compound collisions nor another word of the same code are truly unambiguous, fit for
+//! reliable smooshed transmission.
+
+use crate::decode::all_words_to_smooshedmerse;
+use crate::merses::merse_to_morse;
+use crate::merses::Merse;
+use crate::records::Record;
+use crate::wordlist::get_all_words;
+use color_eyre::Report;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use tracing::info;
+
+/// Binary trie of the word codes
+#[derive(Debug, Clone)]
+struct CodeTrie {
+    /// Children of each node by symbol, dot first, 0 for none (the root is no child)
+    children: Vec<[usize; 2]>,
+    /// First word and number of words ending at each node
+    words: Vec<Option<(usize, usize)>>,
+}
+
+impl CodeTrie {
+    fn new(codes: &[Merse]) -> Self {
+        let mut trie = CodeTrie {
+            children: vec![[0, 0]],
+            words: vec![None],
+        };
+        for (i, code) in codes.iter().enumerate() {
+            let mut node = 0;
+            for symbol in code.iter() {
+                let next = trie.children[node][usize::from(symbol)];
+                node = if next == 0 {
+                    trie.children.push([0, 0]);
+                    trie.words.push(None);
+                    let child = trie.children.len() - 1;
+                    trie.children[node][usize::from(symbol)] = child;
+                    child
+                } else {
+                    next
+                };
+            }
+            trie.words[node] = match trie.words[node] {
+                Some((first, count)) => Some((first, count + 1)),
+                None => Some((i, 1)),
+            };
+        }
+        trie
+    }
+
+    /// Number of words of the code
+    fn count(&self, code: &Merse) -> usize {
+        let mut node = 0;
+        for symbol in code.iter() {
+            node = self.children[node][usize::from(symbol)];
+            if node == 0 {
+                return 0;
+            }
+        }
+        self.words[node].map_or(0, |(_, count)| count)
+    }
+
+    /// Fewest words of two or more whose codes make the code, as positions of the first
+    /// word of each code
+    fn split(&self, code: &Merse) -> Option<Vec<usize>> {
+        let symbols: Vec<bool> = code.iter().collect();
+        let len = symbols.len();
+        // an empty code (a blank line) is made of no words
+        if len == 0 {
+            return None;
+        }
+        // best[j]: (fewest words covering symbols[..j], start of the last one, its word)
+        let mut best: Vec<Option<(usize, usize, usize)>> = vec![None; len + 1];
+        best[0] = Some((0, 0, 0));
+        for i in 0..len {
+            let count = match best[i] {
+                Some((count, _, _)) => count,
+                None => continue,
+            };
+            let mut node = 0;
+            for (j, symbol) in symbols.iter().enumerate().skip(i) {
+                node = self.children[node][usize::from(*symbol)];
+                if node == 0 {
+                    break;
+                }
+                let word = match self.words[node] {
+                    // the whole code is no split
+                    Some(_) if i == 0 && j + 1 == len => continue,
+                    Some((word, _)) => word,
+                    None => continue,
+                };
+                if best[j + 1].is_none_or(|(c, _, _)| count + 1 < c) {
+                    best[j + 1] = Some((count + 1, i, word));
+                }
+            }
+        }
+        let mut words: Vec<usize> = Vec::new();
+        let mut j = len;
+        while j > 0 {
+            let (_, i, word) = best[j]?;
+            words.push(word);
+            j = i;
+        }
+        words.reverse();
+        Some(words)
+    }
+}
+
+/// A word and how its code splits in other words, if it does
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Compound {
+    pub word: usize,
+    /// Words of the same code, itself included
+    pub collisions: usize,
+    /// Fewest words of two or more with the same code as the word
+    pub split: Option<Vec<usize>>,
+}
+
+impl Compound {
+    pub fn is_unambiguous(&self) -> bool {
+        self.collisions == 1 && self.split.is_none()
+    }
+}
+
+/// Analysis of every code, in order
+pub fn find_compounds(codes: &[Merse]) -> Vec<Compound> {
+    let trie = CodeTrie::new(codes);
+    #[cfg(feature = "parallel")]
+    let all_codes = codes.par_iter();
+    #[cfg(not(feature = "parallel"))]
+    let all_codes = codes.iter();
+    all_codes
+        .enumerate()
+        .map(|(word, code)| Compound {
+            word,
+            collisions: trie.count(code),
+            split: trie.split(code),
+        })
+        .collect()
+}
+
+/// Words whose code splits in other words, with the fewest words of the split, or the
+/// unambiguous words
+pub fn run(words_file: Option<&str>, unambiguous: bool) -> Result<Vec<Record>, Report> {
+    let all_words: Vec<String> = get_all_words(words_file)?;
+
+    info!("Converting all words to smooshedmorse...");
+    let all_merse_words = all_words_to_smooshedmerse(&all_words)?;
+    info!("Converting all words to smooshedmorse: done");
+
+    let compounds = find_compounds(&all_merse_words);
+    let record = |word: usize| {
+        Record::new()
+            .with("word", all_words[word].clone())
+            .with("code", merse_to_morse(&all_merse_words[word]))
+    };
+    let records: Vec<Record> = if unambiguous {
+        compounds
+            .iter()
+            .filter(|compound| compound.is_unambiguous())
+            .map(|compound| {
+                record(compound.word).with("length", all_merse_words[compound.word].len())
+            })
+            .collect()
+    } else {
+        compounds
+            .into_iter()
+            .filter_map(|compound| {
+                let split = compound.split?;
+                let words: Vec<String> = split.iter().map(|i| all_words[*i].clone()).collect();
+                Some(
+                    record(compound.word)
+                        .with("count", words.len())
+                        .with("words", words),
+                )
+            })
+            .collect()
+    };
+    info!("Found {} words", records.len());
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode::word_to_smooshedmerse;
+
+    fn codes(words: &[&str]) -> Vec<Merse> {
+        words
+            .iter()
+            .map(|word| word_to_smooshedmerse(word).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_trie() {
+        let trie = CodeTrie::new(&codes(&["et", "a", "tee", "n"]));
+        assert_eq!(trie.count(&".-".parse().unwrap()), 2);
+        assert_eq!(trie.count(&"-.".parse().unwrap()), 1);
+        assert_eq!(trie.count(&"-".parse().unwrap()), 0);
+        assert_eq!(trie.count(&"---".parse().unwrap()), 0);
+        // tee is n and e, but e is no word
+        assert_eq!(trie.split(&"-..".parse().unwrap()), None);
+        // the whole code is no split, even if other words share it
+        assert_eq!(trie.split(&".-".parse().unwrap()), None);
+        assert_eq!(trie.split(&".--.".parse().unwrap()), Some(vec![0, 3]));
+        assert_eq!(trie.split(&Merse::new()), None);
+    }
+
+    #[test]
+    fn test_find_compounds() {
+        // sos is s and os (or so and s), so is s and o, os is o and s
+        let words = ["sos", "so", "s", "o", "os", "dog"];
+        let compounds = find_compounds(&codes(&words));
+        assert_eq!(compounds[0].split, Some(vec![2, 4]));
+        assert_eq!(compounds[1].split, Some(vec![2, 3]));
+        assert_eq!(compounds[2].split, None);
+        assert_eq!(compounds[4].split, Some(vec![3, 2]));
+        let unambiguous: Vec<&str> = compounds
+            .iter()
+            .filter(|compound| compound.is_unambiguous())
+            .map(|compound| words[compound.word])
+            .collect();
+        assert_eq!(unambiguous, vec!["s", "o", "dog"]);
+    }
+
+    #[test]
+    fn test_run() {
+        let compounds = run(Some("tests/wordlist"), false).unwrap();
+        let unambiguous = run(Some("tests/wordlist"), true).unwrap();
+        assert!(!compounds.is_empty() && !unambiguous.is_empty());
+        for record in compounds {
+            let words = record.get("words").unwrap().as_array().unwrap();
+            assert!(words.len() >= 2);
+            let joined: String = words
+                .iter()
+                .map(|word| merse_to_morse(&word_to_smooshedmerse(word.as_str().unwrap()).unwrap()))
+                .collect();
+            assert_eq!(record.get("code").unwrap(), joined.as_str());
+        }
+    }
+}
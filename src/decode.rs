@@ -1,9 +1,13 @@
 use crate::encode::word_to_smooshedmerse;
 use crate::merses::smooshedmorse_to_merse;
+use crate::merses::Merse;
 use crate::morses::validate_morse_str;
 use crate::wordlist::get_all_words;
-use color_eyre::Report;
-use tracing::debug;
+use color_eyre::{eyre::eyre, Report};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::collections::HashMap;
+use tracing::{debug, info};
 
 pub fn decode(smooshedmorse_word: &str, wordlist: Option<&str>) -> Result<Vec<String>, Report> {
     let smooshedmorse_word = smooshedmorse_word.trim();
@@ -14,13 +18,13 @@ pub fn decode(smooshedmorse_word: &str, wordlist: Option<&str>) -> Result<Vec<St
 }
 
 pub fn decode_merse(
-    smooshedmerse_word: Vec<bool>,
+    smooshedmerse_word: Merse,
     wordlist: Option<&str>,
 ) -> Result<Vec<String>, Report> {
     let all_words: Vec<String> = get_all_words(wordlist)?;
 
     debug!("Converting all words to smooshedmorse...");
-    let all_merse_words = all_words_to_smooshedmerse(&all_words);
+    let all_merse_words = all_words_to_smooshedmerse(&all_words)?;
     debug!("Converting all words to smooshedmorse: done");
 
     debug!("Searching for corresponding words...");
@@ -34,25 +38,39 @@ pub fn decode_merse(
     Ok(res)
 }
 
-pub fn all_words_to_smooshedmerse(all_words: &[String]) -> Vec<Vec<bool>> {
-    // this is the slow function
-    let mut all_merse_words: Vec<Vec<bool>> = Vec::new();
-    for word in all_words {
-        let merse_word: Vec<bool> = word_to_smooshedmerse(word)
-            .expect("Word with forbidden characters present in the word list");
-        debug!("Converted: {}", word);
-        all_merse_words.push(merse_word);
-    }
-    all_merse_words
+/// Like `decode`, with an already loaded word list index, to decode many words
+pub fn decode_with_index(
+    smooshedmorse_word: &str,
+    index: &DecodeIndex,
+) -> Result<Vec<String>, Report> {
+    let smooshedmorse_word = smooshedmorse_word.trim();
+    debug!("Decoding: {}", smooshedmorse_word);
+    validate_morse_str(smooshedmorse_word)?;
+    let merse_word = smooshedmorse_to_merse(smooshedmorse_word)?;
+    Ok(index.get(&merse_word).to_vec())
+}
+
+/// Smooshedmerse of every word, failing on a word with characters missing from morse code
+pub fn all_words_to_smooshedmerse(all_words: &[String]) -> Result<Vec<Merse>, Report> {
+    // this is the slow function, spread on all threads with the parallel feature
+    #[cfg(feature = "parallel")]
+    let all_words = all_words.par_iter();
+    #[cfg(not(feature = "parallel"))]
+    let all_words = all_words.iter();
+    all_words
+        .map(|word| {
+            let merse_word: Merse = word_to_smooshedmerse(word)
+                .map_err(|e| eyre!("Word `{}` of the word list: {}", word, e))?;
+            debug!("Converted: {}", word);
+            Ok(merse_word)
+        })
+        .collect()
 }
 
-pub fn find_merse_corresponding_words(
-    merse_word: &[bool],
-    all_merse_words: &[Vec<bool>],
-) -> Vec<usize> {
+pub fn find_merse_corresponding_words(merse_word: &Merse, all_merse_words: &[Merse]) -> Vec<usize> {
     let mut indexes: Vec<usize> = Vec::new();
     for (i, word) in all_merse_words.iter().enumerate() {
-        if *word == merse_word {
+        if word == merse_word {
             debug!("Found: {:?} at {}", word, i);
             indexes.push(i);
         }
@@ -60,6 +78,65 @@ pub fn find_merse_corresponding_words(
     indexes
 }
 
+/// Word list indexed by smooshedmerse code, built once to decode many codes
+#[derive(Debug, Clone, Default)]
+pub struct DecodeIndex {
+    words: HashMap<Merse, Vec<String>>,
+    max_code_len: usize,
+}
+
+impl DecodeIndex {
+    pub fn new(all_words: &[String]) -> Result<Self, Report> {
+        let mut index = DecodeIndex::default();
+        for (word, merse_word) in all_words.iter().zip(all_words_to_smooshedmerse(all_words)?) {
+            index.max_code_len = index.max_code_len.max(merse_word.len());
+            index
+                .words
+                .entry(merse_word)
+                .or_insert_with(Vec::new)
+                .push(word.to_string());
+        }
+        Ok(index)
+    }
+
+    pub fn load(wordlist: Option<&str>) -> Result<Self, Report> {
+        let all_words: Vec<String> = get_all_words(wordlist)?;
+        info!("Indexing {} words...", all_words.len());
+        let index = DecodeIndex::new(&all_words)?;
+        info!("Indexing {} words: done", all_words.len());
+        Ok(index)
+    }
+
+    /// Words encoding to the given smooshedmerse, in word list order
+    pub fn get(&self, smooshedmerse_word: &Merse) -> &[String] {
+        match self.words.get(smooshedmerse_word) {
+            Some(words) => words,
+            None => &[],
+        }
+    }
+
+    /// Length of the longest code in the index
+    pub fn max_code_len(&self) -> usize {
+        self.max_code_len
+    }
+
+    /// Number of distinct codes
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Codes and their words, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (&Merse, &[String])> {
+        self.words
+            .iter()
+            .map(|(code, words)| (code, words.as_slice()))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -68,42 +145,78 @@ mod tests {
     fn test_find_merse_corresponding_words() {
         assert_eq!(
             find_merse_corresponding_words(
-                &vec![true, false],
-                &vec![
-                    vec![true, false],
-                    vec![false, true],
-                    vec![true, false],
-                    vec![true, false, true],
-                    vec![false],
+                &Merse::from(vec![true, false]),
+                &[
+                    Merse::from(vec![true, false]),
+                    Merse::from(vec![false, true]),
+                    Merse::from(vec![true, false]),
+                    Merse::from(vec![true, false, true]),
+                    Merse::from(vec![false]),
                 ]
             ),
             vec![0, 2]
         );
     }
 
+    #[test]
+    fn test_decode_index() {
+        let index =
+            DecodeIndex::new(&["caret".to_string(), "a".to_string(), "ceca".to_string()]).unwrap();
+        assert_eq!(
+            index.get(&Merse::from(vec![
+                true, false, true, false, false, true, false, true, false, false, true
+            ])),
+            &["caret".to_string(), "ceca".to_string()]
+        );
+        assert_eq!(
+            index.get(&Merse::from(vec![false, true])),
+            &["a".to_string()]
+        );
+        assert!(index.get(&Merse::from(vec![true])).is_empty());
+        assert_eq!(index.max_code_len(), 11);
+        assert_eq!(index.len(), 2);
+        assert_eq!(index.iter().map(|(_, words)| words.len()).sum::<usize>(), 3);
+        // an error rather than a panic on words that can't be encoded
+        let bad = DecodeIndex::new(&["a".to_string(), "don't".to_string()]).unwrap_err();
+        assert!(bad.to_string().contains("`don't`"));
+    }
+
+    #[test]
+    fn test_decode_with_index() {
+        let index =
+            DecodeIndex::new(&["caret".to_string(), "a".to_string(), "ceca".to_string()]).unwrap();
+        assert_eq!(
+            decode_with_index(" -.-..-.-..-\n", &index).unwrap(),
+            vec!["caret".to_string(), "ceca".to_string()]
+        );
+        assert!(decode_with_index("-", &index).unwrap().is_empty());
+        assert!(decode_with_index("-x", &index).is_err());
+    }
+
     #[test]
     fn test_all_words_to_smoshedmerse() {
         assert_eq!(
-            all_words_to_smooshedmerse(&vec![
+            all_words_to_smooshedmerse(&[
                 "Lancelot".to_string(),
                 "Ginevra".to_string(),
                 "a".to_string(),
                 "horse".to_string()
-            ]),
+            ])
+            .unwrap(),
             vec![
-                vec![
+                Merse::from(vec![
                     false, true, false, false, false, true, true, false, true, false, true, false,
                     false, false, true, false, false, true, true, true, true
-                ],
-                vec![
+                ]),
+                Merse::from(vec![
                     true, true, false, false, false, true, false, false, false, false, false, true,
                     false, true, false, false, true
-                ],
-                vec![false, true],
-                vec![
+                ]),
+                Merse::from(vec![false, true]),
+                Merse::from(vec![
                     false, false, false, false, true, true, true, false, true, false, false, false,
                     false, false
-                ]
+                ])
             ]
         );
     }
@@ -0,0 +1,456 @@
+//! Design of a morse-like code from the letter frequencies of a word list: the prefix-free
+//! code of dots and dashes with the shortest expected letter duration, so that smooshed
+//! letters split in a single way. Letters are timed by `timing::Timing`: by default a dot
+//! and its gap take 2 units and a dash and its gap 4, so it is no plain Huffman code. It is
+//! found by dynamic programming over the truncated code trees, level by level (Golin and
+//! Rote), a level being the largest unit both costs are whole multiples of.
+
+use crate::audio::Spacing;
+use crate::morses::{letter_index, ALPHABET, DASH, DOT, MORSE};
+use crate::records::Record;
+use crate::timing::Timing;
+use crate::wordlist::get_all_words;
+use color_eyre::{eyre::eyre, Report};
+use std::collections::{HashMap, VecDeque};
+use tracing::info;
+
+/// Most levels a dot or a dash may take, for the search to stay tractable
+pub const MAX_LEVELS: usize = 12;
+/// Largest denominator tried for the costs of a dot and a dash in levels
+const MAX_SCALE: usize = 100;
+
+/// Duration of a letter code and the gap after it, in dot units
+pub fn code_units(code: &str, timing: &Timing) -> f64 {
+    timing.letter_units(code) + timing.letter_gap_units(Spacing::Standard)
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    match b {
+        0 => a,
+        _ => gcd(b, a % b),
+    }
+}
+
+/// Levels of the code tree taken by a dot and by a dash, each with the gap after it
+pub fn symbol_levels(timing: &Timing) -> Result<[usize; 2], Report> {
+    let costs = [1.0, timing.dash].map(|mark| mark + timing.element_gap);
+    for scale in 1..=MAX_SCALE {
+        let scaled = costs.map(|cost| cost * scale as f64);
+        if scaled.iter().all(|cost| (cost - cost.round()).abs() < 1e-9) {
+            let levels = scaled.map(|cost| cost.round() as usize);
+            let divisor = gcd(levels[0], levels[1]);
+            let levels = levels.map(|level| level / divisor);
+            if levels.iter().all(|level| *level <= MAX_LEVELS) {
+                return Ok(levels);
+            }
+            break;
+        }
+    }
+    Err(eyre!(
+        "No code design for a dot and a dash of {} and {} units with their gaps: their ratio must be of whole numbers up to {}",
+        costs[0],
+        costs[1],
+        MAX_LEVELS
+    ))
+}
+
+/// Word and weight of a word list line: an optional frequency column follows the word
+pub fn parse_weighted(line: &str) -> Result<(&str, f64), Report> {
+    let mut columns = line.split_whitespace();
+    let word = columns.next().unwrap_or("");
+    let weight = match columns.next() {
+        Some(column) => column
+            .parse()
+            .map_err(|_| eyre!("Invalid frequency `{}` for word `{}`", column, word))?,
+        None => 1.0,
+    };
+    Ok((word, weight))
+}
+
+/// Weighted occurrences of each letter of the lines, indexed like ALPHABET. Other
+/// characters are ignored.
+pub fn letter_weights(lines: &[String]) -> Result<[f64; 26], Report> {
+    let mut weights = [0.0; 26];
+    for line in lines {
+        let (word, weight) = parse_weighted(line)?;
+        for i in word.chars().filter_map(letter_index) {
+            weights[i] += weight;
+        }
+    }
+    Ok(weights)
+}
+
+/// Cheapest completions of the truncated code trees. A tree truncated at a level has `m`
+/// leaves up to the level, for the `m` heaviest letters, and nodes pending below it: the
+/// children of its internal nodes of the last levels, as many as a dot or a dash takes.
+struct Designer<'a> {
+    /// Weights in decreasing order
+    weights: &'a [f64],
+    /// Weight of the letters after the first `m`
+    remaining: Vec<f64>,
+    /// Levels of a dot and of a dash
+    levels: [usize; 2],
+    /// Cost and number of nodes made internal at the next level, per signature: `m` and
+    /// the internal nodes of the last levels, the last one last
+    memo: HashMap<(usize, Vec<usize>), (f64, usize)>,
+}
+
+impl<'a> Designer<'a> {
+    fn new(weights: &'a [f64], levels: [usize; 2]) -> Self {
+        let n = weights.len();
+        let mut remaining = vec![0.0; n + 1];
+        for m in (0..n).rev() {
+            remaining[m] = remaining[m + 1] + weights[m];
+        }
+        Designer {
+            weights,
+            remaining,
+            levels,
+            memo: HashMap::new(),
+        }
+    }
+
+    /// Levels of the longest symbol, those whose internal nodes have pending children
+    fn span(&self) -> usize {
+        self.levels[0].max(self.levels[1])
+    }
+
+    /// Nodes of the next level: children of the internal nodes of the last levels
+    fn next_level(&self, internal: &[usize]) -> usize {
+        let span = internal.len();
+        self.levels.iter().map(|level| internal[span - level]).sum()
+    }
+
+    /// Nodes below the last level
+    fn pending(&self, internal: &[usize]) -> usize {
+        let span = internal.len();
+        internal
+            .iter()
+            .enumerate()
+            .map(|(k, count)| {
+                count
+                    * self
+                        .levels
+                        .iter()
+                        .filter(|level| **level + k >= span)
+                        .count()
+            })
+            .sum()
+    }
+
+    /// Cheapest cost of the remaining levels, and the choice reaching it. Going one level
+    /// down costs the weight of the letters not yet placed.
+    fn solve(&mut self, m: usize, internal: &[usize]) -> (f64, usize) {
+        let n = self.weights.len();
+        if m == n {
+            return (0.0, 0);
+        }
+        if let Some(solved) = self.memo.get(&(m, internal.to_vec())) {
+            return *solved;
+        }
+        let nodes = self.next_level(internal);
+        let mut best = (f64::INFINITY, 0);
+        for inner in 0..=nodes {
+            let placed = m + nodes - inner;
+            let mut next = internal[1..].to_vec();
+            next.push(inner);
+            // in an optimal tree every pending node holds a letter
+            let pending = self.pending(&next);
+            if placed > n || pending > n - placed || (pending == 0 && placed < n) {
+                continue;
+            }
+            let cost = self.remaining[m] + self.solve(placed, &next).0;
+            if cost < best.0 {
+                best = (cost, inner);
+            }
+        }
+        self.memo.insert((m, internal.to_vec()), best);
+        best
+    }
+
+    /// Codes of the letters, heaviest first, shallow leaves in code order
+    fn codes(&mut self) -> Vec<String> {
+        let n = self.weights.len();
+        let symbols = [DOT, DASH];
+        if n < 2 {
+            let cheapest = if self.levels[1] < self.levels[0] {
+                DASH
+            } else {
+                DOT
+            };
+            return vec![cheapest.to_string(); n];
+        }
+        let span = self.span();
+        // the root is the internal node of the top level
+        let mut internal = vec![0; span];
+        internal[span - 1] = 1;
+        // codes of the nodes of the levels below, the next one first
+        let mut below: VecDeque<Vec<String>> = vec![Vec::new(); span].into();
+        for (symbol, level) in symbols.iter().zip(self.levels) {
+            below[level - 1].push(symbol.to_string());
+        }
+        let mut codes: Vec<String> = Vec::with_capacity(n);
+        while codes.len() < n {
+            let (_, inner) = self.solve(codes.len(), &internal);
+            let mut level = below.pop_front().unwrap_or_default();
+            below.push_back(Vec::new());
+            // dot before dash, unlike their characters
+            level.sort_by(|a, b| {
+                a.chars()
+                    .map(|c| c == DASH)
+                    .cmp(b.chars().map(|c| c == DASH))
+            });
+            let parents = level.split_off(level.len() - inner);
+            codes.extend(level);
+            for parent in &parents {
+                for (symbol, level) in symbols.iter().zip(self.levels) {
+                    below[level - 1].push(format!("{}{}", parent, symbol));
+                }
+            }
+            internal.remove(0);
+            internal.push(inner);
+        }
+        codes
+    }
+}
+
+/// Prefix-free codes with the least expected duration under the timing, for weights in any
+/// order
+pub fn design(weights: &[f64], timing: &Timing) -> Result<Vec<String>, Report> {
+    let levels = symbol_levels(timing)?;
+    let mut order: Vec<usize> = (0..weights.len()).collect();
+    order.sort_by(|a, b| weights[*b].total_cmp(&weights[*a]).then(a.cmp(b)));
+    let sorted: Vec<f64> = order.iter().map(|i| weights[*i]).collect();
+    let mut codes = vec![String::new(); weights.len()];
+    for (i, code) in order
+        .into_iter()
+        .zip(Designer::new(&sorted, levels).codes())
+    {
+        codes[i] = code;
+    }
+    Ok(codes)
+}
+
+/// Mean duration of a letter under the codes
+pub fn mean_units(weights: &[f64], codes: &[impl AsRef<str>], timing: &Timing) -> f64 {
+    let total: f64 = weights.iter().sum();
+    if total == 0.0 {
+        return 0.0;
+    }
+    weights
+        .iter()
+        .zip(codes)
+        .map(|(weight, code)| weight * code_units(code.as_ref(), timing))
+        .sum::<f64>()
+        / total
+}
+
+/// Designed code of each letter, compared with the standard code, then the mean letter
+/// durations of both under the timing; and the code book, to write for `--code-book`
+pub fn run(words_file: Option<&str>, timing: &Timing) -> Result<(Vec<Record>, String), Report> {
+    timing.validate()?;
+    let lines: Vec<String> = get_all_words(words_file)?;
+    let weights = letter_weights(&lines)?;
+    info!("Designing a code for {} words...", lines.len());
+    let codes = design(&weights, timing)?;
+    info!("Designing a code for {} words: done", lines.len());
+    // the standard code, even under another code book
+    let standard: Vec<&str> = MORSE.split(' ').collect();
+    let total: f64 = weights.iter().sum();
+    let round = |x: f64| (x * 1e4).round() / 1e4;
+    let mut records: Vec<Record> = ALPHABET
+        .iter()
+        .enumerate()
+        .map(|(i, letter)| {
+            let frequency = if total == 0.0 {
+                0.0
+            } else {
+                weights[i] / total
+            };
+            Record::new()
+                .with("letter", letter.to_string())
+                .with("frequency", round(frequency))
+                .with("code", codes[i].clone())
+                .with("units", round(code_units(&codes[i], timing)))
+                .with("standard", standard[i])
+                .with("standard units", round(code_units(standard[i], timing)))
+        })
+        .collect();
+    records.push(
+        Record::new()
+            .with("letter", "mean")
+            .with("frequency", 1.0)
+            .with("code", "")
+            .with("units", round(mean_units(&weights, &codes, timing)))
+            .with("standard", "")
+            .with(
+                "standard units",
+                round(mean_units(&weights, &standard, timing)),
+            ),
+    );
+    Ok((records, codes.join(" ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::morses::parse_code_book;
+
+    fn is_prefix_free(codes: &[String]) -> bool {
+        codes.iter().enumerate().all(|(i, a)| {
+            codes
+                .iter()
+                .enumerate()
+                .all(|(j, b)| i == j || !b.starts_with(a.as_str()))
+        })
+    }
+
+    /// Cheapest expected cost of every full code tree, by brute force, for the costs of a
+    /// dot and a dash
+    fn brute_force(weights: &[f64], costs: [f64; 2]) -> f64 {
+        let mut sorted = weights.to_vec();
+        sorted.sort_by(|a, b| b.total_cmp(a));
+        // costs of the leaves of every full tree of n leaves, cheapest first
+        fn trees(n: usize, costs: [f64; 2]) -> Vec<Vec<f64>> {
+            if n == 1 {
+                return vec![vec![0.0]];
+            }
+            let mut all = Vec::new();
+            for left in 1..n {
+                for dots in trees(left, costs) {
+                    for dashes in trees(n - left, costs) {
+                        let mut leaves: Vec<f64> = dots.iter().map(|c| c + costs[0]).collect();
+                        leaves.extend(dashes.iter().map(|c| c + costs[1]));
+                        leaves.sort_by(f64::total_cmp);
+                        all.push(leaves);
+                    }
+                }
+            }
+            all
+        }
+        trees(sorted.len(), costs)
+            .iter()
+            .map(|leaves| sorted.iter().zip(leaves).map(|(w, c)| w * c).sum())
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    #[test]
+    fn test_code_units() {
+        let timing = Timing::default();
+        assert_eq!(code_units(".", &timing), 4.0);
+        assert_eq!(code_units("-", &timing), 6.0);
+        assert_eq!(code_units(".-", &timing), 8.0);
+        assert_eq!(code_units("...", &timing), 8.0);
+        let long = Timing {
+            dash: 4.0,
+            letter_gap: 5.0,
+            ..Timing::default()
+        };
+        assert_eq!(code_units(".-", &long), 11.0);
+    }
+
+    #[test]
+    fn test_symbol_levels() {
+        let levels = |dash: f64, element_gap: f64| {
+            symbol_levels(&Timing {
+                dash,
+                element_gap,
+                ..Timing::default()
+            })
+        };
+        assert_eq!(levels(3.0, 1.0).unwrap(), [1, 2]);
+        assert_eq!(levels(4.0, 1.0).unwrap(), [2, 5]);
+        assert_eq!(levels(3.5, 0.5).unwrap(), [3, 8]);
+        assert_eq!(levels(0.5, 0.5).unwrap(), [3, 2]);
+        assert!(levels(3.0, 0.123).is_err());
+        assert!(levels(std::f64::consts::PI, 1.0).is_err());
+    }
+
+    #[test]
+    fn test_parse_weighted() {
+        assert_eq!(parse_weighted("horse").unwrap(), ("horse", 1.0));
+        assert_eq!(parse_weighted("the\t2300").unwrap(), ("the", 2300.0));
+        assert_eq!(parse_weighted("of 0.5").unwrap(), ("of", 0.5));
+        assert!(parse_weighted("of many").is_err());
+        let lines = vec!["ab 2".to_string(), "Ba".to_string()];
+        let weights = letter_weights(&lines).unwrap();
+        assert_eq!((weights[0], weights[1], weights[2]), (3.0, 3.0, 0.0));
+    }
+
+    #[test]
+    fn test_design() {
+        let timing = Timing::default();
+        assert!(design(&[], &timing).unwrap().is_empty());
+        assert_eq!(design(&[5.0], &timing).unwrap(), vec!["."]);
+        assert_eq!(design(&[1.0, 2.0], &timing).unwrap(), vec!["-", "."]);
+        // .. - .- (7 levels) beats . -. -- (8 levels)
+        assert_eq!(
+            design(&[1.0, 1.0, 1.0], &timing).unwrap(),
+            vec!["..", "-", ".-"]
+        );
+        // with dashes as short as dots, any full tree: . -. -- here
+        let even = Timing {
+            dash: 1.0,
+            ..Timing::default()
+        };
+        assert_eq!(
+            design(&[2.0, 1.0, 1.0], &even).unwrap(),
+            vec![".", "-.", "--"]
+        );
+        for timing in [
+            timing,
+            even,
+            Timing {
+                dash: 4.0,
+                letter_gap: 5.0,
+                ..Timing::default()
+            },
+            Timing {
+                dash: 5.5,
+                element_gap: 0.5,
+                ..Timing::default()
+            },
+            Timing {
+                dash: 0.5,
+                ..Timing::default()
+            },
+        ] {
+            let costs = [1.0, timing.dash].map(|mark| mark + timing.element_gap);
+            for weights in [
+                vec![1.0, 1.0, 1.0, 1.0, 1.0],
+                vec![10.0, 1.0, 3.0, 7.0, 2.0, 2.0, 9.0],
+                vec![0.0, 0.0, 4.0, 1.0, 0.0, 30.0, 2.0, 1.0],
+            ] {
+                let codes = design(&weights, &timing).unwrap();
+                assert!(is_prefix_free(&codes));
+                // a letter lasts its symbols with their gaps, but the last element gap,
+                // then the letter gap
+                let total: f64 = weights.iter().sum();
+                let gap = timing.letter_gap - timing.element_gap;
+                let cost = (mean_units(&weights, &codes, &timing) - gap) * total;
+                assert!((cost - brute_force(&weights, costs)).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn test_run() {
+        let (records, code_book) = run(Some("tests/wordlist"), &Timing::default()).unwrap();
+        assert_eq!(records.len(), 27);
+        let codes = parse_code_book(&code_book).unwrap();
+        assert!(is_prefix_free(&codes));
+        let mean = records.last().unwrap();
+        assert_eq!(mean.get("letter").unwrap(), "mean");
+        assert!(mean.get("units").unwrap().as_f64().unwrap() > 0.0);
+        // longer dashes give codes of fewer dashes
+        let dashes = |timing: &Timing| {
+            let (_, code_book) = run(Some("tests/wordlist"), timing).unwrap();
+            code_book.matches(DASH).count()
+        };
+        let slow = Timing {
+            dash: 6.0,
+            ..Timing::default()
+        };
+        assert!(dashes(&slow) < dashes(&Timing::default()));
+    }
+}
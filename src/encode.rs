@@ -1,6 +1,8 @@
 use crate::merses::char_to_merse;
 use crate::merses::merse_to_morse;
+use crate::merses::Merse;
 use crate::morses::char_to_morse;
+use crate::unicode::fold;
 use color_eyre::{eyre::eyre, Report};
 use tracing::{debug, trace};
 
@@ -10,31 +12,36 @@ pub fn encode(word: &str) -> Result<Vec<String>, Report> {
     Ok(vec![merse_to_morse(&smooshedmerse)])
 }
 
-pub fn word_to_smooshedmerse(word: &str) -> Result<Vec<bool>, Report> {
-    validate_ascii(&word)?;
-    let mut merse: Vec<Vec<bool>> = Vec::new();
+/// Merse of a word of ASCII letters or ITU extension letters, in any case
+pub fn word_to_smooshedmerse(word: &str) -> Result<Merse, Report> {
+    let folded;
+    let word = match word.is_ascii() {
+        true => word,
+        false => {
+            folded = fold(word);
+            folded.as_str()
+        }
+    };
+    let mut encoded = Merse::new();
     for ch in word.chars() {
         let mc = char_to_merse(ch)?;
-        merse.push(mc);
+        encoded.extend_from_merse(&mc);
     }
-    let encoded: Vec<bool> = merse.into_iter().flatten().collect();
     trace!("{}->{:?}", word, encoded);
     Ok(encoded)
 }
 
 pub fn word_to_smooshedmorse(word: &str) -> Result<String, Report> {
-    validate_ascii(&word)?;
-    let mut morse_vec: Vec<String> = Vec::new();
-    for ch in word.chars() {
-        let mc = char_to_morse(ch);
-        morse_vec.push(mc);
-    }
-    let encoded: String = morse_vec.into_iter().collect();
-    Ok(encoded)
+    Ok(word_codes(word)?.concat())
+}
+
+/// Codes of the letters of a word of ASCII letters or ITU extension letters, in any case
+pub fn word_codes(word: &str) -> Result<Vec<&'static str>, Report> {
+    fold(word).chars().map(char_to_morse).collect()
 }
 
 pub fn validate_ascii(word: &str) -> Result<(), Report> {
-    if !word.chars().all(char::is_alphabetic) {
+    if !word.chars().all(|ch| ch.is_ascii_alphabetic()) {
         return Err(eyre!(
             "Word doesn't contain only ASCII alphabetic characters: `{}`",
             word
@@ -57,6 +64,26 @@ mod tests {
         assert!(validate_ascii("AB♡").is_err());
         assert!(validate_ascii("Sandro ").is_err());
         assert!(validate_ascii("S C").is_err());
+        assert!(validate_ascii("Crème").is_err());
+    }
+
+    #[test]
+    fn test_accented() {
+        assert_eq!(word_to_smooshedmorse("Crème").unwrap(), "-.-..-..-..---.");
+        assert_eq!(
+            word_to_smooshedmorse("CRE\u{300}ME").unwrap(),
+            "-.-..-..-..---."
+        );
+        assert_eq!(
+            word_to_smooshedmerse("SEÑOR").unwrap(),
+            "....--.-----.-.".parse().unwrap()
+        );
+        assert_eq!(
+            word_codes("Über").unwrap(),
+            vec!["..--", "-...", ".", ".-."]
+        );
+        assert!(word_to_smooshedmorse("Straße").is_err());
+        assert!(word_to_smooshedmerse("东京").is_err());
     }
 
     #[test]
@@ -74,13 +101,13 @@ mod tests {
     fn test_word_to_smooshedmerse() {
         assert!(word_to_smooshedmerse("a ").is_err());
         assert!(word_to_smooshedmerse("b b").is_err());
-        assert_eq!(word_to_smooshedmerse("").unwrap(), Vec::<bool>::new());
+        assert_eq!(word_to_smooshedmerse("").unwrap(), Merse::new());
         assert_eq!(
             word_to_smooshedmerse("Carlotta").unwrap(),
-            vec![
+            Merse::from(vec![
                 true, false, true, false, false, true, false, true, false, false, true, false,
                 false, true, true, true, true, true, false, true
-            ]
+            ])
         );
     }
 }
@@ -1,8 +1,12 @@
 use crate::decode::all_words_to_smooshedmerse;
 use crate::decode::find_merse_corresponding_words;
 use crate::merses::merse_to_morse;
+use crate::merses::Merse;
+use crate::records::Record;
 use crate::wordlist::get_all_words;
 use color_eyre::Report;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use std::collections::HashMap;
 use tracing::{info, trace};
 
@@ -12,48 +16,70 @@ const STARTING_FREQUENCY: u32 = 0; // higher to optimize search, 12 is the highe
 
 /// The sequence -...-....-.--. is the code for four different words (needing, nervate,
 /// niding, tiling). Find the only sequence that's the code for 13 different words.
-pub fn run() -> Result<Vec<String>, Report> {
+pub fn run() -> Result<Vec<Record>, Report> {
     let all_words: Vec<String> = get_all_words(None)?;
 
     info!("Converting all words to smooshedmorse...");
-    let all_merse_words = all_words_to_smooshedmerse(&all_words);
+    let all_merse_words = all_words_to_smooshedmerse(&all_words)?;
     info!("Converting all words to smooshedmorse: done");
 
     info!("Counting occurrences...");
     let counted = count_smooshedmorse(&all_merse_words);
     let most_frequent = get_higher_counted(counted);
 
-    // display corresponding words in log
+    let mut records: Vec<Record> = Vec::new();
     for code in most_frequent.iter() {
         let corresponding_positions: Vec<usize> =
-            find_merse_corresponding_words(&code, &all_merse_words);
+            find_merse_corresponding_words(code, &all_merse_words);
         info!("Found: {:?}", corresponding_positions);
         let mut decoded: Vec<String> = Vec::new();
         for i in corresponding_positions {
             decoded.push(all_words[i].to_string());
         }
-        info!("{}: {:?}", merse_to_morse(&code), decoded);
+        info!("{}: {:?}", merse_to_morse(code), decoded);
+        records.push(
+            Record::new()
+                .with("code", merse_to_morse(code))
+                .with("length", code.len())
+                .with("count", decoded.len())
+                .with("words", decoded),
+        );
     }
-
-    let morse_most_frequent: Vec<String> =
-        most_frequent.iter().map(|m| merse_to_morse(m)).collect();
-    info!("Most frequently occurring codes: {:?}", morse_most_frequent);
-    Ok(morse_most_frequent)
+    Ok(records)
 }
 
 /// Return a map of each input code and his frequency
-fn count_smooshedmorse(all_merse_words: &[Vec<bool>]) -> HashMap<Vec<bool>, u32> {
-    let mut map: HashMap<Vec<bool>, u32> = HashMap::new();
+#[cfg(not(feature = "parallel"))]
+fn count_smooshedmorse(all_merse_words: &[Merse]) -> HashMap<Merse, u32> {
+    let mut map: HashMap<Merse, u32> = HashMap::new();
     for c in all_merse_words.iter().cloned() {
         *map.entry(c).or_insert(0) += 1;
     }
     map
 }
 
+/// Return a map of each input code and his frequency, counting chunks on all threads and
+/// then merging the partial maps
+#[cfg(feature = "parallel")]
+fn count_smooshedmorse(all_merse_words: &[Merse]) -> HashMap<Merse, u32> {
+    all_merse_words
+        .par_iter()
+        .fold(HashMap::new, |mut map: HashMap<Merse, u32>, c| {
+            *map.entry(c.clone()).or_insert(0) += 1;
+            map
+        })
+        .reduce(HashMap::new, |mut map, partial| {
+            for (c, count) in partial {
+                *map.entry(c).or_insert(0) += count;
+            }
+            map
+        })
+}
+
 /// Return all merse codes that have the highest frequency
-fn get_higher_counted(merse_count_map: HashMap<Vec<bool>, u32>) -> Vec<Vec<bool>> {
+fn get_higher_counted(merse_count_map: HashMap<Merse, u32>) -> Vec<Merse> {
     let mut higher_count: u32 = STARTING_FREQUENCY;
-    let mut higher_codes: Vec<Vec<bool>> = Vec::new();
+    let mut higher_codes: Vec<Merse> = Vec::new();
     for (m, c) in merse_count_map {
         if let Some(max) = MAX_FREQUENCY_ALLOWED {
             if c > max {
@@ -70,6 +96,8 @@ fn get_higher_counted(merse_count_map: HashMap<Vec<bool>, u32>) -> Vec<Vec<bool>
             higher_codes.push(m);
         }
     }
+    // the map iteration order is random
+    higher_codes.sort();
     higher_codes
 }
 
@@ -79,20 +107,20 @@ mod tests {
 
     #[test]
     fn test_count_smooshedmorse() {
-        let mut testmap: HashMap<Vec<bool>, u32> = HashMap::new();
-        testmap.insert(vec![false, false, false], 1);
-        testmap.insert(vec![false, true], 1);
-        testmap.insert(vec![true], 2);
-        testmap.insert(vec![true, false], 3);
+        let mut testmap: HashMap<Merse, u32> = HashMap::new();
+        testmap.insert(Merse::from(vec![false, false, false]), 1);
+        testmap.insert(Merse::from(vec![false, true]), 1);
+        testmap.insert(Merse::from(vec![true]), 2);
+        testmap.insert(Merse::from(vec![true, false]), 3);
         assert_eq!(
-            count_smooshedmorse(&vec![
-                vec![true],
-                vec![true, false],
-                vec![true, false],
-                vec![false, true],
-                vec![false, false, false],
-                vec![true, false],
-                vec![true]
+            count_smooshedmorse(&[
+                Merse::from(vec![true]),
+                Merse::from(vec![true, false]),
+                Merse::from(vec![true, false]),
+                Merse::from(vec![false, true]),
+                Merse::from(vec![false, false, false]),
+                Merse::from(vec![true, false]),
+                Merse::from(vec![true])
             ]),
             testmap
         );
@@ -100,11 +128,14 @@ mod tests {
 
     #[test]
     fn test_get_higher_counted() {
-        let mut testmap: HashMap<Vec<bool>, u32> = HashMap::new();
-        testmap.insert(vec![false, false, false], 1);
-        testmap.insert(vec![false, true], 1);
-        testmap.insert(vec![true], 2);
-        testmap.insert(vec![true, false], 3);
-        assert_eq!(get_higher_counted(testmap), vec![vec![true, false]]);
+        let mut testmap: HashMap<Merse, u32> = HashMap::new();
+        testmap.insert(Merse::from(vec![false, false, false]), 1);
+        testmap.insert(Merse::from(vec![false, true]), 1);
+        testmap.insert(Merse::from(vec![true]), 2);
+        testmap.insert(Merse::from(vec![true, false]), 3);
+        assert_eq!(
+            get_higher_counted(testmap),
+            vec![Merse::from(vec![true, false])]
+        );
     }
 }
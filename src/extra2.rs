@@ -1,6 +1,8 @@
 use crate::decode::all_words_to_smooshedmerse;
 use crate::decode::find_merse_corresponding_words;
 use crate::merses::merse_to_morse;
+use crate::merses::Merse;
+use crate::records::Record;
 use crate::wordlist::get_all_words;
 use color_eyre::Report;
 use tracing::info;
@@ -9,14 +11,14 @@ const DASHES_NUMBER_SEARCHED: u32 = 15;
 
 /// autotomous encodes to .-..--------------..-..., which has 14 dashes in a row. Find
 /// the only word that has 15 dashes in a row.
-pub fn run() -> Result<Vec<String>, Report> {
+pub fn run() -> Result<Vec<Record>, Report> {
     let all_words: Vec<String> = get_all_words(None)?;
 
     info!("Converting all words to smooshedmorse...");
-    let all_merse_words = all_words_to_smooshedmerse(&all_words);
+    let all_merse_words = all_words_to_smooshedmerse(&all_words)?;
     info!("Converting all words to smooshedmorse: done");
 
-    let many_dashes_word: &Vec<bool> = find_first_with_many_dashes(&all_merse_words)
+    let many_dashes_word: &Merse = find_first_with_many_dashes(&all_merse_words)
         .expect("Couldn't find a word with so many dashes!");
     let morse_many_dashes_word: String = merse_to_morse(many_dashes_word);
 
@@ -34,23 +36,23 @@ pub fn run() -> Result<Vec<String>, Report> {
         decoded.push(all_words[i].to_string());
     }
     info!("{}: {:?}", morse_many_dashes_word, decoded);
-    Ok(vec![morse_many_dashes_word])
+    Ok(vec![Record::new()
+        .with("code", morse_many_dashes_word)
+        .with("dashes", count_consecutive_dashes(many_dashes_word))
+        .with("words", decoded)])
 }
 
-fn find_first_with_many_dashes(all_merse_words: &[Vec<bool>]) -> Option<&Vec<bool>> {
-    for word in all_merse_words {
-        if count_consecutive_dashes(word) >= DASHES_NUMBER_SEARCHED {
-            return Some(word);
-        }
-    }
-    None
+fn find_first_with_many_dashes(all_merse_words: &[Merse]) -> Option<&Merse> {
+    all_merse_words
+        .iter()
+        .find(|word| count_consecutive_dashes(word) >= DASHES_NUMBER_SEARCHED)
 }
 
-fn count_consecutive_dashes(merse_word: &[bool]) -> u32 {
+fn count_consecutive_dashes(merse_word: &Merse) -> u32 {
     let mut count: u32 = 0;
     let mut higher_count: u32 = 0;
     for i in merse_word {
-        if *i {
+        if i {
             count += 1
         } else {
             count = 0
@@ -67,33 +69,42 @@ mod tests {
     use super::*;
     #[test]
     fn test_count_consecutive_dashes() {
-        assert_eq!(count_consecutive_dashes(&vec![]), 0);
-        assert_eq!(count_consecutive_dashes(&vec![false, false, false]), 0);
-        assert_eq!(count_consecutive_dashes(&vec![false, true, false]), 1);
+        assert_eq!(count_consecutive_dashes(&Merse::new()), 0);
+        assert_eq!(
+            count_consecutive_dashes(&Merse::from(vec![false, false, false])),
+            0
+        );
         assert_eq!(
-            count_consecutive_dashes(&vec![true, false, true, true, true, false]),
+            count_consecutive_dashes(&Merse::from(vec![false, true, false])),
+            1
+        );
+        assert_eq!(
+            count_consecutive_dashes(&Merse::from(vec![true, false, true, true, true, false])),
             3
         );
     }
     #[test]
     fn test_find_first_with_many_dashes() {
-        assert_eq!(find_first_with_many_dashes(&vec![]), None);
+        assert_eq!(find_first_with_many_dashes(&[]), None);
         assert_eq!(
-            find_first_with_many_dashes(&vec![vec![false, false], vec![true, false, true]]),
+            find_first_with_many_dashes(&[
+                Merse::from(vec![false, false]),
+                Merse::from(vec![true, false, true])
+            ]),
             None
         );
-        let mdw: Vec<bool> = vec![
+        let mdw: Merse = Merse::from(vec![
             true, true, true, true, true, true, true, true, true, true, true, true, true, true,
             true,
-        ];
+        ]);
         assert_eq!(
-            find_first_with_many_dashes(&vec![
-                vec![false, false],
-                vec![true, false, true],
-                vec![
+            find_first_with_many_dashes(&[
+                Merse::from(vec![false, false]),
+                Merse::from(vec![true, false, true]),
+                Merse::from(vec![
                     true, true, true, true, true, true, true, true, true, true, true, true, true,
                     true, true,
-                ]
+                ])
             ]),
             Some(&mdw)
         );
@@ -1,7 +1,11 @@
 use crate::decode::all_words_to_smooshedmerse;
 use crate::merses::merse_to_morse;
+use crate::merses::Merse;
+use crate::records::Record;
 use crate::wordlist::get_all_words;
 use color_eyre::Report;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use tracing::info;
 
 const LETTERS_NUMBER: usize = 21;
@@ -9,31 +13,34 @@ const LETTERS_NUMBER: usize = 21;
 /// Call a word perfectly balanced if its code has the same number of dots as dashes.
 /// counterdemonstrations is one of two 21-letter words that's perfectly balanced. Find
 /// the other one.
-pub fn run() -> Result<Vec<String>, Report> {
+pub fn run() -> Result<Vec<Record>, Report> {
     let all_words: Vec<String> = get_all_words(None)?;
 
     let filtered_words = filter_words_by_lenght(all_words, LETTERS_NUMBER);
 
     info!("Converting all words to smooshedmorse...");
-    let merse_words = all_words_to_smooshedmerse(&filtered_words);
+    let merse_words = all_words_to_smooshedmerse(&filtered_words)?;
     info!("Converting all words to smooshedmorse: done");
 
     let balanced_merse: Vec<usize> = find_balanced(&merse_words);
-    let mut balanced_words: Vec<String> = Vec::new();
-    for i in balanced_merse.iter().copied() {
-        balanced_words.push(filtered_words[i].clone())
-    }
-
+    let mut records: Vec<Record> = Vec::new();
     for i in balanced_merse {
         info!("Found balanced: {}", merse_to_morse(&merse_words[i]));
+        records.push(
+            Record::new()
+                .with("word", filtered_words[i].clone())
+                .with("code", merse_to_morse(&merse_words[i]))
+                .with("dots", merse_words[i].count_dots())
+                .with("dashes", merse_words[i].count_dashes()),
+        );
     }
 
-    Ok(balanced_words)
+    Ok(records)
 }
 
 pub fn filter_words_by_lenght(words: Vec<String>, letters: usize) -> Vec<String> {
     let mut given_lenght_words: Vec<String> = Vec::new();
-    for word in words.iter().cloned() {
+    for word in words.iter() {
         let letterscount: usize = word.chars().count();
         if letterscount == letters {
             given_lenght_words.push(word.clone())
@@ -42,32 +49,21 @@ pub fn filter_words_by_lenght(words: Vec<String>, letters: usize) -> Vec<String>
     given_lenght_words
 }
 
-fn find_balanced(smooshedmerses: &[Vec<bool>]) -> Vec<usize> {
-    let mut indexes: Vec<usize> = Vec::new();
-    for (i, w) in smooshedmerses.iter().enumerate() {
-        if is_balanced(w) {
-            indexes.push(i)
-        }
-    }
-    indexes
+fn find_balanced(smooshedmerses: &[Merse]) -> Vec<usize> {
+    #[cfg(feature = "parallel")]
+    let smooshedmerses = smooshedmerses.par_iter();
+    #[cfg(not(feature = "parallel"))]
+    let smooshedmerses = smooshedmerses.iter();
+    smooshedmerses
+        .enumerate()
+        .filter(|(_, w)| is_balanced(w))
+        .map(|(i, _)| i)
+        .collect()
 }
 
-fn is_balanced(smooshedmerse: &[bool]) -> bool {
-    // Return true if given smooshed word has same number of true and false
-    // Rely on dots being false and dashes being true
-    let mut dotcount: u32 = 0;
-    let mut dashcount: u32 = 0;
-    for elem in smooshedmerse.iter().copied() {
-        if elem {
-            dashcount += 1;
-        } else {
-            dotcount += 1;
-        }
-    }
-    if dotcount == dashcount {
-        return true;
-    }
-    false
+fn is_balanced(smooshedmerse: &Merse) -> bool {
+    // Return true if given smooshed word has same number of dots and dashes
+    smooshedmerse.count_dots() == smooshedmerse.count_dashes()
 }
 
 #[cfg(test)]
@@ -76,12 +72,12 @@ mod tests {
     #[test]
     fn test_find_balanced() {
         assert_eq!(
-            find_balanced(&vec![
-                vec![true],
-                vec![true, false, false],
-                vec![true, false],
-                vec![false],
-                vec![true, false, true, false, false, true],
+            find_balanced(&[
+                Merse::from(vec![true]),
+                Merse::from(vec![true, false, false]),
+                Merse::from(vec![true, false]),
+                Merse::from(vec![false]),
+                Merse::from(vec![true, false, true, false, false, true]),
             ]),
             vec![2, 4]
         );
@@ -89,11 +85,15 @@ mod tests {
 
     #[test]
     fn test_is_balanced() {
-        assert!(is_balanced(&vec![true, false, true, true, false, false]));
-        assert!(is_balanced(&vec![]));
-        assert!(!is_balanced(&vec![true, true, true, false, false]));
-        assert!(!is_balanced(&vec![true]));
-        assert!(!is_balanced(&vec![false]));
+        assert!(is_balanced(&Merse::from(vec![
+            true, false, true, true, false, false
+        ])));
+        assert!(is_balanced(&Merse::new()));
+        assert!(!is_balanced(&Merse::from(vec![
+            true, true, true, false, false
+        ])));
+        assert!(!is_balanced(&Merse::from(vec![true])));
+        assert!(!is_balanced(&Merse::from(vec![false])));
     }
 
     #[test]
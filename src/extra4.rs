@@ -1,8 +1,12 @@
 use crate::decode::all_words_to_smooshedmerse;
 use crate::extra3::filter_words_by_lenght;
 use crate::merses::merse_to_morse;
+use crate::merses::Merse;
+use crate::records::Record;
 use crate::wordlist::get_all_words;
 use color_eyre::Report;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use tracing::info;
 
 const LETTERS_NUMBER: usize = 6;
@@ -10,46 +14,44 @@ const LETTERS_NUMBER: usize = 6;
 /// protectorate is 12 letters long and encodes to .--..-.----.-.-.----.-..--., which is
 /// a palindrome (i.e. the string is the same when reversed). Find the only 13-letter
 /// word that encodes to a palindrome.
-pub fn run() -> Result<Vec<String>, Report> {
+pub fn run() -> Result<Vec<Record>, Report> {
     let all_words: Vec<String> = get_all_words(None)?;
 
     let filtered_words = filter_words_by_lenght(all_words, LETTERS_NUMBER);
 
     info!("Converting all words to smooshedmorse...");
-    let merse_words = all_words_to_smooshedmerse(&filtered_words);
+    let merse_words = all_words_to_smooshedmerse(&filtered_words)?;
     info!("Converting all words to smooshedmorse: done");
 
     let palindrome_merse: Vec<usize> = find_palindrome(&merse_words);
-    let mut palindrome_words: Vec<String> = Vec::new();
-    for i in palindrome_merse.iter().copied() {
-        palindrome_words.push(filtered_words[i].clone())
-    }
-
+    let mut records: Vec<Record> = Vec::new();
     for i in palindrome_merse {
         info!("Found palindrome: {}", merse_to_morse(&merse_words[i]));
+        records.push(
+            Record::new()
+                .with("word", filtered_words[i].clone())
+                .with("code", merse_to_morse(&merse_words[i]))
+                .with("length", merse_words[i].len()),
+        );
     }
 
-    Ok(palindrome_words)
+    Ok(records)
 }
 
-fn find_palindrome(smooshedmerses: &[Vec<bool>]) -> Vec<usize> {
-    let mut indexes: Vec<usize> = Vec::new();
-    for (i, w) in smooshedmerses.iter().enumerate() {
-        if is_palindrome(w) {
-            indexes.push(i)
-        }
-    }
-    indexes
+fn find_palindrome(smooshedmerses: &[Merse]) -> Vec<usize> {
+    #[cfg(feature = "parallel")]
+    let smooshedmerses = smooshedmerses.par_iter();
+    #[cfg(not(feature = "parallel"))]
+    let smooshedmerses = smooshedmerses.iter();
+    smooshedmerses
+        .enumerate()
+        .filter(|(_, w)| is_palindrome(w))
+        .map(|(i, _)| i)
+        .collect()
 }
 
-fn is_palindrome(smooshedmerse: &[bool]) -> bool {
-    // Rely on dots being false and dashes being true
-    for (d, i) in smooshedmerse.iter().zip(smooshedmerse.iter().rev()) {
-        if d != i {
-            return false;
-        }
-    }
-    true
+fn is_palindrome(smooshedmerse: &Merse) -> bool {
+    smooshedmerse.iter().eq(smooshedmerse.iter().rev())
 }
 
 #[cfg(test)]
@@ -58,12 +60,12 @@ mod tests {
     #[test]
     fn test_find_palindrome() {
         assert_eq!(
-            find_palindrome(&vec![
-                vec![true, false],
-                vec![true, false, false],
-                vec![true, false, true],
-                vec![false, true, true, true],
-                vec![true, false, false, true],
+            find_palindrome(&[
+                Merse::from(vec![true, false]),
+                Merse::from(vec![true, false, false]),
+                Merse::from(vec![true, false, true]),
+                Merse::from(vec![false, true, true, true]),
+                Merse::from(vec![true, false, false, true]),
             ]),
             vec![2, 4]
         );
@@ -71,12 +73,16 @@ mod tests {
 
     #[test]
     fn test_is_palindrome() {
-        assert!(is_palindrome(&vec![true, false, true, true, false, true]));
-        assert!(is_palindrome(&vec![true, true]));
-        assert!(is_palindrome(&vec![false, true, false]));
-        assert!(is_palindrome(&vec![false]));
-        assert!(is_palindrome(&vec![]));
-        assert!(!is_palindrome(&vec![true, true, true, false, false]));
-        assert!(!is_palindrome(&vec![false, true]));
+        assert!(is_palindrome(&Merse::from(vec![
+            true, false, true, true, false, true
+        ])));
+        assert!(is_palindrome(&Merse::from(vec![true, true])));
+        assert!(is_palindrome(&Merse::from(vec![false, true, false])));
+        assert!(is_palindrome(&Merse::from(vec![false])));
+        assert!(is_palindrome(&Merse::new()));
+        assert!(!is_palindrome(&Merse::from(vec![
+            true, true, true, false, false
+        ])));
+        assert!(!is_palindrome(&Merse::from(vec![false, true])));
     }
 }
@@ -0,0 +1,238 @@
+//! Glyphs of dots and dashes: morse pasted from elsewhere (`·−`, `•–`, `._`...) is read
+//! leniently, and morse is rendered in other styles (`·−`, `dit-dah`, `01`, any pair).
+
+use crate::morses::{DASH, DOT};
+use crate::spaced::Separators;
+use color_eyre::{eyre::eyre, Report};
+use std::str::FromStr;
+use tracing::trace;
+
+/// Characters read as dots by default: full stop, middle dot, bullet, bullet operator
+pub const DOT_GLYPHS: &str = ".\u{b7}\u{2022}\u{2219}";
+/// Characters read as dashes by default: hyphen, minus sign, en dash, em dash, underscore
+pub const DASH_GLYPHS: &str = "-\u{2212}\u{2013}\u{2014}_";
+pub const STYLES: [&str; 4] = ["ascii", "unicode", "words", "binary"];
+
+/// Accepted dot and dash glyphs of morse input
+#[derive(Debug, Clone, PartialEq)]
+pub struct Glyphs {
+    dots: Vec<char>,
+    dashes: Vec<char>,
+    /// Remove whitespace inside the input too, not only around it
+    strip_whitespace: bool,
+}
+
+impl Default for Glyphs {
+    fn default() -> Self {
+        Glyphs::new(DOT_GLYPHS, DASH_GLYPHS, false).expect("default glyphs are valid")
+    }
+}
+
+impl Glyphs {
+    /// `.` and `-` are always accepted, besides the given glyphs
+    pub fn new(dots: &str, dashes: &str, strip_whitespace: bool) -> Result<Self, Report> {
+        let mut glyphs = Glyphs {
+            dots: vec![DOT],
+            dashes: vec![DASH],
+            strip_whitespace,
+        };
+        for (set, chars) in [(&mut glyphs.dots, dots), (&mut glyphs.dashes, dashes)] {
+            for ch in chars.chars() {
+                if ch.is_whitespace() {
+                    return Err(eyre!("Whitespace can't be a dot or a dash glyph"));
+                }
+                if !set.contains(&ch) {
+                    set.push(ch);
+                }
+            }
+        }
+        if let Some(ch) = glyphs.dots.iter().find(|ch| glyphs.dashes.contains(ch)) {
+            return Err(eyre!("`{}` can't be both a dot and a dash glyph", ch));
+        }
+        Ok(glyphs)
+    }
+
+    pub fn is_glyph(&self, ch: char) -> bool {
+        self.dots.contains(&ch) || self.dashes.contains(&ch)
+    }
+
+    /// Input with its glyphs turned to `.` and `-` and trimmed, other characters are kept
+    /// for validation to report
+    pub fn normalize(&self, input: &str) -> String {
+        let normalized: String = input
+            .trim()
+            .chars()
+            .filter(|ch| !(self.strip_whitespace && ch.is_whitespace()))
+            .map(|ch| {
+                if self.dots.contains(&ch) {
+                    DOT
+                } else if self.dashes.contains(&ch) {
+                    DASH
+                } else {
+                    ch
+                }
+            })
+            .collect();
+        trace!("{:?}->{}", input, normalized);
+        normalized
+    }
+
+    /// Separators of spaced morse input must not contain glyphs, nor whitespace when it is
+    /// stripped
+    pub fn validate_separators(&self, separators: &Separators) -> Result<(), Report> {
+        for sep in [&separators.letter, &separators.word].iter() {
+            if sep.chars().any(|ch| self.is_glyph(ch)) {
+                return Err(eyre!("Separator `{}` contains a dot or dash glyph", sep));
+            }
+            if self.strip_whitespace && sep.chars().any(char::is_whitespace) {
+                return Err(eyre!(
+                    "Separator `{}` contains whitespace, which --strip-whitespace removes",
+                    sep
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Rendering of dots and dashes
+#[derive(Debug, Clone, PartialEq)]
+pub struct Style {
+    dot: String,
+    dash: String,
+    /// Between the elements of a letter or smooshed word
+    element_separator: String,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style::custom(&DOT.to_string(), &DASH.to_string()).expect("default style is valid")
+    }
+}
+
+impl FromStr for Style {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ascii" => Ok(Style::default()),
+            "unicode" => Style::custom("\u{b7}", "\u{2212}"),
+            // not a space, which separates letters
+            "words" => Ok(Style {
+                element_separator: "-".to_string(),
+                ..Style::custom("dit", "dah")?
+            }),
+            "binary" => Style::custom("0", "1"),
+            _ => Err(eyre!(
+                "Unknown style {}, expected one of {}",
+                s,
+                STYLES.join(", ")
+            )),
+        }
+    }
+}
+
+impl Style {
+    pub fn custom(dot: &str, dash: &str) -> Result<Self, Report> {
+        if dot.is_empty() || dash.is_empty() || dot == dash {
+            return Err(eyre!(
+                "Dot and dash must be non empty and differ: `{}`, `{}`",
+                dot,
+                dash
+            ));
+        }
+        Ok(Style {
+            dot: dot.to_string(),
+            dash: dash.to_string(),
+            element_separator: String::new(),
+        })
+    }
+
+    /// Morse with its dots and dashes rendered, other characters (separators) are kept
+    pub fn render(&self, morse: &str) -> String {
+        let mut rendered = String::new();
+        let mut after_element = false;
+        for ch in morse.chars() {
+            let element = match ch {
+                DOT => &self.dot,
+                DASH => &self.dash,
+                _ => {
+                    rendered.push(ch);
+                    after_element = false;
+                    continue;
+                }
+            };
+            if after_element {
+                rendered.push_str(&self.element_separator);
+            }
+            rendered.push_str(element);
+            after_element = true;
+        }
+        rendered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        let glyphs = Glyphs::default();
+        assert_eq!(glyphs.normalize("....---.-.....\n"), "....---.-.....");
+        assert_eq!(
+            glyphs.normalize("\u{b7}\u{2212}\u{2022}\u{2013}\u{2014}_"),
+            ".-.---"
+        );
+        assert_eq!(glyphs.normalize(" \u{b7}\u{b7} \u{2212} "), ".. -");
+        assert_eq!(glyphs.normalize(".x-"), ".x-");
+        let strip = Glyphs::new("", "", true).unwrap();
+        assert_eq!(strip.normalize(" .. -\t-\n"), "..--");
+        assert_eq!(strip.normalize("\u{b7}"), "\u{b7}");
+        let binary = Glyphs::new("0", "1", false).unwrap();
+        assert_eq!(binary.normalize("0110.-"), ".--..-");
+        assert!(Glyphs::new("-", "", false).is_err());
+        assert!(Glyphs::new("x", "yx", false).is_err());
+        assert!(Glyphs::new(" ", "", false).is_err());
+    }
+
+    #[test]
+    fn test_validate_separators() {
+        let glyphs = Glyphs::default();
+        assert!(glyphs.validate_separators(&Separators::default()).is_ok());
+        let underscores = Separators::new(" ", " __ ").unwrap();
+        assert!(glyphs.validate_separators(&underscores).is_err());
+        assert!(Glyphs::new("", "", false)
+            .unwrap()
+            .validate_separators(&underscores)
+            .is_ok());
+        // the separators would be stripped with the whitespace
+        let strip = Glyphs::new("", "", true).unwrap();
+        assert!(strip.validate_separators(&Separators::default()).is_err());
+        assert!(strip
+            .validate_separators(&Separators::new("|", "/").unwrap())
+            .is_ok());
+    }
+
+    #[test]
+    fn test_render() {
+        let morse = "... --- / .-";
+        assert_eq!(Style::default().render(morse), morse);
+        let style: Style = "unicode".parse().unwrap();
+        assert_eq!(
+            style.render(morse),
+            "\u{b7}\u{b7}\u{b7} \u{2212}\u{2212}\u{2212} / \u{b7}\u{2212}"
+        );
+        let style: Style = "words".parse().unwrap();
+        assert_eq!(style.render(".-"), "dit-dah");
+        assert_eq!(style.render(".. -|."), "dit-dit dah|dit");
+        // letters stay apart in standard morse
+        assert_eq!(style.render(".- . / . -"), "dit-dah dit / dit dah");
+        let style: Style = "binary".parse().unwrap();
+        assert_eq!(style.render("-..-"), "1001");
+        assert_eq!(Style::custom("o", "=").unwrap().render(".-"), "o=");
+        assert!("morse".parse::<Style>().is_err());
+        assert!(Style::custom("x", "x").is_err());
+        assert!(Style::custom("", "-").is_err());
+    }
+}
@@ -0,0 +1,408 @@
+//! Collision graph, for visual inspection in GraphViz or graph editors: words linked to
+//! their code, to the words sharing their code, or to the words whose codes are within one
+//! edit (a dot or dash changed, added or removed). Written as DOT or GraphML.
+
+use crate::collisions::{groups, Filter, Group};
+use crate::merses::merse_to_morse;
+use crate::merses::Merse;
+use crate::wordlist::get_all_words;
+use color_eyre::{eyre::eyre, Report};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::str::FromStr;
+use tracing::info;
+
+pub const GRAPH_MODES: [&str; 3] = ["code", "shared", "edit"];
+pub const GRAPH_FORMATS: [&str; 2] = ["dot", "graphml"];
+/// Groups of a single word have no collision
+pub const DEFAULT_MIN_GROUP: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphMode {
+    /// Word and code nodes, each word linked to its code
+    Code,
+    /// Word nodes, linked when sharing a code
+    Shared,
+    /// Word nodes, linked when their codes are equal or one edit apart
+    Edit,
+}
+
+impl FromStr for GraphMode {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "code" => Ok(GraphMode::Code),
+            "shared" => Ok(GraphMode::Shared),
+            "edit" => Ok(GraphMode::Edit),
+            _ => Err(eyre!(
+                "Unknown graph mode {}, expected one of {}",
+                s,
+                GRAPH_MODES.join(", ")
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    Dot,
+    GraphMl,
+}
+
+impl FromStr for GraphFormat {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(GraphFormat::Dot),
+            "graphml" => Ok(GraphFormat::GraphMl),
+            _ => Err(eyre!(
+                "Unknown graph format {}, expected one of {}",
+                s,
+                GRAPH_FORMATS.join(", ")
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    /// `w` or `c` and a number
+    pub id: String,
+    /// The word or the code
+    pub label: String,
+    pub is_code: bool,
+}
+
+/// Undirected edge between nodes, by position
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub source: usize,
+    pub target: usize,
+    /// Edit distance of the codes of the nodes
+    pub distance: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Graph {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+impl Graph {
+    fn add_node(&mut self, label: String, is_code: bool) -> usize {
+        let prefix = if is_code { 'c' } else { 'w' };
+        self.nodes.push(Node {
+            id: format!("{}{}", prefix, self.nodes.len()),
+            label,
+            is_code,
+        });
+        self.nodes.len() - 1
+    }
+
+    fn add_edges_between(&mut self, sources: &[usize], targets: &[usize], distance: usize) {
+        for source in sources {
+            for target in targets {
+                self.edges.push(Edge {
+                    source: *source,
+                    target: *target,
+                    distance,
+                });
+            }
+        }
+    }
+}
+
+/// Graph of the groups of words sharing a code
+pub fn build(groups: &[Group], mode: GraphMode) -> Graph {
+    let mut graph = Graph::default();
+    let word_nodes: Vec<Vec<usize>> = groups
+        .iter()
+        .map(|group| {
+            group
+                .words
+                .iter()
+                .map(|word| graph.add_node(word.clone(), false))
+                .collect()
+        })
+        .collect();
+    for (group, words) in groups.iter().zip(&word_nodes) {
+        match mode {
+            GraphMode::Code => {
+                let code = graph.add_node(merse_to_morse(&group.code), true);
+                graph.add_edges_between(words, &[code], 0);
+            }
+            GraphMode::Shared | GraphMode::Edit => {
+                for (i, word) in words.iter().enumerate() {
+                    graph.add_edges_between(&[*word], &words[i + 1..], 0);
+                }
+            }
+        }
+    }
+    if mode == GraphMode::Edit {
+        let positions: HashMap<&Merse, usize> = groups
+            .iter()
+            .enumerate()
+            .map(|(i, group)| (&group.code, i))
+            .collect();
+        for (i, group) in groups.iter().enumerate() {
+            let neighbours: HashSet<usize> = one_edit(&group.code)
+                .iter()
+                .filter_map(|code| positions.get(code).copied())
+                .filter(|j| *j > i)
+                .collect();
+            let mut neighbours: Vec<usize> = neighbours.into_iter().collect();
+            neighbours.sort_unstable();
+            for j in neighbours {
+                graph.add_edges_between(&word_nodes[i], &word_nodes[j], 1);
+            }
+        }
+    }
+    graph
+}
+
+/// Codes one edit away: a symbol changed, removed or added
+fn one_edit(code: &Merse) -> Vec<Merse> {
+    let symbols: Vec<bool> = code.iter().collect();
+    let mut variants: Vec<Merse> = Vec::new();
+    for i in 0..symbols.len() {
+        let mut changed = symbols.clone();
+        changed[i] = !changed[i];
+        variants.push(Merse::from(changed));
+        let mut removed = symbols.clone();
+        removed.remove(i);
+        variants.push(Merse::from(removed));
+    }
+    for i in 0..=symbols.len() {
+        for symbol in [false, true] {
+            let mut added = symbols.clone();
+            added.insert(i, symbol);
+            variants.push(Merse::from(added));
+        }
+    }
+    variants
+}
+
+/// Graph of the word list groups kept by the filter, of at least `min_group` words
+pub fn run(
+    words_file: Option<&str>,
+    filter: &Filter,
+    min_group: usize,
+    mode: GraphMode,
+) -> Result<Graph, Report> {
+    let all_words: Vec<String> = get_all_words(words_file)?;
+    let groups: Vec<Group> = groups(&all_words, filter)?
+        .into_iter()
+        .filter(|group| group.words.len() >= min_group)
+        .collect();
+    let graph = build(&groups, mode);
+    info!(
+        "Graph of {} groups: {} nodes, {} edges",
+        groups.len(),
+        graph.nodes.len(),
+        graph.edges.len()
+    );
+    Ok(graph)
+}
+
+pub fn render(graph: &Graph, format: GraphFormat) -> String {
+    match format {
+        GraphFormat::Dot => to_dot(graph),
+        GraphFormat::GraphMl => to_graphml(graph),
+    }
+}
+
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Codes are boxes, edges one edit apart are labelled 1
+pub fn to_dot(graph: &Graph) -> String {
+    let mut dot = String::from("graph smooshedmorse {\n");
+    for node in &graph.nodes {
+        let shape = if node.is_code { ", shape=box" } else { "" };
+        writeln!(
+            dot,
+            "  {} [label=\"{}\"{}];",
+            node.id,
+            dot_escape(&node.label),
+            shape
+        )
+        .expect("writing to a string");
+    }
+    for edge in &graph.edges {
+        let label = match edge.distance {
+            0 => String::new(),
+            distance => format!(" [label=\"{}\"]", distance),
+        };
+        let (source, target) = (&graph.nodes[edge.source].id, &graph.nodes[edge.target].id);
+        writeln!(dot, "  {} -- {}{};", source, target, label).expect("writing to a string");
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Nodes have a label and a kind (word or code), edges a distance
+pub fn to_graphml(graph: &Graph) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n\
+         \x20 <key id=\"label\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>\n\
+         \x20 <key id=\"kind\" for=\"node\" attr.name=\"kind\" attr.type=\"string\"/>\n\
+         \x20 <key id=\"distance\" for=\"edge\" attr.name=\"distance\" attr.type=\"int\"/>\n\
+         \x20 <graph id=\"smooshedmorse\" edgedefault=\"undirected\">\n",
+    );
+    for node in &graph.nodes {
+        let kind = if node.is_code { "code" } else { "word" };
+        writeln!(
+            xml,
+            "    <node id=\"{}\"><data key=\"label\">{}</data><data key=\"kind\">{}</data></node>",
+            node.id,
+            xml_escape(&node.label),
+            kind
+        )
+        .expect("writing to a string");
+    }
+    for edge in &graph.edges {
+        writeln!(
+            xml,
+            "    <edge source=\"{}\" target=\"{}\"><data key=\"distance\">{}</data></edge>",
+            graph.nodes[edge.source].id, graph.nodes[edge.target].id, edge.distance
+        )
+        .expect("writing to a string");
+    }
+    xml.push_str("  </graph>\n</graphml>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils;
+
+    fn group(code: &str, words: &[&str]) -> Group {
+        Group {
+            code: code.parse().unwrap(),
+            words: test_utils::words(words),
+        }
+    }
+
+    fn groups() -> Vec<Group> {
+        vec![
+            group("...", &["eee", "s"]),
+            group(".-", &["a", "et"]),
+            group("..-", &["u"]),
+            group("-.-..-.-..-", &["caret", "ceca"]),
+        ]
+    }
+
+    fn labels(graph: &Graph) -> Vec<(&str, &str, usize)> {
+        graph
+            .edges
+            .iter()
+            .map(|edge| {
+                (
+                    graph.nodes[edge.source].label.as_str(),
+                    graph.nodes[edge.target].label.as_str(),
+                    edge.distance,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_build_code() {
+        let graph = build(&groups(), GraphMode::Code);
+        assert_eq!(graph.nodes.len(), 7 + 4);
+        assert_eq!(graph.nodes.iter().filter(|node| node.is_code).count(), 4);
+        assert_eq!(labels(&graph)[..2], [("eee", "...", 0), ("s", "...", 0)]);
+        assert_eq!(graph.edges.len(), 7);
+    }
+
+    #[test]
+    fn test_build_shared() {
+        let graph = build(&groups(), GraphMode::Shared);
+        assert_eq!(graph.nodes.len(), 7);
+        assert_eq!(
+            labels(&graph),
+            vec![("eee", "s", 0), ("a", "et", 0), ("caret", "ceca", 0)]
+        );
+    }
+
+    #[test]
+    fn test_build_edit() {
+        let graph = build(&groups(), GraphMode::Edit);
+        let edges = labels(&graph);
+        assert_eq!(edges.len(), 3 + 2 + 2);
+        // ... and ..- differ by a symbol, .- and ..- by an added dot
+        assert!(edges.contains(&("eee", "u", 1)));
+        assert!(edges.contains(&("s", "u", 1)));
+        assert!(edges.contains(&("a", "u", 1)));
+        assert!(edges.contains(&("et", "u", 1)));
+        assert!(edges.contains(&("caret", "ceca", 0)));
+    }
+
+    #[test]
+    fn test_one_edit() {
+        let variants = one_edit(&".-".parse().unwrap());
+        assert_eq!(variants.len(), 2 * 2 + 2 * 3);
+        for code in ["--", "..", "-", ".", ".-.", "-.-", "..-", ".--"].iter() {
+            assert!(variants.contains(&code.parse().unwrap()), "{}", code);
+        }
+        assert_eq!(one_edit(&Merse::new()).len(), 2);
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let graph = build(&groups()[..1], GraphMode::Code);
+        assert_eq!(
+            to_dot(&graph),
+            "graph smooshedmorse {\n  w0 [label=\"eee\"];\n  w1 [label=\"s\"];\n  \
+             c2 [label=\"...\", shape=box];\n  w0 -- c2;\n  w1 -- c2;\n}\n"
+        );
+        let graph = build(&groups()[1..3], GraphMode::Edit);
+        assert!(to_dot(&graph).contains("  w0 -- w2 [label=\"1\"];\n"));
+        assert_eq!(dot_escape("a\"b\\"), "a\\\"b\\\\");
+    }
+
+    #[test]
+    fn test_to_graphml() {
+        let graph = build(&groups()[..1], GraphMode::Code);
+        let xml = to_graphml(&graph);
+        assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<graphml "));
+        assert!(xml.contains(
+            "    <node id=\"c2\"><data key=\"label\">...</data><data key=\"kind\">code</data></node>\n"
+        ));
+        assert!(xml.contains(
+            "    <edge source=\"w1\" target=\"c2\"><data key=\"distance\">0</data></edge>\n"
+        ));
+        assert!(xml.ends_with("  </graph>\n</graphml>\n"));
+        assert_eq!(xml_escape("<a & 'b'>"), "&lt;a &amp; &apos;b&apos;&gt;");
+    }
+
+    #[test]
+    fn test_run() {
+        let filter = Filter::default();
+        let graph = run(Some("tests/wordlist"), &filter, 3, GraphMode::Code).unwrap();
+        for (i, node) in graph.nodes.iter().enumerate() {
+            if node.is_code {
+                let words = graph.edges.iter().filter(|edge| edge.target == i).count();
+                assert!(words >= 3, "{} has {} words", node.label, words);
+            }
+        }
+        assert!("tree".parse::<GraphMode>().is_err());
+        assert_eq!(
+            "graphml".parse::<GraphFormat>().unwrap(),
+            GraphFormat::GraphMl
+        );
+    }
+}
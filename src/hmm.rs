@@ -0,0 +1,379 @@
+//! Probabilistic decoding of poorly keyed morse: letters are the hidden states of a hidden
+//! Markov model. Each letter emits its marks and gaps (likelihoods from their timing), and
+//! letters follow each other as in the word list (a character n-gram model). Viterbi search
+//! keeps the N best paths, so alternatives come with the most likely text.
+
+use crate::keying::{ElementKind, Reading};
+use crate::morses::{get_morse_table, letter_index, ALPHABET, DASH};
+use crate::wordlist::get_all_words;
+use color_eyre::{eyre::eyre, Report};
+use std::collections::HashMap;
+use tracing::{debug, info};
+
+pub const DEFAULT_ORDER: usize = 3;
+/// Longer contexts are hardly ever seen in a word list
+pub const MAX_ORDER: usize = 8;
+pub const DEFAULT_BEST: usize = 5;
+/// Word boundary symbol of the character model, also separating words in the text
+const BOUNDARY: char = ' ';
+/// Symbols of the character model: the letters and the word boundary
+const SYMBOLS: usize = 27;
+/// Additive smoothing of the character counts
+const SMOOTHING: f64 = 0.1;
+/// Paths extended from each mark position, the most likely first
+const BEAM: usize = 500;
+/// Spread of durations around their ideal length: standard deviation of the log ratio
+const SIGMA: f64 = 0.3;
+
+/// Character n-gram model, interpolating all orders up to `order`
+#[derive(Debug, Clone)]
+pub struct CharModel {
+    order: usize,
+    /// Symbol counts after each context of 0 to order - 1 symbols
+    counts: HashMap<String, [u32; SYMBOLS]>,
+}
+
+fn symbol_index(symbol: char) -> usize {
+    letter_index(symbol).unwrap_or(SYMBOLS - 1)
+}
+
+impl CharModel {
+    /// Train on the words, ignoring those with other characters than letters
+    pub fn train(words: &[String], order: usize) -> Result<Self, Report> {
+        if !(1..=MAX_ORDER).contains(&order) {
+            return Err(eyre!(
+                "The character model order must be from 1 to {}: {}",
+                MAX_ORDER,
+                order
+            ));
+        }
+        let mut counts: HashMap<String, [u32; SYMBOLS]> = HashMap::new();
+        let padding = BOUNDARY.to_string().repeat(order - 1);
+        for word in words
+            .iter()
+            .filter(|w| w.chars().all(|c| c.is_ascii_alphabetic()))
+        {
+            let symbols: Vec<char> =
+                format!("{}{}{}", padding, word.to_ascii_lowercase(), BOUNDARY)
+                    .chars()
+                    .collect();
+            for i in order - 1..symbols.len() {
+                for len in 0..order {
+                    let context: String = symbols[i - len..i].iter().collect();
+                    counts.entry(context).or_insert([0; SYMBOLS])[symbol_index(symbols[i])] += 1;
+                }
+            }
+        }
+        debug!("Character model: {} contexts", counts.len());
+        Ok(CharModel { order, counts })
+    }
+
+    pub fn load(wordlist: Option<&str>, order: usize) -> Result<Self, Report> {
+        let model = CharModel::train(&get_all_words(wordlist)?, order)?;
+        info!("Character model of order {} trained", order);
+        Ok(model)
+    }
+
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    /// Log probability of the symbol after the context (only its last order - 1 symbols
+    /// count)
+    pub fn log_prob(&self, context: &str, symbol: char) -> f64 {
+        self.log_probs(context)[symbol_index(symbol)]
+    }
+
+    /// Log probabilities of every symbol after the context, letters then word boundary.
+    /// Longer contexts weigh twice as much as the next shorter one.
+    pub fn log_probs(&self, context: &str) -> [f64; SYMBOLS] {
+        let context: Vec<char> = context.chars().collect();
+        let mut probs = [0.0; SYMBOLS];
+        let mut weights = 0.0;
+        for len in 0..self.order.min(context.len() + 1) {
+            let suffix: String = context[context.len() - len..].iter().collect();
+            let weight = 2f64.powi(len as i32);
+            let counts = self.counts.get(&suffix).copied().unwrap_or([0; SYMBOLS]);
+            let total = f64::from(counts.iter().sum::<u32>()) + SMOOTHING * SYMBOLS as f64;
+            for (prob, count) in probs.iter_mut().zip(counts.iter()) {
+                *prob += weight * (f64::from(*count) + SMOOTHING) / total;
+            }
+            weights += weight;
+        }
+        probs.map(|prob| (prob / weights).ln())
+    }
+}
+
+/// A decoded text and its log probability
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hypothesis {
+    pub text: String,
+    pub log_prob: f64,
+}
+
+/// Log likelihood of a duration for an ideal length, both in dots
+fn log_likelihood(units: f64, ideal: f64) -> f64 {
+    let x = (units / ideal).ln() / SIGMA;
+    -0.5 * x * x
+}
+
+/// Log probabilities of each kind given the durations, normalized over the kinds
+fn log_posteriors(units: f64, ideals: &[f64]) -> Vec<f64> {
+    let likelihoods: Vec<f64> = ideals
+        .iter()
+        .map(|ideal| log_likelihood(units, *ideal))
+        .collect();
+    let max = likelihoods
+        .iter()
+        .cloned()
+        .fold(f64::NEG_INFINITY, f64::max);
+    let total = max
+        + likelihoods
+            .iter()
+            .map(|l| (l - max).exp())
+            .sum::<f64>()
+            .ln();
+    likelihoods.iter().map(|l| l - total).collect()
+}
+
+/// Mean length in dots of the elements of a kind, or the standard one
+fn ideal_units(reading: &Reading, kind: ElementKind, standard: f64) -> f64 {
+    let units: Vec<f64> = reading
+        .elements
+        .iter()
+        .filter(|element| element.kind == kind)
+        .map(|element| element.units)
+        .collect();
+    match units.is_empty() {
+        true => standard,
+        false => units.iter().sum::<f64>() / units.len() as f64,
+    }
+}
+
+/// Gap log probabilities: inside a letter, between letters, between words
+struct GapEmission {
+    element: f64,
+    letter: f64,
+    word: f64,
+}
+
+/// A path ending at a mark position: its text, log probability, and character context
+#[derive(Debug, Clone)]
+struct Path {
+    text: String,
+    context: String,
+    log_prob: f64,
+}
+
+/// Keep the best paths for each context
+fn insert(paths: &mut HashMap<String, Vec<Path>>, path: Path, best: usize) {
+    let kept = paths.entry(path.context.clone()).or_default();
+    kept.push(path);
+    kept.sort_by(|a, b| {
+        b.log_prob
+            .partial_cmp(&a.log_prob)
+            .expect("NaN probability")
+    });
+    kept.truncate(best);
+}
+
+/// The `best` most likely texts of the keying, most likely first. When the reading
+/// found letter gaps unreliable, any gap shorter than a word gap may end a letter.
+pub fn decode(reading: &Reading, model: &CharModel, best: usize) -> Vec<Hypothesis> {
+    let marks: Vec<(f64, f64)> = reading
+        .elements
+        .iter()
+        .filter(|element| element.kind.is_mark())
+        .map(|element| {
+            let posteriors = log_posteriors(element.units, &[1.0, 3.0]);
+            (posteriors[0], posteriors[1])
+        })
+        .collect();
+    let letter_ideal = ideal_units(reading, ElementKind::LetterGap, 3.0);
+    let word_ideal = ideal_units(reading, ElementKind::WordGap, 7.0).max(letter_ideal * 1.5);
+    let smooshed = reading.spacing == crate::audio::Spacing::Smooshed;
+    let gaps: Vec<GapEmission> = reading
+        .elements
+        .iter()
+        .filter(|element| !element.kind.is_mark())
+        .map(|element| {
+            let p = log_posteriors(element.units, &[1.0, letter_ideal, word_ideal]);
+            GapEmission {
+                element: p[0],
+                // between smooshed letters, element gaps too
+                letter: if smooshed {
+                    (p[0].exp() + p[1].exp()).ln()
+                } else {
+                    p[1]
+                },
+                word: p[2],
+            }
+        })
+        .collect();
+    let codes: Vec<(char, Vec<bool>)> = ALPHABET
+        .iter()
+        .zip(get_morse_table().iter())
+        .map(|(letter, code)| (*letter, code.chars().map(|c| c == DASH).collect()))
+        .collect();
+    let context_len = model.order() - 1;
+    let trim = |context: String| -> String {
+        let skip = context.chars().count().saturating_sub(context_len);
+        context.chars().skip(skip).collect()
+    };
+    // paths ending before each mark, by context
+    let mut lattice: Vec<HashMap<String, Vec<Path>>> = vec![HashMap::new(); marks.len() + 1];
+    let start = Path {
+        text: String::new(),
+        context: trim(BOUNDARY.to_string().repeat(context_len)),
+        log_prob: 0.0,
+    };
+    lattice[0].insert(start.context.clone(), vec![start]);
+    let mut finished: Vec<Hypothesis> = Vec::new();
+    let mut distributions: HashMap<String, [f64; SYMBOLS]> = HashMap::new();
+    let mut log_prob = |context: &str, symbol: char| {
+        distributions
+            .entry(context.to_string())
+            .or_insert_with(|| model.log_probs(context))[symbol_index(symbol)]
+    };
+    for i in 0..marks.len() {
+        let mut paths: Vec<Path> = std::mem::take(&mut lattice[i])
+            .into_values()
+            .flatten()
+            .collect();
+        paths.sort_by(|a, b| {
+            b.log_prob
+                .partial_cmp(&a.log_prob)
+                .expect("NaN probability")
+        });
+        paths.truncate(BEAM);
+        // letters that can start here, with the log probability of their marks and gaps
+        let letters: Vec<(char, usize, f64)> = codes
+            .iter()
+            .filter(|(_, code)| i + code.len() <= marks.len())
+            .map(|(letter, code)| {
+                let end = i + code.len();
+                let emission = code
+                    .iter()
+                    .zip(&marks[i..end])
+                    .map(|(dash, (dot_p, dash_p))| if *dash { dash_p } else { dot_p })
+                    .sum::<f64>()
+                    + gaps[i..end - 1].iter().map(|gap| gap.element).sum::<f64>();
+                (*letter, end, emission)
+            })
+            .collect();
+        for path in paths {
+            for &(letter, end, emission) in letters.iter() {
+                let path_prob = path.log_prob + emission + log_prob(&path.context, letter);
+                let mut text = path.text.clone();
+                text.push(letter);
+                let context = trim(format!("{}{}", path.context, letter));
+                let boundary_prob = log_prob(&context, BOUNDARY);
+                if end == marks.len() {
+                    finished.push(Hypothesis {
+                        text,
+                        log_prob: path_prob + boundary_prob,
+                    });
+                    continue;
+                }
+                let gap = &gaps[end - 1];
+                insert(
+                    &mut lattice[end],
+                    Path {
+                        text: text.clone(),
+                        context: context.clone(),
+                        log_prob: path_prob + gap.letter,
+                    },
+                    best,
+                );
+                text.push(BOUNDARY);
+                insert(
+                    &mut lattice[end],
+                    Path {
+                        log_prob: path_prob + gap.word + boundary_prob,
+                        text,
+                        context: trim(format!("{}{}", context, BOUNDARY)),
+                    },
+                    best,
+                );
+            }
+        }
+    }
+    finished.sort_by(|a, b| {
+        b.log_prob
+            .partial_cmp(&a.log_prob)
+            .expect("NaN probability")
+    });
+    finished.truncate(best);
+    debug!("Best decodings: {:?}", finished);
+    finished
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{keying as audio_keying, AudioConfig, Key, Spacing};
+    use crate::keying::read;
+    use crate::test_utils::words;
+
+    fn model() -> CharModel {
+        let list = words(&["sos", "horse", "hello", "world", "the", "tee", "eel", "see"]);
+        CharModel::train(&list, DEFAULT_ORDER).unwrap()
+    }
+
+    fn keys(sentence: &str, spacing: Spacing) -> Vec<Key> {
+        audio_keying(sentence, spacing, &AudioConfig::default()).unwrap()
+    }
+
+    #[test]
+    fn test_char_model() {
+        let model = model();
+        assert!(model.log_prob("  ", 's') > model.log_prob("  ", 'x'));
+        assert!(model.log_prob("so", 's') > model.log_prob("so", 'e'));
+        // unseen contexts back off to shorter ones
+        assert!(model.log_prob("qq", 'e') > model.log_prob("qq", 'q'));
+        let total: f64 = ALPHABET
+            .iter()
+            .chain(std::iter::once(&BOUNDARY))
+            .map(|symbol| model.log_prob("he", *symbol).exp())
+            .sum();
+        assert!((total - 1.0).abs() < 1e-9);
+        assert!(CharModel::train(&[], 0).is_err());
+        assert!(CharModel::train(&[], MAX_ORDER).is_ok());
+        assert!(CharModel::train(&[], 40).is_err());
+    }
+
+    #[test]
+    fn test_decode_spaced() {
+        let reading = read(&keys("hello world", Spacing::Standard), false).unwrap();
+        let hypotheses = decode(&reading, &model(), 3);
+        assert_eq!(hypotheses.len(), 3);
+        assert_eq!(hypotheses[0].text, "hello world");
+        assert!(hypotheses[0].log_prob > hypotheses[1].log_prob);
+    }
+
+    #[test]
+    fn test_decode_sloppy_gaps() {
+        // letter gaps hardly longer than element gaps: the character model splits letters
+        let mut keys = keys("sos", Spacing::Standard);
+        for key in keys.iter_mut().filter(|key| !key.on) {
+            key.duration = if key.duration > 0.1 { 0.14 } else { 0.085 };
+        }
+        let reading = read(&keys, false).unwrap();
+        assert_eq!(reading.spacing, Spacing::Smooshed);
+        assert_eq!(decode(&reading, &model(), 1)[0].text, "sos");
+        let reading = read(&self::keys("horse", Spacing::Smooshed), false).unwrap();
+        let hypotheses = decode(&reading, &model(), DEFAULT_BEST);
+        assert_eq!(hypotheses[0].text, "horse");
+        assert_eq!(hypotheses.len(), DEFAULT_BEST);
+    }
+
+    #[test]
+    fn test_decode_ambiguous_mark() {
+        // the dash of "t" keyed too short: the model still prefers "the"
+        let mut keys = keys("the", Spacing::Standard);
+        keys[0].duration *= 0.65;
+        let reading = read(&keys, false).unwrap();
+        assert_ne!(reading.to_morse(&Default::default()), "- .... .");
+        assert_eq!(decode(&reading, &model(), 1)[0].text, "the");
+    }
+}
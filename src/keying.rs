@@ -0,0 +1,585 @@
+//! Read morse from keying: key down and up durations, as logged by a straight key or heard
+//! by `listen`. Durations are clustered into dots and dashes, element, letter and word gaps,
+//! following the speed as it drifts, and every element gets a confidence.
+
+use crate::audio::{Key, Spacing};
+use crate::merses::Merse;
+use crate::spaced::Separators;
+use color_eyre::{eyre::eyre, Report};
+use serde_json::Value;
+use std::io::Read;
+use tracing::{debug, info, trace};
+
+/// How fast the dot length follows the elements, from 0 (fixed) to 1 (last element only)
+const TRACKING: f64 = 0.25;
+/// An element moves the dot length by at most this factor
+const MAX_STEP: f64 = 1.5;
+/// Letter gaps must be this much longer than element gaps to be trusted, else words are
+/// read smooshed
+const MIN_GAP_MARGIN: f64 = 1.8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElementKind {
+    Dot,
+    Dash,
+    /// Between the marks of a letter
+    ElementGap,
+    LetterGap,
+    WordGap,
+}
+
+impl ElementKind {
+    pub fn is_mark(self) -> bool {
+        matches!(self, ElementKind::Dot | ElementKind::Dash)
+    }
+}
+
+/// A key down or up, classified
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Element {
+    pub kind: ElementKind,
+    /// Duration, seconds
+    pub duration: f64,
+    /// Duration in dots of the speed at that point
+    pub units: f64,
+    /// 1 at the ideal length of the kind, 0.5 on the boundary with the next kind
+    pub confidence: f64,
+}
+
+/// Morse read from keying
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reading {
+    pub elements: Vec<Element>,
+    /// Mean dot length, seconds
+    pub unit: f64,
+    /// Standard when reliable letter gaps were found, else smooshed
+    pub spacing: Spacing,
+}
+
+impl Reading {
+    /// Mean character speed, words per minute
+    pub fn wpm(&self) -> f64 {
+        1.2 / self.unit
+    }
+
+    /// Elements of each word, word gaps excluded
+    pub fn word_elements(&self) -> Vec<&[Element]> {
+        self.elements
+            .split(|element| element.kind == ElementKind::WordGap)
+            .collect()
+    }
+
+    /// Letter codes of each word, one code per word when smooshed
+    pub fn words(&self) -> Vec<Vec<Merse>> {
+        self.word_elements()
+            .iter()
+            .map(|elements| {
+                let mut letters = vec![Merse::new()];
+                for element in elements.iter() {
+                    match element.kind {
+                        ElementKind::Dot => letters.last_mut().expect("No letter").push(false),
+                        ElementKind::Dash => letters.last_mut().expect("No letter").push(true),
+                        ElementKind::LetterGap if self.spacing == Spacing::Standard => {
+                            letters.push(Merse::new())
+                        }
+                        _ => {}
+                    }
+                }
+                letters
+            })
+            .collect()
+    }
+
+    /// Spaced morse (smooshed words when letter gaps are unreliable)
+    pub fn to_morse(&self, separators: &Separators) -> String {
+        self.words()
+            .iter()
+            .map(|letters| {
+                letters
+                    .iter()
+                    .map(Merse::to_string)
+                    .collect::<Vec<String>>()
+                    .join(&separators.letter)
+            })
+            .collect::<Vec<String>>()
+            .join(&separators.word)
+    }
+
+    /// Smooshed code of each word
+    pub fn smooshed_words(&self) -> Vec<String> {
+        self.words()
+            .iter()
+            .map(|letters| letters.iter().map(Merse::to_string).collect())
+            .collect()
+    }
+
+    /// Lowest element confidence
+    pub fn confidence(&self) -> f64 {
+        lowest_confidence(&self.elements)
+    }
+}
+
+pub fn lowest_confidence(elements: &[Element]) -> f64 {
+    elements
+        .iter()
+        .map(|element| element.confidence)
+        .fold(1.0, f64::min)
+}
+
+/// Key durations from milliseconds: a JSON array of numbers (alternately down and up,
+/// starting down) or of objects `{"state": "on", "duration": 60}`, or CSV lines of
+/// `state,duration` (or only durations, alternately down and up) with an optional header.
+/// States are on/down/1/true or off/up/0/false.
+pub fn parse_timings(text: &str) -> Result<Vec<Key>, Report> {
+    let timings = if text.trim_start().starts_with('[') {
+        parse_json_timings(text)?
+    } else {
+        parse_csv_timings(text)?
+    };
+    timings
+        .into_iter()
+        .map(|(on, ms)| {
+            if ms.is_finite() && ms >= 0.0 {
+                Ok(Key {
+                    on,
+                    duration: ms / 1000.0,
+                })
+            } else {
+                Err(eyre!("Invalid duration: {} ms", ms))
+            }
+        })
+        .collect()
+}
+
+/// Key durations from a file, or from stdin for `-`, see `parse_timings`
+pub fn read_timings_file(path: &str) -> Result<Vec<Key>, Report> {
+    let text = if path == crate::batch::STDIN_ARG {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text)?;
+        text
+    } else {
+        std::fs::read_to_string(path)?
+    };
+    parse_timings(&text)
+}
+
+fn parse_state(state: &str) -> Result<bool, Report> {
+    match state.trim().to_lowercase().as_str() {
+        "on" | "down" | "1" | "true" => Ok(true),
+        "off" | "up" | "0" | "false" => Ok(false),
+        _ => Err(eyre!("Invalid key state: {}", state)),
+    }
+}
+
+fn parse_json_timings(text: &str) -> Result<Vec<(bool, f64)>, Report> {
+    let values: Vec<Value> = serde_json::from_str(text)?;
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| match value {
+            Value::Number(ms) => Ok((i.is_multiple_of(2), ms.as_f64().unwrap_or(f64::NAN))),
+            Value::Object(timing) => {
+                let on = match timing.get("state") {
+                    Some(Value::Bool(on)) => *on,
+                    Some(Value::String(state)) => parse_state(state)?,
+                    _ => return Err(eyre!("Missing state in {}", value)),
+                };
+                let ms = timing
+                    .get("duration")
+                    .and_then(Value::as_f64)
+                    .ok_or_else(|| eyre!("Missing duration in {}", value))?;
+                Ok((on, ms))
+            }
+            _ => Err(eyre!("Expected a duration or a timing object: {}", value)),
+        })
+        .collect()
+}
+
+fn parse_csv_timings(text: &str) -> Result<Vec<(bool, f64)>, Report> {
+    let mut timings: Vec<(bool, f64)> = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let timing = match fields.as_slice() {
+            [""] => continue,
+            [ms] => ms
+                .parse()
+                .ok()
+                .map(|ms| (timings.len().is_multiple_of(2), ms)),
+            [state, ms] => match (parse_state(state), ms.parse()) {
+                (Ok(on), Ok(ms)) => Some((on, ms)),
+                _ => None,
+            },
+            _ => {
+                return Err(eyre!(
+                    "Expected `state,duration` on line {}: {}",
+                    i + 1,
+                    line
+                ))
+            }
+        };
+        match timing {
+            Some(timing) => timings.push(timing),
+            // header
+            None if i == 0 => continue,
+            None => return Err(eyre!("Invalid timing on line {}: {}", i + 1, line)),
+        }
+    }
+    Ok(timings)
+}
+
+/// Merge repeated states, drop leading and trailing key up
+fn normalize(keys: &[Key]) -> Vec<Key> {
+    let mut merged: Vec<Key> = Vec::new();
+    for key in keys.iter().filter(|key| key.duration > 0.0) {
+        match merged.last_mut() {
+            Some(last) if last.on == key.on => last.duration += key.duration,
+            _ => merged.push(*key),
+        }
+    }
+    while merged.first().is_some_and(|key| !key.on) {
+        merged.remove(0);
+    }
+    while merged.last().is_some_and(|key| !key.on) {
+        merged.pop();
+    }
+    merged
+}
+
+/// Split values in two clusters (1D k-means), returning the threshold between them and
+/// the cluster means, or None if the values are all close
+fn two_means(values: &[f64], min_ratio: f64) -> Option<(f64, f64, f64)> {
+    let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = values.iter().cloned().fold(0.0, f64::max);
+    if max < min * min_ratio {
+        return None;
+    }
+    let (mut short, mut long) = (min, max);
+    for _ in 0..20 {
+        let threshold = (short + long) / 2.0;
+        let mean = |values: Vec<f64>| values.iter().sum::<f64>() / values.len() as f64;
+        short = mean(values.iter().cloned().filter(|v| *v < threshold).collect());
+        long = mean(values.iter().cloned().filter(|v| *v >= threshold).collect());
+    }
+    Some(((short + long) / 2.0, short, long))
+}
+
+/// Dot length from the marks: dots and dashes (3 dots) when both are there, else the
+/// gaps inside letters (1 dot) tell whether the marks are dots or dashes. The gaps
+/// inside letters then refine it: a slow envelope shortens marks as much as it
+/// lengthens gaps.
+fn estimate_unit(keys: &[Key]) -> Result<f64, Report> {
+    let marks: Vec<f64> = keys.iter().filter(|k| k.on).map(|k| k.duration).collect();
+    let rough = match two_means(&marks, 2.0) {
+        Some((threshold, short, _)) => short.min(threshold / 2.0),
+        None => {
+            let mark = marks.iter().sum::<f64>() / marks.len() as f64;
+            let shortest_gap = keys
+                .iter()
+                .filter(|k| !k.on)
+                .map(|k| k.duration)
+                .fold(f64::INFINITY, f64::min);
+            if mark > 2.0 * shortest_gap {
+                mark / 3.0
+            } else {
+                mark
+            }
+        }
+    };
+    let (units, time) = keys
+        .iter()
+        .filter(|k| k.on || k.duration < 2.0 * rough)
+        .fold((0.0, 0.0), |(units, time), key| {
+            let key_units = if key.duration < 2.0 * rough { 1.0 } else { 3.0 };
+            (units + key_units, time + key.duration)
+        });
+    let unit = time / units;
+    if !unit.is_finite() || unit <= 0.0 {
+        return Err(eyre!("Couldn't estimate the dot length"));
+    }
+    debug!("Dot length estimated at {:.4} s", unit);
+    Ok(unit)
+}
+
+/// 1 at the ideal length, 0.5 on the boundary, 0 twice as far (in ratio). No boundary on
+/// a side means no other kind there.
+fn confidence(units: f64, lower: Option<f64>, ideal: f64, upper: Option<f64>) -> f64 {
+    let boundary = if units < ideal { lower } else { upper };
+    match boundary {
+        Some(boundary) => {
+            let distance = (units / ideal).ln().abs() / (boundary / ideal).ln().abs();
+            (1.0 - distance / 2.0).clamp(0.0, 1.0)
+        }
+        None => 1.0,
+    }
+}
+
+/// Duration of the key in tracked dots, then follow the speed if it's a mark or an
+/// element gap
+fn track(key: &Key, tracked: &mut f64) -> f64 {
+    let units = key.duration / *tracked;
+    if key.on || units < 2.0 {
+        let ideal = if units < 2.0 { 1.0 } else { 3.0 };
+        let measured = (key.duration / ideal).clamp(*tracked / MAX_STEP, *tracked * MAX_STEP);
+        *tracked += TRACKING * (measured - *tracked);
+    }
+    units
+}
+
+/// Classify keying. Gaps of 2 dots or more are letter or word gaps (3 and 7 dots, or
+/// both stretched by Farnsworth spacing): two lengths of them mean both are there, a
+/// single length is taken as word gaps only if longer than 5 dots and not `spaced`.
+pub fn read(keys: &[Key], spaced: bool) -> Result<Reading, Report> {
+    let keys = normalize(keys);
+    if keys.is_empty() {
+        return Err(eyre!("No key down"));
+    }
+    let unit = estimate_unit(&keys)?;
+    // tracking backwards from the mean speed gives the starting speed, then durations are
+    // in dots of the speed tracked until them
+    let mut tracked = unit;
+    keys.iter().rev().for_each(|key| {
+        track(key, &mut tracked);
+    });
+    let units: Vec<f64> = keys.iter().map(|key| track(key, &mut tracked)).collect();
+    let gaps: Vec<f64> = keys
+        .iter()
+        .zip(&units)
+        .filter(|(key, units)| !key.on && **units >= 2.0)
+        .map(|(_, units)| *units)
+        .collect();
+    let mean_gap = gaps.iter().sum::<f64>() / gaps.len() as f64;
+    // letter gap and word gap ideals, with the boundary between them
+    let (letter, word, boundary) = match two_means(&gaps, 1.8) {
+        Some((threshold, letter, word)) => (Some(letter), Some(word), Some(threshold)),
+        None if spaced || mean_gap < 5.0 => (Some(mean_gap), None, None),
+        None => (None, Some(mean_gap), None),
+    };
+    let elements: Vec<Element> = keys
+        .iter()
+        .zip(&units)
+        .map(|(key, &units)| {
+            let (kind, confidence) = if key.on && units < 2.0 {
+                (ElementKind::Dot, confidence(units, None, 1.0, Some(2.0)))
+            } else if key.on {
+                (ElementKind::Dash, confidence(units, Some(2.0), 3.0, None))
+            } else if units < 2.0 {
+                (
+                    ElementKind::ElementGap,
+                    confidence(units, None, 1.0, Some(2.0)),
+                )
+            } else {
+                match (letter, word) {
+                    (Some(letter), _) if boundary.is_none_or(|b| units < b) => (
+                        ElementKind::LetterGap,
+                        confidence(units, Some(2.0), letter, boundary),
+                    ),
+                    (_, Some(word)) => (
+                        ElementKind::WordGap,
+                        confidence(units, boundary.or(Some(2.0)), word, None),
+                    ),
+                    _ => unreachable!(),
+                }
+            };
+            Element {
+                kind,
+                duration: key.duration,
+                units,
+                confidence,
+            }
+        })
+        .collect();
+    trace!("Elements: {:?}", elements);
+    let longest = |kind: ElementKind| {
+        elements
+            .iter()
+            .filter(|element| element.kind == kind)
+            .map(|element| element.units)
+            .fold(None, |longest: Option<f64>, units| {
+                Some(longest.map_or(units, |l| l.max(units)))
+            })
+    };
+    let shortest_letter_gap = elements
+        .iter()
+        .filter(|element| element.kind == ElementKind::LetterGap)
+        .map(|element| element.units)
+        .fold(None, |shortest: Option<f64>, units| {
+            Some(shortest.map_or(units, |s| s.min(units)))
+        });
+    let margin = match (shortest_letter_gap, longest(ElementKind::ElementGap)) {
+        (Some(letter_gap), Some(element_gap)) => letter_gap / element_gap,
+        (Some(_), None) => f64::INFINITY,
+        (None, _) => 0.0,
+    };
+    let spacing = if margin >= MIN_GAP_MARGIN || (spaced && shortest_letter_gap.is_some()) {
+        Spacing::Standard
+    } else {
+        Spacing::Smooshed
+    };
+    info!(
+        "Read {} elements at {:.1} wpm, {:?} spacing (gap margin {:.2})",
+        elements.len(),
+        1.2 / unit,
+        spacing,
+        margin
+    );
+    Ok(Reading {
+        elements,
+        unit,
+        spacing,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{keying as audio_keying, AudioConfig};
+    use crate::timing::Timing;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    /// Keying of a sentence with every duration off by up to `jitter` (a fraction), and
+    /// the speed going from `wpm` to `end_wpm`
+    fn hand_keyed(
+        sentence: &str,
+        spacing: Spacing,
+        wpm: f64,
+        end_wpm: f64,
+        jitter: f64,
+    ) -> Vec<Key> {
+        let config = AudioConfig {
+            timing: Timing {
+                wpm,
+                ..Timing::default()
+            },
+            ..AudioConfig::default()
+        };
+        let keys = audio_keying(sentence, spacing, &config).unwrap();
+        let mut rng = StdRng::seed_from_u64(380);
+        let last = (keys.len() - 1).max(1) as f64;
+        keys.iter()
+            .enumerate()
+            .map(|(i, key)| {
+                let slowdown = wpm / (wpm + (end_wpm - wpm) * i as f64 / last);
+                let noise = 1.0 + rng.gen_range(-jitter..=jitter);
+                Key {
+                    on: key.on,
+                    duration: key.duration * slowdown * noise,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_timings() {
+        let key = |on: bool, ms: f64| Key {
+            on,
+            duration: ms / 1000.0,
+        };
+        let expected = vec![key(true, 60.0), key(false, 60.0), key(true, 180.0)];
+        assert_eq!(parse_timings("[60, 60, 180]").unwrap(), expected);
+        assert_eq!(
+            parse_timings(
+                r#"[{"state": "on", "duration": 60}, {"state": false, "duration": 60},
+                    {"state": "down", "duration": 180}]"#
+            )
+            .unwrap(),
+            expected
+        );
+        assert_eq!(
+            parse_timings("state,duration\non,60\nup, 60\n1,180\n").unwrap(),
+            expected
+        );
+        assert_eq!(parse_timings("60\n60\n\n180").unwrap(), expected);
+        assert!(parse_timings("on,60\nsideways,60").is_err());
+        assert!(parse_timings("on,60\non,x").is_err());
+        assert!(parse_timings("[60, -1]").is_err());
+        assert!(parse_timings(r#"[{"state": "on"}]"#).is_err());
+        assert!(parse_timings("[60,").is_err());
+    }
+
+    #[test]
+    fn test_read_spaced() {
+        let keys = hand_keyed("hello world", Spacing::Standard, 20.0, 20.0, 0.0);
+        let reading = read(&keys, false).unwrap();
+        assert_eq!(reading.spacing, Spacing::Standard);
+        assert!((reading.wpm() - 20.0).abs() < 1e-6);
+        assert!((reading.confidence() - 1.0).abs() < 1e-6);
+        assert_eq!(
+            reading.to_morse(&Separators::default()),
+            ".... . .-.. .-.. --- / .-- --- .-. .-.. -.."
+        );
+        assert_eq!(reading.word_elements().len(), 2);
+    }
+
+    #[test]
+    fn test_read_drifting_speed() {
+        // slowing from 25 to 12 wpm, with sloppy timing: a fixed dot length fails
+        let keys = hand_keyed("the quick brown fox", Spacing::Standard, 25.0, 12.0, 0.2);
+        let reading = read(&keys, false).unwrap();
+        assert_eq!(reading.spacing, Spacing::Standard);
+        assert_eq!(
+            reading.to_morse(&Separators::default()),
+            "- .... . / --.- ..- .. -.-. -.- / -... .-. --- .-- -. / ..-. --- -..-"
+        );
+        assert!(reading.confidence() < 1.0);
+    }
+
+    #[test]
+    fn test_read_smooshed() {
+        let keys = hand_keyed("horse sos", Spacing::Smooshed, 18.0, 18.0, 0.1);
+        let reading = read(&keys, false).unwrap();
+        assert_eq!(reading.spacing, Spacing::Smooshed);
+        assert_eq!(
+            reading.smooshed_words(),
+            vec!["....---.-.....", "...---..."]
+        );
+        // letter gaps too close to element gaps to be trusted
+        let mut keys = hand_keyed("sos", Spacing::Standard, 20.0, 20.0, 0.0);
+        for key in keys.iter_mut().filter(|key| !key.on && key.duration > 0.1) {
+            key.duration = 0.14;
+        }
+        for key in keys.iter_mut().filter(|key| !key.on && key.duration < 0.1) {
+            key.duration = 0.085;
+        }
+        let reading = read(&keys, false).unwrap();
+        assert_eq!(reading.spacing, Spacing::Smooshed);
+        assert_eq!(reading.smooshed_words(), vec!["...---..."]);
+        assert_eq!(read(&keys, true).unwrap().spacing, Spacing::Standard);
+    }
+
+    #[test]
+    fn test_read_single_gap_length() {
+        let keys = hand_keyed("tee", Spacing::Standard, 20.0, 20.0, 0.0);
+        assert_eq!(
+            read(&keys, false).unwrap().to_morse(&Separators::default()),
+            "- . ."
+        );
+        let keys = hand_keyed("t e e", Spacing::Smooshed, 20.0, 20.0, 0.0);
+        assert_eq!(
+            read(&keys, false).unwrap().to_morse(&Separators::default()),
+            "- / . / ."
+        );
+        assert_eq!(
+            read(&keys, true).unwrap().to_morse(&Separators::default()),
+            "- . ."
+        );
+    }
+
+    #[test]
+    fn test_confidence() {
+        assert_eq!(confidence(1.0, None, 1.0, Some(2.0)), 1.0);
+        assert_eq!(confidence(0.5, None, 1.0, Some(2.0)), 1.0);
+        assert!((confidence(2.0, Some(2.0), 3.0, None) - 0.5).abs() < 1e-9);
+        assert!((confidence(4.0, None, 1.0, Some(2.0))).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_read_errors() {
+        assert!(read(&[], false).is_err());
+        let silence = Key {
+            on: false,
+            duration: 1.0,
+        };
+        assert!(read(&[silence], false).is_err());
+    }
+}
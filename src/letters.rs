@@ -0,0 +1,116 @@
+//! Decode smooshed morse into letter strings, without a dictionary: every way the code
+//! splits into letters of the code book is a valid decoding (call signs, codes...)
+
+use crate::merses::{get_merse_trie, merse_to_morse, smooshedmorse_to_merse, Merse, MerseTrie};
+use crate::morses::validate_morse_str;
+use color_eyre::{eyre::eyre, Report};
+use tracing::{debug, trace};
+
+/// Letter decodings are exponential in the code length, refuse to list more than this
+pub const MAX_LETTER_DECODINGS: u128 = 100_000;
+
+/// Return every letter string encoding to the given smooshed morse.
+/// The number of results grows exponentially with the input length: codes of more than
+/// `MAX_LETTER_DECODINGS` are an error, see `count_letters`
+pub fn decode_letters(smooshedmorse_word: &str) -> Result<Vec<String>, Report> {
+    let smooshedmorse_word = smooshedmorse_word.trim();
+    debug!("Decoding to letters: {}", smooshedmorse_word);
+    validate_morse_str(smooshedmorse_word)?;
+    let merse_word = smooshedmorse_to_merse(smooshedmorse_word)?;
+    decode_merse_letters(&merse_word)
+}
+
+pub fn decode_merse_letters(smooshedmerse_word: &Merse) -> Result<Vec<String>, Report> {
+    // an overflowing count is too many as well
+    let count = count_merse_letters(smooshedmerse_word).unwrap_or(u128::MAX);
+    if count > MAX_LETTER_DECODINGS {
+        return Err(eyre!(
+            "`{}` has more than {} letter decodings: count them with --count",
+            merse_to_morse(smooshedmerse_word),
+            MAX_LETTER_DECODINGS
+        ));
+    }
+    let trie = get_merse_trie();
+    let mut res: Vec<String> = Vec::new();
+    collect_parses(smooshedmerse_word, 0, trie, &mut String::new(), &mut res);
+    debug!("Found {} letter strings", res.len());
+    Ok(res)
+}
+
+/// Push to res every parse of merse[start..], following the already parsed letters
+fn collect_parses(
+    merse: &Merse,
+    start: usize,
+    trie: &MerseTrie,
+    parsed: &mut String,
+    res: &mut Vec<String>,
+) {
+    if start == merse.len() {
+        trace!("Parsed: {}", parsed);
+        res.push(parsed.clone());
+        return;
+    }
+    for (len, ch) in trie.prefixes(merse.iter().skip(start)) {
+        parsed.push(ch);
+        collect_parses(merse, start + len, trie, parsed, res);
+        parsed.pop();
+    }
+}
+
+/// Return the number of letter strings encoding to the given smooshed morse, without
+/// enumerating them
+pub fn count_letters(smooshedmorse_word: &str) -> Result<u128, Report> {
+    let smooshedmorse_word = smooshedmorse_word.trim();
+    debug!("Counting letter decodings: {}", smooshedmorse_word);
+    validate_morse_str(smooshedmorse_word)?;
+    let merse_word = smooshedmorse_to_merse(smooshedmorse_word)?;
+    count_merse_letters(&merse_word)
+}
+
+pub fn count_merse_letters(smooshedmerse_word: &Merse) -> Result<u128, Report> {
+    let trie = get_merse_trie();
+    // counts[i]: number of parses of smooshedmerse_word[i..]
+    let mut counts: Vec<u128> = vec![0; smooshedmerse_word.len() + 1];
+    counts[smooshedmerse_word.len()] = 1;
+    for i in (0..smooshedmerse_word.len()).rev() {
+        for (len, _) in trie.prefixes(smooshedmerse_word.iter().skip(i)) {
+            counts[i] = counts[i]
+                .checked_add(counts[i + len])
+                .ok_or_else(|| eyre!("Too many letter decodings to count"))?;
+        }
+        trace!("Parses from {}: {}", i, counts[i]);
+    }
+    Ok(counts[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_letters() {
+        assert_eq!(decode_letters("").unwrap(), vec![String::new()]);
+        assert_eq!(decode_letters(".").unwrap(), vec!["e".to_string()]);
+        assert_eq!(
+            decode_letters("-.").unwrap(),
+            vec!["te".to_string(), "n".to_string()]
+        );
+        assert!(decode_letters("...").unwrap().contains(&"ei".to_string()));
+        assert!(decode_letters(".-x").is_err());
+        // 142,368,356,257 decodings
+        let err = decode_letters(&".".repeat(40)).unwrap_err();
+        assert!(err.to_string().contains("--count"));
+    }
+
+    #[test]
+    fn test_count_letters() {
+        assert_eq!(count_letters("").unwrap(), 1);
+        assert_eq!(count_letters("-.").unwrap(), 2);
+        assert_eq!(count_letters("...").unwrap(), 4);
+        assert_eq!(
+            count_letters("....---.-.....").unwrap(),
+            decode_letters("....---.-.....").unwrap().len() as u128
+        );
+        assert!(count_letters(&".".repeat(400)).is_err());
+    }
+}
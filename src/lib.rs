@@ -1,12 +1,38 @@
+pub mod audio;
+pub mod batch;
+pub mod collisions;
+pub mod compounds;
 pub mod decode;
+pub mod design;
 pub mod encode;
+pub mod glyphs;
+pub mod graph;
+pub mod keying;
+pub mod letters;
+pub mod listen;
 pub mod merses;
 pub mod morses;
+pub mod optimize;
+pub mod pairs;
+pub mod records;
+pub mod repl;
+pub mod resmoosh;
+pub mod search;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod spaced;
+pub mod stats;
+#[cfg(test)]
+mod test_utils;
+pub mod timeline;
+pub mod timing;
+pub mod unicode;
 
 pub mod extra1;
 pub mod extra2;
 pub mod extra3;
 pub mod extra4;
+pub mod hmm;
 pub mod permutations;
 
 mod wordlist;
@@ -0,0 +1,297 @@
+//! Recover morse from audio: find the tone (Goertzel filter on a range of frequencies),
+//! follow its envelope and threshold it into keying, then read it with `keying`.
+//! The inverse of `audio`, robust to noise and speed.
+
+use crate::audio::Key;
+use crate::keying::{self, Reading};
+use crate::spaced::Separators;
+use color_eyre::{eyre::eyre, Report};
+use std::f64::consts::PI;
+use std::path::Path;
+use tracing::{debug, info, trace};
+
+/// Tone search range and step, Hz
+const TONE_MIN: f64 = 200.0;
+const TONE_MAX: f64 = 2000.0;
+const TONE_STEP: f64 = 10.0;
+/// Block length for the tone search, seconds
+const SEARCH_BLOCK: f64 = 0.025;
+/// Block length for the envelope, seconds: the time resolution of the keying
+const ENVELOPE_BLOCK: f64 = 0.004;
+/// Runs shorter than this many envelope blocks are glitches
+const MIN_RUN_BLOCKS: usize = 2;
+
+/// Morse recovered from audio
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recovered {
+    /// Tone frequency, Hz
+    pub tone: f64,
+    pub reading: Reading,
+}
+
+/// Mono samples in [-1, 1] and the sample rate of a WAV file, channels are averaged
+pub fn read_wav<P: AsRef<Path>>(path: P) -> Result<(Vec<f64>, u32), Report> {
+    let mut reader = hound::WavReader::open(&path)?;
+    let spec = reader.spec();
+    debug!("Reading {}: {:?}", path.as_ref().display(), spec);
+    let interleaved: Vec<f64> = match spec.sample_format {
+        hound::SampleFormat::Float => reader
+            .samples::<f32>()
+            .map(|s| s.map(f64::from))
+            .collect::<Result<_, _>>()?,
+        hound::SampleFormat::Int => {
+            let full_scale = f64::from(1u32 << (spec.bits_per_sample - 1));
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|s| f64::from(s) / full_scale))
+                .collect::<Result<_, _>>()?
+        }
+    };
+    let channels = usize::from(spec.channels.max(1));
+    let samples = interleaved
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f64>() / frame.len() as f64)
+        .collect();
+    Ok((samples, spec.sample_rate))
+}
+
+/// Recover morse from a WAV file, see `recover`
+pub fn recover_wav<P: AsRef<Path>>(
+    path: P,
+    tone: Option<f64>,
+    spaced: bool,
+) -> Result<Recovered, Report> {
+    let (samples, sample_rate) = read_wav(path)?;
+    recover(&samples, sample_rate, tone, spaced)
+}
+
+/// Recover morse from mono samples. The tone is searched if not given. With `spaced`,
+/// gaps of a single length are taken as letter gaps rather than word gaps.
+pub fn recover(
+    samples: &[f64],
+    sample_rate: u32,
+    tone: Option<f64>,
+    spaced: bool,
+) -> Result<Recovered, Report> {
+    let tone = match tone {
+        Some(tone) => tone,
+        None => detect_tone(samples, sample_rate)?,
+    };
+    let block = ((ENVELOPE_BLOCK * f64::from(sample_rate)).round() as usize).max(1);
+    let envelope: Vec<f64> = samples
+        .chunks(block)
+        .map(|chunk| goertzel(chunk, tone, sample_rate).sqrt())
+        .collect();
+    let block_time = block as f64 / f64::from(sample_rate);
+    let reading = keying::read(&threshold(&envelope, block_time)?, spaced)?;
+    info!(
+        "Heard {:.0} Hz: {}",
+        tone,
+        reading.to_morse(&Separators::default())
+    );
+    Ok(Recovered { tone, reading })
+}
+
+/// Power of the signal at the frequency
+fn goertzel(samples: &[f64], frequency: f64, sample_rate: u32) -> f64 {
+    let coeff = 2.0 * (2.0 * PI * frequency / f64::from(sample_rate)).cos();
+    let (mut s1, mut s2) = (0.0, 0.0);
+    for sample in samples {
+        let s0 = sample + coeff * s1 - s2;
+        s2 = s1;
+        s1 = s0;
+    }
+    (s1 * s1 + s2 * s2 - coeff * s1 * s2) / samples.len().max(1) as f64
+}
+
+/// Frequency of the search range with the most power, summed on short blocks so that
+/// keying doesn't spread the peak
+pub fn detect_tone(samples: &[f64], sample_rate: u32) -> Result<f64, Report> {
+    let block = ((SEARCH_BLOCK * f64::from(sample_rate)).round() as usize).max(1);
+    let max = TONE_MAX.min(f64::from(sample_rate) / 2.0 - TONE_STEP);
+    let steps = ((max - TONE_MIN) / TONE_STEP).floor().max(0.0) as usize;
+    let (tone, power) = (0..=steps)
+        .map(|i| TONE_MIN + i as f64 * TONE_STEP)
+        .map(|frequency| {
+            let power: f64 = samples
+                .chunks(block)
+                .map(|chunk| goertzel(chunk, frequency, sample_rate))
+                .sum();
+            (frequency, power)
+        })
+        .fold((0.0, 0.0), |best, candidate| {
+            if candidate.1 > best.1 {
+                candidate
+            } else {
+                best
+            }
+        });
+    if power <= 0.0 {
+        return Err(eyre!("No tone found"));
+    }
+    debug!("Tone detected at {} Hz", tone);
+    Ok(tone)
+}
+
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    sorted[((sorted.len() - 1) as f64 * p).round() as usize]
+}
+
+/// Threshold the envelope into keying, with hysteresis and without glitches
+fn threshold(envelope: &[f64], block_time: f64) -> Result<Vec<Key>, Report> {
+    if envelope.is_empty() {
+        return Err(eyre!("No audio"));
+    }
+    let mut sorted = envelope.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).expect("NaN in envelope"));
+    let (low, high) = (percentile(&sorted, 0.1), percentile(&sorted, 0.9));
+    if high <= low * 2.0 {
+        return Err(eyre!("No keying heard: the tone level is steady"));
+    }
+    let (off_below, on_above) = (low + 0.4 * (high - low), low + 0.6 * (high - low));
+    // runs of (on, blocks)
+    let mut runs: Vec<(bool, usize)> = Vec::new();
+    let mut on = false;
+    for level in envelope {
+        if on && *level < off_below {
+            on = false;
+        } else if !on && *level > on_above {
+            on = true;
+        }
+        match runs.last_mut() {
+            Some((run_on, blocks)) if *run_on == on => *blocks += 1,
+            _ => runs.push((on, 1)),
+        }
+    }
+    // glitches join the run before them
+    let mut merged: Vec<(bool, usize)> = Vec::new();
+    for (on, blocks) in runs {
+        match merged.last_mut() {
+            Some((_, last_blocks)) if blocks < MIN_RUN_BLOCKS => *last_blocks += blocks,
+            Some((last_on, last_blocks)) if *last_on == on => *last_blocks += blocks,
+            _ => merged.push((on, blocks)),
+        }
+    }
+    trace!("Keying runs (on, blocks): {:?}", merged);
+    Ok(merged
+        .into_iter()
+        .map(|(on, blocks)| Key {
+            on,
+            duration: blocks as f64 * block_time,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{keying as audio_keying, render, AudioConfig, Spacing};
+    use crate::timing::Timing;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    /// Synthesized sentence with uniform noise of the given amplitude (full scale is 1)
+    fn noisy(sentence: &str, spacing: Spacing, config: &AudioConfig, noise: f64) -> Vec<f64> {
+        let keys = audio_keying(sentence, spacing, config).unwrap();
+        let mut rng = StdRng::seed_from_u64(380);
+        let silence = vec![0; config.sample_rate as usize / 4];
+        silence
+            .iter()
+            .chain(render(&keys, config).iter())
+            .chain(silence.iter())
+            .map(|s| f64::from(*s) / f64::from(i16::MAX) + rng.gen_range(-noise..=noise))
+            .collect()
+    }
+
+    #[test]
+    fn test_goertzel() {
+        let sine: Vec<f64> = (0..800)
+            .map(|i| (2.0 * PI * 1000.0 * i as f64 / 8000.0).sin())
+            .collect();
+        assert!(goertzel(&sine, 1000.0, 8000) > 100.0 * goertzel(&sine, 1300.0, 8000));
+        assert!((detect_tone(&sine, 8000).unwrap() - 1000.0).abs() <= TONE_STEP);
+    }
+
+    #[test]
+    fn test_recover_spaced() {
+        let config = AudioConfig {
+            tone: 700.0,
+            ..AudioConfig::default()
+        };
+        let samples = noisy("hello world", Spacing::Standard, &config, 0.3);
+        let recovered = recover(&samples, config.sample_rate, None, false).unwrap();
+        assert!((recovered.tone - 700.0).abs() <= TONE_STEP);
+        assert!((recovered.reading.wpm() - 20.0).abs() < 1.0);
+        assert_eq!(recovered.reading.spacing, Spacing::Standard);
+        assert_eq!(
+            recovered.reading.to_morse(&Separators::default()),
+            ".... . .-.. .-.. --- / .-- --- .-. .-.. -.."
+        );
+    }
+
+    #[test]
+    fn test_recover_farnsworth() {
+        let config = AudioConfig {
+            timing: Timing {
+                wpm: 30.0,
+                farnsworth_wpm: Some(12.0),
+                ..Timing::default()
+            },
+            sample_rate: 8000,
+            ..AudioConfig::default()
+        };
+        let samples = noisy("paris paris", Spacing::Standard, &config, 0.2);
+        let recovered = recover(&samples, config.sample_rate, Some(600.0), false).unwrap();
+        assert!((recovered.reading.wpm() - 30.0).abs() < 2.0);
+        assert_eq!(
+            recovered.reading.to_morse(&Separators::default()),
+            ".--. .- .-. .. ... / .--. .- .-. .. ..."
+        );
+    }
+
+    #[test]
+    fn test_recover_smooshed() {
+        let config = AudioConfig {
+            timing: Timing {
+                wpm: 15.0,
+                ..Timing::default()
+            },
+            ..AudioConfig::default()
+        };
+        let samples = noisy("horse sos", Spacing::Smooshed, &config, 0.3);
+        let recovered = recover(&samples, config.sample_rate, None, false).unwrap();
+        assert_eq!(recovered.reading.spacing, Spacing::Smooshed);
+        assert_eq!(
+            recovered.reading.smooshed_words(),
+            vec!["....---.-.....".to_string(), "...---...".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_recover_single_spaced_word() {
+        let config = AudioConfig::default();
+        let samples = noisy("tee", Spacing::Standard, &config, 0.1);
+        let recovered = recover(&samples, config.sample_rate, None, true).unwrap();
+        assert_eq!(recovered.reading.to_morse(&Separators::default()), "- . .");
+    }
+
+    #[test]
+    fn test_recover_errors() {
+        assert!(recover(&[], 8000, None, false).is_err());
+        assert!(recover(&[0.0; 8000], 8000, Some(600.0), false).is_err());
+    }
+
+    #[test]
+    fn test_recover_wav() {
+        let path =
+            std::env::temp_dir().join(format!("smooshedmorse-listen-{}.wav", std::process::id()));
+        let config = AudioConfig::default();
+        crate::audio::render_wav("sos", Spacing::Smooshed, &config, &path).unwrap();
+        let recovered = recover_wav(&path, None, false).unwrap();
+        assert_eq!(
+            recovered.reading.smooshed_words(),
+            vec!["...---...".to_string()]
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,46 +1,238 @@
 // use crate::input::InputLines;
-use clap::{App, AppSettings, SubCommand};
-use color_eyre::Report;
-use serde_json::json;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use color_eyre::{eyre::eyre, Report};
 use tracing::trace;
 use tracing_subscriber::EnvFilter;
 
+use smooshedmorse::audio;
+use smooshedmorse::batch;
+use smooshedmorse::collisions;
+use smooshedmorse::compounds;
 use smooshedmorse::decode;
+use smooshedmorse::design;
 use smooshedmorse::encode;
 use smooshedmorse::extra1;
 use smooshedmorse::extra2;
 use smooshedmorse::extra3;
 use smooshedmorse::extra4;
+use smooshedmorse::glyphs::{self, Glyphs, Style};
+use smooshedmorse::graph;
+use smooshedmorse::hmm;
+use smooshedmorse::keying;
+use smooshedmorse::letters;
+use smooshedmorse::listen;
+use smooshedmorse::morses;
+use smooshedmorse::optimize;
+use smooshedmorse::pairs;
 use smooshedmorse::permutations;
+use smooshedmorse::records::{count_value, print_records, Format, Record, ALL_FIELDS, FORMATS};
+use smooshedmorse::repl;
+use smooshedmorse::resmoosh;
+#[cfg(feature = "server")]
+use smooshedmorse::server;
+use smooshedmorse::spaced;
+use smooshedmorse::stats;
+use smooshedmorse::timeline;
+use smooshedmorse::timing;
+use smooshedmorse::unicode;
 
 fn main() -> Result<(), Report> {
     setup()?;
-    let matches = App::new("smooshedmorse")
+    let app = App::new("smooshedmorse")
         .about("Smooshed Morse encoding and decoding
 Normally, you would indicate where one letter ends and the next begins, for instance
 with a space between the letters' codes, but in smooshed morse  all the
 coded letters are smooshed together into a single string consisting of only dashes and dots.
 https://www.reddit.com/r/dailyprogrammer/comments/cmd1hb/20190805_challenge_380_easy_smooshed_morse_code_1/")
         .setting(AppSettings::SubcommandRequiredElseHelp)
-                .arg_from_usage("-j, --json 'Serialize output to JSON'")
+        .arg(
+            Arg::from_usage("--format=[FORMAT] 'Output format: text, or structured records as json, ndjson (one JSON object per line), csv or tsv'")
+                .possible_values(&FORMATS)
+                .default_value("text"),
+        )
+        .arg_from_usage("--jobs=[N] 'Number of worker threads (builds with the parallel feature only)'")
+        .arg(
+            Arg::from_usage("--style=[STYLE] 'Rendering of the morse output of encode, decode and permutations: ascii (.-), unicode (·−), words (dit-dah) or binary (01)'")
+                .possible_values(&glyphs::STYLES)
+                .default_value("ascii"),
+        )
+        .arg(Arg::from_usage("--dot=[TEXT] 'Render dots as TEXT instead of a style (with --dash)'").requires("dash"))
+        .arg(Arg::from_usage("--dash=[TEXT] 'Render dashes as TEXT instead of a style (with --dot)'").requires("dot"))
+        .arg_from_usage("--dots=[GLYPHS] 'Characters also read as dots in morse input (default: ·•∙)'")
+        .arg_from_usage("--dashes=[GLYPHS] 'Characters also read as dashes in morse input (default: −–—_)'")
+        .arg_from_usage("--code-book=[FILE] 'Letter codes to use instead of the standard ones, as written by design-code'")
+        .arg(
+            Arg::from_usage("--locale=[LOCALE] 'Case mapping of the input: default (Unicode) or Turkish and Azeri (tr, az: I to ı, İ to i)'")
+                .possible_values(&unicode::LOCALES)
+                .default_value("default"),
+        )
+        .arg_from_usage("--strip-whitespace 'Remove whitespace inside smooshed morse input, not only around it'")
         .subcommand(
             SubCommand::with_name("encode")
                 .about("Encode a word to smooshedmorse.\nExample:\nsmooshedmorse encode Horse")
-                .arg_from_usage("<WORD> 'Word to be encoded to smooshedmorse'")
+                .arg_from_usage("-s, --spaced 'Encode a whole sentence to standard morse, with letter and word gaps'")
+                .arg_from_usage("--letter-separator=[SEP] 'Letter separator of spaced morse (default: \" \")'")
+                .arg_from_usage("--word-separator=[SEP] 'Word separator of spaced morse (default: \" / \")'")
+                .arg_from_usage("-t, --transliterate 'Spell letters missing from morse code and its ITU extensions in ASCII (û as u, ß as ss)'")
+                .arg_from_usage("--wav=[FILE] 'Also render the code as a sine tone WAV file (without letter gaps, or with them with --spaced)'")
+                .arg_from_usage("--wpm=[WPM] 'Audio character speed, words per minute (default: 20)'")
+                .arg_from_usage("--farnsworth=[WPM] 'Audio overall speed, stretching the gaps (default: the character speed)'")
+                .arg_from_usage("--tone=[HZ] 'Audio tone frequency (default: 600)'")
+                .arg_from_usage("--sample-rate=[HZ] 'Audio sample rate (default: 22050)'")
+                .arg_from_usage("--ramp=[MS] 'Audio keying rise and fall time, milliseconds (default: 5)'")
+                .arg_from_usage("--timeline=[FILE] 'Also write the on/off schedule of the keying with the same timing as the audio, for lights and buzzers'")
+                .arg(
+                    Arg::from_usage("--timeline-format=[FORMAT] 'Timeline file format: json (start, duration in ms and state of each period) or compact binary'")
+                        .possible_values(&timeline::TIMELINE_FORMATS)
+                        .default_value("json"),
+                )
+                .arg_from_usage("--play 'Also play the keying on the terminal (stderr) as a blinking block, in real time'")
+                .arg_from_usage("--dash-ratio=[DOTS] 'Dash length, in dots (default: 3)'")
+                .arg_from_usage("--element-gap=[DOTS] 'Gap between the dots and dashes of a letter, in dots (default: 1)'")
+                .arg_from_usage("--letter-gap=[DOTS] 'Gap between letters, in dots (default: 3)'")
+                .arg_from_usage("--word-gap=[DOTS] 'Gap between words, in dots (default: 7)'")
+                .arg_from_usage("<WORD> 'Word to be encoded to smooshedmorse (or sentence with --spaced), - to read one per line from stdin'")
+        )
+        .subcommand(
+            SubCommand::with_name("play")
+                .about("Play a timeline file (JSON, binary, or key timings) on the terminal as a blinking block, in real time")
+                .arg_from_usage("<TIMELINE> 'Timeline file, as written by encode --timeline'")
         )
         .subcommand(
             SubCommand::with_name("decode")
                 .about("Decode a smooshedmorse English word.\nExample:\nsmooshedmorse decode -- '....---.-.....'")
                 .arg_from_usage("-w, --words=[FILE] 'Word list file to use'")
-                .arg_from_usage("<SMOOSHEDMORSE> 'Smooshedmorse word to decode (give it after --)'")
+                .arg_from_usage("-l, --letters 'Decode to every letter string, not only to words of the word list'")
+                .arg_from_usage("-c, --count 'Only count the letter strings, for long inputs (implies --letters)'")
+                .arg_from_usage("-s, --spaced 'Decode standard morse, with letter and word gaps'")
+                .arg_from_usage("--letter-separator=[SEP] 'Letter separator of spaced morse (default: \" \")'")
+                .arg_from_usage("--word-separator=[SEP] 'Word separator of spaced morse (default: \" / \")'")
+                .arg_from_usage("--wav=[FILE] 'Decode a morse recording instead: spaced morse if letter gaps are heard, else smooshed words'")
+                .arg_from_usage("--tone=[HZ] 'Tone frequency of the recording (default: detected)'")
+                .arg_from_usage("--timings=[FILE] 'Decode key down and up durations in milliseconds instead, as CSV or JSON (- for stdin)'")
+                .arg_from_usage("--viterbi 'Decode --wav or --timings keying with a hidden Markov model of the word list letters, listing the most likely texts'")
+                .arg_from_usage("--best=[N] 'Number of texts listed by --viterbi (default: 5)'")
+                .arg_from_usage("--order=[N] 'Order of the character n-gram model of --viterbi (default: 3)'")
+                .arg(
+                    Arg::from_usage("<SMOOSHEDMORSE> 'Smooshedmorse word to decode (give it after --), - to read one per line from stdin'")
+                        .required_unless_one(&["wav", "timings"]),
+                )
         )
         .subcommand(
             SubCommand::with_name("permutations")
                 .about("Given a smooshed Morse code encoding of a permutation of the alphabet, find one of the permutations it encodes. Implement smooshedmorse challenge 2: https://www.reddit.com/r/dailyprogrammer/comments/cn6gz5/20190807_challenge_380_intermediate_smooshed/\nExample:\nsmooshedmorse permutations -- '.--...-.-.-.....-.--........----.-.-..---.---.--.--.-.-....-..-...-.---..--.----..'")
+                .arg_from_usage("-f, --file=[FILE] 'Solve every alphabet permutation of the file, one per line'")
                 .arg_from_usage(
                     "[ALPHABET_PERMUTATION] 'Smooshedmorse alphabet permutation to decode, if not given a random one is generated'"
                     )
         )
+        .subcommand(
+            SubCommand::with_name("unsmoosh")
+                .about("Convert smooshed morse to spaced morse, splitting it in words of the word list.\nExample:\nsmooshedmorse unsmoosh -- '......-...-..---.-----.-..-..-..'")
+                .arg_from_usage("-w, --words=[FILE] 'Word list file to use'")
+                .arg_from_usage("--letter-separator=[SEP] 'Letter separator of spaced morse (default: \" \")'")
+                .arg_from_usage("--word-separator=[SEP] 'Word separator of spaced morse (default: \" / \")'")
+                .arg_from_usage("<SMOOSHEDMORSE> 'Smooshedmorse sentence to convert (give it after --)'")
+        )
+        .subcommand(
+            SubCommand::with_name("smoosh")
+                .about("Convert spaced morse to smooshed morse, removing letter and word gaps.\nExample:\nsmooshedmorse smoosh -- '.... . .-.. .-.. --- / .-- --- .-. .-.. -..'")
+                .arg_from_usage("--letter-separator=[SEP] 'Letter separator of spaced morse (default: \" \")'")
+                .arg_from_usage("--word-separator=[SEP] 'Word separator of spaced morse (default: \" / \")'")
+                .arg_from_usage("<MORSE> 'Spaced morse sentence to convert (give it after --)'")
+        )
+        .subcommand(
+            SubCommand::with_name("collisions")
+                .about("List the largest groups of words of the word list sharing a code, or the number of groups of each size.\nExample:\nsmooshedmorse collisions --top 5 --word-length 8")
+                .arg_from_usage("-w, --words=[FILE] 'Word list file to use'")
+                .arg_from_usage("-k, --top=[K] 'Number of groups to list, 0 for all (default: 10)'")
+                .arg_from_usage("--histogram 'List the number of groups of each size instead (size 1 are the unambiguous codes)'")
+                .arg_from_usage("--word-length=[N] 'Only consider words of N letters'")
+                .arg_from_usage("--code-length=[N] 'Only consider codes of N dots and dashes'")
+        )
+        .subcommand(
+            SubCommand::with_name("compounds")
+                .about("List the words whose code is also the code of two or more shorter words of the word list, with the fewest such words.\nExample:\nsmooshedmorse compounds --unambiguous")
+                .arg_from_usage("-w, --words=[FILE] 'Word list file to use'")
+                .arg_from_usage("-u, --unambiguous 'List the truly unambiguous words instead: the only ones of their code, which splits in no other words'")
+        )
+        .subcommand(
+            SubCommand::with_name("design-code")
+                .about("Design the prefix-free code of the shortest mean letter duration for the letter frequencies of the word list (weighted by a frequency column after the words, if any), compared with the standard code.\nExample:\nsmooshedmorse design-code --output code.txt && smooshedmorse --code-book code.txt encode sos")
+                .arg_from_usage("-w, --words=[FILE] 'Word list file to use, with an optional frequency column'")
+                .arg_from_usage("-o, --output=[FILE] 'Write the code book to FILE, for --code-book'")
+                .arg_from_usage("--dash-ratio=[DOTS] 'Dash length, in dots (default: 3)'")
+                .arg_from_usage("--element-gap=[DOTS] 'Gap between the dots and dashes of a letter, in dots (default: 1)'")
+                .arg_from_usage("--letter-gap=[DOTS] 'Gap between letters, in dots (default: 3)'")
+        )
+        .subcommand(
+            SubCommand::with_name("duration")
+                .about("List the fastest and slowest words of the word list to send, in dot units and seconds.\nExample:\nsmooshedmorse duration --letters 6 --wpm 15 --farnsworth 10")
+                .arg_from_usage("-w, --words=[FILE] 'Word list file to use'")
+                .arg_from_usage("-n, --letters=[N] 'Only consider words of N letters'")
+                .arg_from_usage("-k, --top=[K] 'Number of words listed each way (default: 10)'")
+                .arg_from_usage("--smooshed 'Time the words smooshed, without letter gaps'")
+                .arg_from_usage("--wpm=[WPM] 'Character speed, words per minute (default: 20)'")
+                .arg_from_usage("--farnsworth=[WPM] 'Overall speed, stretching the gaps (default: the character speed)'")
+                .arg_from_usage("--dash-ratio=[DOTS] 'Dash length, in dots (default: 3)'")
+                .arg_from_usage("--element-gap=[DOTS] 'Gap between the dots and dashes of a letter, in dots (default: 1)'")
+                .arg_from_usage("--letter-gap=[DOTS] 'Gap between letters, in dots (default: 3)'")
+                .arg_from_usage("--word-gap=[DOTS] 'Gap between words, in dots (default: 7)'")
+        )
+        .subcommand(
+            SubCommand::with_name("graph")
+                .about("Write the collision graph of the word list as GraphViz DOT or GraphML, for visual inspection.\nExample:\nsmooshedmorse graph --min-group 8 | dot -Tsvg > collisions.svg")
+                .arg_from_usage("-w, --words=[FILE] 'Word list file to use'")
+                .arg(
+                    Arg::from_usage("-m, --mode=[MODE] 'Edges: code (words to their code node), shared (words sharing a code) or edit (also words whose codes are one edit apart)'")
+                        .possible_values(&graph::GRAPH_MODES)
+                        .default_value("code"),
+                )
+                .arg(
+                    Arg::from_usage("--graph-format=[FORMAT] 'Graph file format'")
+                        .possible_values(&graph::GRAPH_FORMATS)
+                        .default_value("dot"),
+                )
+                .arg_from_usage("-o, --output=[FILE] 'Write the graph to FILE instead of stdout'")
+                .arg_from_usage("--min-group=[N] 'Only consider codes of at least N words (default: 2)'")
+                .arg_from_usage("--word-length=[N] 'Only consider words of N letters'")
+                .arg_from_usage("--code-length=[N] 'Only consider codes of N dots and dashes'")
+        )
+        .subcommand(
+            SubCommand::with_name("optimize-code")
+                .about("Search by simulated annealing for the letter codes making the fewest colliding words of the word list when smooshed, permuting the current codes or choosing among all codes up to 4 symbols.\nExample:\nsmooshedmorse optimize-code --seed 380 --iterations 5000 --output code.txt")
+                .arg_from_usage("-w, --words=[FILE] 'Word list file to use'")
+                .arg(
+                    Arg::from_usage("-c, --codes=[SET] 'Codes to give the letters: the current ones permuted, or all codes up to 4 symbols'")
+                        .possible_values(&optimize::CODE_SETS)
+                        .default_value("permute"),
+                )
+                .arg_from_usage("-i, --iterations=[N] 'Number of moves to try (default: 1000)'")
+                .arg_from_usage("--temperature=[WORDS] 'Starting temperature, in colliding words (default: 100)'")
+                .arg_from_usage("--seed=[N] 'Seed of the search, random by default'")
+                .arg_from_usage("--progress=[N] 'Report the search on stderr every N moves, 0 for never (default: 100)'")
+                .arg_from_usage("-o, --output=[FILE] 'Write the code book found to FILE, for --code-book'")
+        )
+        .subcommand(
+            SubCommand::with_name("pairs")
+                .about("Find the pairs of words of the word list whose codes are related: reversed, complemented (dots and dashes swapped) or both.\nExample:\nsmooshedmorse pairs --transform complement")
+                .arg_from_usage("-w, --words=[FILE] 'Word list file to use'")
+                .arg(
+                    Arg::from_usage("-t, --transform=[TRANSFORM] 'Transform of the first code giving the second: reverse, complement or revcomp (both)'")
+                        .possible_values(&pairs::TRANSFORMS)
+                        .default_value("reverse"),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("stats")
+                .about("Report statistics of the word list: code lengths, dots and dashes, letters, collisions and the information lost by smooshing.\nExample:\nsmooshedmorse --format json stats")
+                .arg_from_usage("-w, --words=[FILE] 'Word list file to use'")
+        )
+        .subcommand(
+            SubCommand::with_name("repl")
+                .about("Interactive session: load the word list once, then run commands (encode, decode, search, stats...), type help for the list")
+                .arg_from_usage("-w, --words=[FILE] 'Word list file to use'")
+        )
         .subcommand(
             SubCommand::with_name("extra1")
         )
@@ -52,76 +244,356 @@ https://www.reddit.com/r/dailyprogrammer/comments/cmd1hb/20190805_challenge_380_
         )
         .subcommand(
             SubCommand::with_name("extra4")
-        )
-        .get_matches();
+        );
+    #[cfg(feature = "server")]
+    let app = app.subcommand(
+        SubCommand::with_name("serve")
+            .about("Serve a local HTTP JSON API: GET /health, POST /encode, POST /decode, POST /permutations, GET /search")
+            .arg_from_usage("-a, --address=[ADDR] 'Address to listen on (default: 127.0.0.1:8080)'")
+            .arg_from_usage("-w, --words=[FILE] 'Word list file to use'")
+            .arg_from_usage("--max-body=[BYTES] 'Maximum request body size (default: 65536)'")
+            .arg_from_usage("--workers=[N] 'Number of threads answering requests (default: 4)'")
+    );
+    let matches = app.get_matches();
     trace!(?matches);
+    if let Some(jobs) = matches.value_of("jobs") {
+        set_jobs(jobs.parse()?)?;
+    }
+    if let Some(code_book) = matches.value_of("code-book") {
+        morses::set_code_book(&std::fs::read_to_string(code_book)?)?;
+    }
+    // safe unwrap, has a default
+    unicode::set_locale(matches.value_of("locale").unwrap().parse()?)?;
+    let format: Format = matches.value_of("format").unwrap().parse()?; // safe unwrap, has a default
+    let style = style(&matches)?;
+    let glyphs = glyphs(&matches)?;
     match matches.subcommand() {
         ("encode", Some(submatches)) => {
             trace!(?submatches);
             // day5::star1(InputLines::from(submatches.value_of(input_par)))?;
-            let res = encode::encode(submatches.value_of("WORD").unwrap())?; // safe unwrap, positional argument is mandatory
-            if matches.is_present("json") {
-                print_json(&res)
+            let word = submatches.value_of("WORD").unwrap(); // safe unwrap, positional argument is mandatory
+            let transliterate = submatches.is_present("transliterate");
+            let prepare = |text: &str| match transliterate {
+                true => unicode::transliterate(text),
+                false => Ok(text.to_string()),
+            };
+            if word == batch::STDIN_ARG {
+                if ["wav", "timeline", "play"]
+                    .iter()
+                    .any(|arg| submatches.is_present(arg))
+                {
+                    return Err(eyre!(
+                        "--wav, --timeline and --play key a single input, not stdin"
+                    ));
+                }
+                let seps = match submatches.is_present("spaced") {
+                    true => Some(separators(submatches)?),
+                    false => None,
+                };
+                batch::run_stdin(format, |line| {
+                    let line = prepare(line)?;
+                    let codes = match &seps {
+                        Some(seps) => vec![spaced::encode_spaced(&line, seps)?],
+                        None => encode::encode(&line)?,
+                    };
+                    Ok(codes.iter().map(|code| style.render(code)).collect())
+                })?;
+                return Ok(());
+            }
+            let text = prepare(word)?;
+            let code = if submatches.is_present("spaced") {
+                spaced::encode_spaced(&text, &separators(submatches)?)?
             } else {
-                print_result(&res);
+                encode::encode(&text)?.remove(0)
+            };
+            let spacing = match submatches.is_present("spaced") {
+                true => audio::Spacing::Standard,
+                false => audio::Spacing::Smooshed,
+            };
+            // the same timing for the units, the audio and the timeline
+            let config = audio_config(submatches)?;
+            let units = config.timing.text_units(&text, spacing)?;
+            let round = |x: f64| (x * 1e4).round() / 1e4;
+            let mut record = Record::new()
+                .with("input", word)
+                .with("length", code.chars().count())
+                .with("code", style.render(&code))
+                .with("units", round(units))
+                .with("seconds", round(config.timing.seconds(units)));
+            if let Some(wav) = submatches.value_of("wav") {
+                let duration = audio::render_wav(&text, spacing, &config, wav)?;
+                record = record.with("wav", wav).with("duration", round(duration));
+            }
+            let keys = match submatches.is_present("timeline") || submatches.is_present("play") {
+                true => audio::keying(&text, spacing, &config)?,
+                false => Vec::new(),
+            };
+            let events = timeline::timeline(&keys);
+            if let Some(file) = submatches.value_of("timeline") {
+                let timeline_format = submatches.value_of("timeline-format").unwrap().parse()?; // default value
+                timeline::write_timeline(file, &events, timeline_format)?;
+                record = record
+                    .with("timeline", file)
+                    .with("duration", round(audio::duration(&keys)));
+            }
+            print_records(&[record], format, "code")?;
+            if submatches.is_present("play") {
+                play(&events)?;
             }
         }
+        ("play", Some(submatches)) => {
+            trace!(?submatches);
+            let file = submatches.value_of("TIMELINE").unwrap(); // mandatory positional
+            play(&timeline::read_timeline(file)?)?;
+        }
         ("decode", Some(submatches)) => {
             trace!(?submatches);
-            let res = decode::decode(
-                submatches.value_of("SMOOSHEDMORSE").unwrap(),
-                submatches.value_of("words"),
-            )?; // idem
-            if matches.is_present("json") {
-                print_json(&res)
-            } else {
-                print_result(&res);
+            if let Some(wav) = submatches.value_of("wav") {
+                let tone = submatches.value_of("tone").map(str::parse).transpose()?;
+                let recovered = listen::recover_wav(wav, tone, submatches.is_present("spaced"))?;
+                decode_reading(wav, &recovered.reading, submatches, format, &style)?;
+                return Ok(());
+            }
+            if let Some(timings) = submatches.value_of("timings") {
+                let keys = keying::read_timings_file(timings)?;
+                let reading = keying::read(&keys, submatches.is_present("spaced"))?;
+                decode_reading(timings, &reading, submatches, format, &style)?;
+                return Ok(());
+            }
+            if submatches.is_present("viterbi") {
+                return Err(eyre!("--viterbi decodes keying, from --wav or --timings"));
+            }
+            let smooshedmorse = submatches.value_of("SMOOSHEDMORSE").unwrap(); // idem
+            if submatches.is_present("spaced") {
+                glyphs.validate_separators(&separators(submatches)?)?;
+            }
+            if smooshedmorse == batch::STDIN_ARG {
+                decode_stdin(submatches, format, &glyphs)?;
+                return Ok(());
             }
+            let record = Record::new().with("input", smooshedmorse.trim());
+            let record = decode_record(&glyphs.normalize(smooshedmorse), record, submatches, None)?;
+            print_records(&[record], format, decode_field(submatches))?;
         }
         ("permutations", Some(submatches)) => {
             trace!(?submatches);
-            let res = permutations::run(submatches.value_of("ALPHABET_PERMUTATION"))?;
-            if matches.is_present("json") {
-                print_json(&res)
+            let smalphas = match submatches.value_of("file") {
+                Some(file) => permutations::read_smalphas(file)?
+                    .iter()
+                    .map(|smalpha| glyphs.normalize(smalpha))
+                    .collect(),
+                None => vec![permutations::smalpha_or_random(
+                    submatches
+                        .value_of("ALPHABET_PERMUTATION")
+                        .map(|smalpha| glyphs.normalize(smalpha))
+                        .as_deref(),
+                )?],
+            };
+            let alphabets = permutations::run_batch(&smalphas, None)?;
+            let records: Vec<Record> = smalphas
+                .into_iter()
+                .zip(alphabets)
+                .map(|(smalpha, alphabet)| {
+                    Record::new()
+                        .with("input", style.render(&smalpha))
+                        .with("alphabet", alphabet)
+                })
+                .collect();
+            print_records(&records, format, "alphabet")?;
+        }
+        ("unsmoosh", Some(submatches)) => {
+            trace!(?submatches);
+            let smooshedmorse = submatches.value_of("SMOOSHEDMORSE").unwrap();
+            let unsmooshed = resmoosh::unsmoosh(
+                smooshedmorse,
+                submatches.value_of("words"),
+                &separators(submatches)?,
+            )?;
+            let record = Record::new()
+                .with("input", smooshedmorse.trim())
+                .with("words", unsmooshed.words)
+                .with("morse", unsmooshed.morse);
+            print_records(&[record], format, "morse")?;
+        }
+        ("smoosh", Some(submatches)) => {
+            trace!(?submatches);
+            let morse = submatches.value_of("MORSE").unwrap();
+            let code = resmoosh::smoosh(morse, &separators(submatches)?)?.remove(0);
+            let record = Record::new()
+                .with("input", morse.trim())
+                .with("length", code.len())
+                .with("code", code);
+            print_records(&[record], format, "code")?;
+        }
+        ("collisions", Some(submatches)) => {
+            trace!(?submatches);
+            let filter = collisions_filter(submatches)?;
+            let words = submatches.value_of("words");
+            let records = if submatches.is_present("histogram") {
+                collisions::run_histogram(words, &filter)?
             } else {
-                print_result(&res);
+                let top = match submatches.value_of("top") {
+                    Some(top) => top.parse()?,
+                    None => collisions::DEFAULT_TOP,
+                };
+                collisions::run(words, &filter, top)?
+            };
+            print_records(&records, format, ALL_FIELDS)?;
+        }
+        ("compounds", Some(submatches)) => {
+            trace!(?submatches);
+            let records = compounds::run(
+                submatches.value_of("words"),
+                submatches.is_present("unambiguous"),
+            )?;
+            print_records(&records, format, ALL_FIELDS)?;
+        }
+        ("design-code", Some(submatches)) => {
+            trace!(?submatches);
+            let (records, code_book) =
+                design::run(submatches.value_of("words"), &timing(submatches)?)?;
+            if let Some(output) = submatches.value_of("output") {
+                std::fs::write(output, code_book + "\n")?;
             }
+            print_records(&records, format, ALL_FIELDS)?;
         }
-        ("extra1", Some(submatches)) => {
+        ("duration", Some(submatches)) => {
             trace!(?submatches);
-            let res = extra1::run()?;
-            if matches.is_present("json") {
-                print_json(&res)
-            } else {
-                print_result(&res);
+            let top = match submatches.value_of("top") {
+                Some(top) => top.parse()?,
+                None => timing::DEFAULT_TOP,
+            };
+            let spacing = match submatches.is_present("smooshed") {
+                true => audio::Spacing::Smooshed,
+                false => audio::Spacing::Standard,
+            };
+            let records = timing::run(
+                submatches.value_of("words"),
+                submatches.value_of("letters").map(str::parse).transpose()?,
+                top,
+                &timing(submatches)?,
+                spacing,
+            )?;
+            print_records(&records, format, ALL_FIELDS)?;
+        }
+        ("graph", Some(submatches)) => {
+            trace!(?submatches);
+            let min_group = match submatches.value_of("min-group") {
+                Some(min_group) => min_group.parse()?,
+                None => graph::DEFAULT_MIN_GROUP,
+            };
+            let mode = submatches.value_of("mode").unwrap().parse()?; // has a default
+            let graph_format = submatches.value_of("graph-format").unwrap().parse()?; // idem
+            let graph = graph::run(
+                submatches.value_of("words"),
+                &collisions_filter(submatches)?,
+                min_group,
+                mode,
+            )?;
+            let rendered = graph::render(&graph, graph_format);
+            match submatches.value_of("output") {
+                Some(file) => {
+                    std::fs::write(file, rendered)?;
+                    let record = Record::new()
+                        .with("output", file)
+                        .with("nodes", graph.nodes.len())
+                        .with("edges", graph.edges.len());
+                    print_records(&[record], format, ALL_FIELDS)?;
+                }
+                None => print!("{}", rendered),
             }
         }
-        ("extra2", Some(submatches)) => {
+        ("optimize-code", Some(submatches)) => {
             trace!(?submatches);
-            let res = extra2::run()?;
-            if matches.is_present("json") {
-                print_json(&res)
-            } else {
-                print_result(&res);
+            let iterations = match submatches.value_of("iterations") {
+                Some(iterations) => iterations.parse()?,
+                None => optimize::DEFAULT_ITERATIONS,
+            };
+            let temperature = match submatches.value_of("temperature") {
+                Some(temperature) => temperature.parse()?,
+                None => optimize::DEFAULT_TEMPERATURE,
+            };
+            let seed = match submatches.value_of("seed") {
+                Some(seed) => seed.parse()?,
+                None => rand::random(),
+            };
+            let progress = match submatches.value_of("progress") {
+                Some(progress) => progress.parse()?,
+                None => optimize::DEFAULT_PROGRESS,
+            };
+            let options = optimize::Options {
+                iterations,
+                temperature,
+                seed,
+                code_set: submatches.value_of("codes").unwrap().parse()?, // has a default
+                progress,
+            };
+            let record = optimize::run(submatches.value_of("words"), &options, |progress| {
+                eprintln!(
+                    "iteration {}, temperature {:.3}: {} colliding words, best {}",
+                    progress.iteration, progress.temperature, progress.colliding, progress.best
+                )
+            })?;
+            if let Some(output) = submatches.value_of("output") {
+                let code_book = record.get("code book").and_then(|book| book.as_str());
+                std::fs::write(output, format!("{}\n", code_book.unwrap_or_default()))?;
             }
+            print_records(&[record], format, ALL_FIELDS)?;
+        }
+        ("pairs", Some(submatches)) => {
+            trace!(?submatches);
+            let transform = submatches.value_of("transform").unwrap().parse()?; // has a default
+            print_records(
+                &pairs::run(submatches.value_of("words"), transform)?,
+                format,
+                ALL_FIELDS,
+            )?;
+        }
+        ("stats", Some(submatches)) => {
+            trace!(?submatches);
+            print_records(
+                &stats::run(submatches.value_of("words"))?,
+                format,
+                ALL_FIELDS,
+            )?;
+        }
+        ("repl", Some(submatches)) => {
+            trace!(?submatches);
+            repl::run(submatches.value_of("words"), format)?;
+        }
+        #[cfg(feature = "server")]
+        ("serve", Some(submatches)) => {
+            trace!(?submatches);
+            server::run(
+                submatches
+                    .value_of("address")
+                    .unwrap_or(server::DEFAULT_ADDRESS),
+                submatches.value_of("words"),
+                match submatches.value_of("max-body") {
+                    Some(max_body) => max_body.parse()?,
+                    None => server::DEFAULT_MAX_BODY,
+                },
+                match submatches.value_of("workers") {
+                    Some(workers) => workers.parse()?,
+                    None => server::DEFAULT_WORKERS,
+                },
+            )?;
+        }
+        ("extra1", Some(submatches)) => {
+            trace!(?submatches);
+            print_records(&extra1::run()?, format, "code")?;
+        }
+        ("extra2", Some(submatches)) => {
+            trace!(?submatches);
+            print_records(&extra2::run()?, format, "code")?;
         }
         ("extra3", Some(submatches)) => {
             trace!(?submatches);
-            let res = extra3::run()?;
-            if matches.is_present("json") {
-                print_json(&res)
-            } else {
-                print_result(&res);
-            }
+            print_records(&extra3::run()?, format, "word")?;
         }
         ("extra4", Some(submatches)) => {
             trace!(?submatches);
-            let res = extra4::run()?;
-            if matches.is_present("json") {
-                print_json(&res)
-            } else {
-                print_result(&res);
-            }
+            print_records(&extra4::run()?, format, "word")?;
         }
         _ => unreachable!(),
     }
@@ -144,13 +616,258 @@ fn setup() -> Result<(), Report> {
     Ok(())
 }
 
-fn print_json(words: &[String]) {
-    let json_words = json!(words);
-    println!("{}", json_words);
+#[cfg(feature = "parallel")]
+fn set_jobs(jobs: usize) -> Result<(), Report> {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs)
+        .build_global()?;
+    Ok(())
+}
+
+#[cfg(not(feature = "parallel"))]
+fn set_jobs(_jobs: usize) -> Result<(), Report> {
+    tracing::warn!("--jobs ignored, built without the parallel feature");
+    Ok(())
+}
+
+/// Decode a code as asked by the options, adding the result to the record. Words are looked
+/// up in the index if given, else in the word list loaded for this code only.
+fn decode_record(
+    code: &str,
+    record: Record,
+    submatches: &ArgMatches,
+    index: Option<&decode::DecodeIndex>,
+) -> Result<Record, Report> {
+    if submatches.is_present("spaced") {
+        let decoded = spaced::decode_spaced(code, &separators(submatches)?)?;
+        Ok(record
+            .with("text", decoded.text)
+            .with("unknown", decoded.unknown))
+    } else if submatches.is_present("count") {
+        let count = letters::count_letters(code)?;
+        Ok(record.with("count", count_value(count)))
+    } else {
+        let words = if submatches.is_present("letters") {
+            letters::decode_letters(code)?
+        } else {
+            match index {
+                Some(index) => decode::decode_with_index(code, index)?,
+                None => decode::decode(code, submatches.value_of("words"))?,
+            }
+        };
+        Ok(record.with("count", words.len()).with("words", words))
+    }
 }
 
-fn print_result(words: &[String]) {
-    for word in words {
-        println!("{}", word);
+/// Text field of the records of `decode_record`
+fn decode_field(submatches: &ArgMatches) -> &'static str {
+    if submatches.is_present("spaced") {
+        "text"
+    } else if submatches.is_present("count") {
+        "count"
+    } else {
+        "words"
     }
 }
+
+/// Decode morse read from keying: spaced morse as a whole, smooshed words one by one.
+/// Records have the speed and the confidence of every element.
+fn decode_reading(
+    input: &str,
+    reading: &keying::Reading,
+    submatches: &ArgMatches,
+    format: Format,
+    style: &Style,
+) -> Result<(), Report> {
+    let round = |x: f64| (x * 100.0).round() / 100.0;
+    if submatches.is_present("viterbi") {
+        let best = match submatches.value_of("best") {
+            Some(best) => best.parse()?,
+            None => hmm::DEFAULT_BEST,
+        };
+        let order = match submatches.value_of("order") {
+            Some(order) => order.parse()?,
+            None => hmm::DEFAULT_ORDER,
+        };
+        let model = hmm::CharModel::load(submatches.value_of("words"), order)?;
+        let records: Vec<Record> = hmm::decode(reading, &model, best)
+            .into_iter()
+            .enumerate()
+            .map(|(i, hypothesis)| {
+                Record::new()
+                    .with("input", input)
+                    .with("wpm", round(reading.wpm()))
+                    .with("rank", i + 1)
+                    .with("text", hypothesis.text)
+                    .with("log_prob", round(hypothesis.log_prob))
+            })
+            .collect();
+        print_records(&records, format, "text")?;
+        return Ok(());
+    }
+    let record = |elements: &[keying::Element]| {
+        let confidences: Vec<f64> = elements.iter().map(|e| round(e.confidence)).collect();
+        Record::new()
+            .with("input", input)
+            .with("wpm", round(reading.wpm()))
+            .with("confidence", round(keying::lowest_confidence(elements)))
+            .with("confidences", confidences)
+    };
+    if reading.spacing == audio::Spacing::Standard {
+        let seps = separators(submatches)?;
+        let morse = reading.to_morse(&seps);
+        let decoded = spaced::decode_spaced(&morse, &seps)?;
+        let record = record(&reading.elements)
+            .with("morse", style.render(&morse))
+            .with("text", decoded.text)
+            .with("unknown", decoded.unknown);
+        print_records(&[record], format, "text")?;
+        return Ok(());
+    }
+    // the word list is loaded once for all the words
+    let index = if ["spaced", "count", "letters"]
+        .iter()
+        .any(|flag| submatches.is_present(flag))
+    {
+        None
+    } else {
+        Some(decode::DecodeIndex::load(submatches.value_of("words"))?)
+    };
+    let records = reading
+        .smooshed_words()
+        .iter()
+        .zip(reading.word_elements())
+        .map(|(code, elements)| {
+            decode_record(
+                code,
+                record(elements).with("morse", style.render(code)),
+                submatches,
+                index.as_ref(),
+            )
+        })
+        .collect::<Result<Vec<Record>, Report>>()?;
+    print_records(&records, format, decode_field(submatches))?;
+    Ok(())
+}
+
+/// Decode every line of stdin, loading the word list only once
+fn decode_stdin(submatches: &ArgMatches, format: Format, glyphs: &Glyphs) -> Result<usize, Report> {
+    if submatches.is_present("spaced") {
+        let seps = separators(submatches)?;
+        batch::run_stdin(format, |line| {
+            Ok(vec![
+                spaced::decode_spaced(&glyphs.normalize(line), &seps)?.text,
+            ])
+        })
+    } else if submatches.is_present("count") {
+        batch::run_stdin(format, |line| {
+            Ok(vec![
+                letters::count_letters(&glyphs.normalize(line))?.to_string()
+            ])
+        })
+    } else if submatches.is_present("letters") {
+        batch::run_stdin(format, |line| {
+            letters::decode_letters(&glyphs.normalize(line))
+        })
+    } else {
+        let index = decode::DecodeIndex::load(submatches.value_of("words"))?;
+        batch::run_stdin(format, |line| {
+            decode::decode_with_index(&glyphs.normalize(line), &index)
+        })
+    }
+}
+
+/// Play a timeline on stderr in real time
+fn play(events: &[timeline::Event]) -> Result<(), Report> {
+    let start = std::time::Instant::now();
+    timeline::play(events, &mut std::io::stderr(), |end| {
+        let end = start + std::time::Duration::from_secs_f64(end);
+        if let Some(wait) = end.checked_duration_since(std::time::Instant::now()) {
+            std::thread::sleep(wait);
+        }
+    })?;
+    Ok(())
+}
+
+fn audio_config(submatches: &ArgMatches) -> Result<audio::AudioConfig, Report> {
+    let mut config = audio::AudioConfig {
+        timing: timing(submatches)?,
+        ..audio::AudioConfig::default()
+    };
+    if let Some(tone) = submatches.value_of("tone") {
+        config.tone = tone.parse()?;
+    }
+    if let Some(sample_rate) = submatches.value_of("sample-rate") {
+        config.sample_rate = sample_rate.parse()?;
+    }
+    if let Some(ramp) = submatches.value_of("ramp") {
+        config.ramp = ramp.parse::<f64>()? / 1000.0;
+    }
+    config.validate()?;
+    Ok(config)
+}
+
+fn timing(submatches: &ArgMatches) -> Result<timing::Timing, Report> {
+    let mut timing = timing::Timing::default();
+    if let Some(wpm) = submatches.value_of("wpm") {
+        timing.wpm = wpm.parse()?;
+    }
+    if let Some(farnsworth_wpm) = submatches.value_of("farnsworth") {
+        timing.farnsworth_wpm = Some(farnsworth_wpm.parse()?);
+    }
+    if let Some(dash) = submatches.value_of("dash-ratio") {
+        timing.dash = dash.parse()?;
+    }
+    if let Some(element_gap) = submatches.value_of("element-gap") {
+        timing.element_gap = element_gap.parse()?;
+    }
+    if let Some(letter_gap) = submatches.value_of("letter-gap") {
+        timing.letter_gap = letter_gap.parse()?;
+    }
+    if let Some(word_gap) = submatches.value_of("word-gap") {
+        timing.word_gap = word_gap.parse()?;
+    }
+    timing.validate()?;
+    Ok(timing)
+}
+
+/// Rendering of morse output, a custom pair or a style
+fn style(matches: &ArgMatches) -> Result<Style, Report> {
+    match (matches.value_of("dot"), matches.value_of("dash")) {
+        (Some(dot), Some(dash)) => Style::custom(dot, dash),
+        _ => matches.value_of("style").unwrap().parse(), // has a default
+    }
+}
+
+/// Glyphs accepted in morse input
+fn glyphs(matches: &ArgMatches) -> Result<Glyphs, Report> {
+    Glyphs::new(
+        matches.value_of("dots").unwrap_or(glyphs::DOT_GLYPHS),
+        matches.value_of("dashes").unwrap_or(glyphs::DASH_GLYPHS),
+        matches.is_present("strip-whitespace"),
+    )
+}
+
+fn collisions_filter(submatches: &ArgMatches) -> Result<collisions::Filter, Report> {
+    Ok(collisions::Filter {
+        word_length: submatches
+            .value_of("word-length")
+            .map(str::parse)
+            .transpose()?,
+        code_length: submatches
+            .value_of("code-length")
+            .map(str::parse)
+            .transpose()?,
+    })
+}
+
+fn separators(submatches: &ArgMatches) -> Result<spaced::Separators, Report> {
+    spaced::Separators::new(
+        submatches
+            .value_of("letter-separator")
+            .unwrap_or(spaced::LETTER_SEPARATOR),
+        submatches
+            .value_of("word-separator")
+            .unwrap_or(spaced::WORD_SEPARATOR),
+    )
+}
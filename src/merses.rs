@@ -3,21 +3,387 @@
 /// - = true
 use crate::morses;
 use morses::{DASH, DOT};
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
+use std::ops::{Add, Bound, RangeBounds};
+use std::str::FromStr;
+use std::sync::OnceLock;
 use thiserror::Error;
 use tracing::trace;
 
 const FALSE_CHAR: char = DOT;
 const TRUE_CHAR: char = DASH;
+/// Symbols packed in each storage word
+const WORD_BITS: usize = 64;
 
 #[derive(Error, Debug)]
 pub enum MorseError {
     #[error("Invalid morse char: `{0}`")]
     InvalidMorseChar(char),
+    #[error("Character not present in morse code: `{0}`")]
+    NotInMorseCode(char),
 }
 
-fn morse_char_to_merse(morse_char: &str) -> Result<Vec<bool>, MorseError> {
-    let mut merse_chars: Vec<bool> = Vec::new();
+/// Sequence of morse symbols packed one bit each (dot = 0, dash = 1) in u64 words.
+/// Codes up to 64 symbols long, nearly every word of the word list, are stored inline,
+/// without heap allocation.
+/// Symbol i is the bit 63 - i % 64 of word i / 64, so that comparing words compares
+/// symbols in order; bits past the end are always 0.
+#[derive(Clone)]
+pub struct Merse {
+    len: usize,
+    bits: Bits,
+}
+
+#[derive(Clone)]
+enum Bits {
+    Inline(u64),
+    Heap(Vec<u64>),
+}
+
+/// Mask of the first len bits of a word, len in 1..=64
+fn head_mask(len: usize) -> u64 {
+    !0u64 << (WORD_BITS - len)
+}
+
+impl Merse {
+    pub fn new() -> Self {
+        Merse {
+            len: 0,
+            bits: Bits::Inline(0),
+        }
+    }
+
+    fn from_words(len: usize, words: Vec<u64>) -> Self {
+        let bits = if len <= WORD_BITS {
+            Bits::Inline(words.first().copied().unwrap_or(0))
+        } else {
+            Bits::Heap(words)
+        };
+        Merse { len, bits }
+    }
+
+    fn words(&self) -> &[u64] {
+        match &self.bits {
+            Bits::Inline(word) => std::slice::from_ref(word),
+            Bits::Heap(words) => words,
+        }
+    }
+
+    fn words_mut(&mut self) -> &mut [u64] {
+        match &mut self.bits {
+            Bits::Inline(word) => std::slice::from_mut(word),
+            Bits::Heap(words) => words,
+        }
+    }
+
+    /// The 64 symbols starting at pos, padded with 0
+    fn word_at(&self, pos: usize) -> u64 {
+        let words = self.words();
+        let (i, offset) = (pos / WORD_BITS, pos % WORD_BITS);
+        let high = words.get(i).copied().unwrap_or(0) << offset;
+        if offset == 0 {
+            return high;
+        }
+        high | words.get(i + 1).copied().unwrap_or(0) >> (WORD_BITS - offset)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, i: usize) -> Option<bool> {
+        if i >= self.len {
+            return None;
+        }
+        Some(self.words()[i / WORD_BITS] & (1 << (WORD_BITS - 1 - i % WORD_BITS)) != 0)
+    }
+
+    pub fn push(&mut self, symbol: bool) {
+        if self.len == WORD_BITS {
+            if let Bits::Inline(word) = self.bits {
+                self.bits = Bits::Heap(vec![word]);
+            }
+        }
+        if let Bits::Heap(words) = &mut self.bits {
+            if self.len.is_multiple_of(WORD_BITS) {
+                words.push(0);
+            }
+        }
+        if symbol {
+            let i = self.len;
+            self.words_mut()[i / WORD_BITS] |= 1 << (WORD_BITS - 1 - i % WORD_BITS);
+        }
+        self.len += 1;
+    }
+
+    /// Append other at the end
+    pub fn extend_from_merse(&mut self, other: &Merse) {
+        match (&mut self.bits, &other.bits) {
+            (Bits::Inline(word), Bits::Inline(other_word)) if self.len + other.len <= WORD_BITS => {
+                if self.len < WORD_BITS {
+                    *word |= other_word >> self.len;
+                }
+                self.len += other.len;
+            }
+            _ => self.extend(other.iter()),
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            merse: self,
+            front: 0,
+            back: self.len,
+        }
+    }
+
+    /// Copy of the given range of symbols, panics if out of bounds like slice indexing
+    pub fn slice<R: RangeBounds<usize>>(&self, range: R) -> Merse {
+        let start = match range.start_bound() {
+            Bound::Included(&s) => s,
+            Bound::Excluded(&s) => s + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&e) => e + 1,
+            Bound::Excluded(&e) => e,
+            Bound::Unbounded => self.len,
+        };
+        assert!(
+            start <= end && end <= self.len,
+            "range {}..{} out of bounds for merse of length {}",
+            start,
+            end,
+            self.len
+        );
+        let len = end - start;
+        if len == 0 {
+            return Merse::new();
+        }
+        let mut words: Vec<u64> = (0..len.div_ceil(WORD_BITS))
+            .map(|k| self.word_at(start + k * WORD_BITS))
+            .collect();
+        if let Some(last) = words.last_mut() {
+            *last &= head_mask((len - 1) % WORD_BITS + 1);
+        }
+        Merse::from_words(len, words)
+    }
+
+    /// Like `slice`, but None if out of bounds
+    pub fn get_slice<R: RangeBounds<usize>>(&self, range: R) -> Option<Merse> {
+        let start_ok = match range.start_bound() {
+            Bound::Included(&s) => s <= self.len,
+            Bound::Excluded(&s) => s < self.len,
+            Bound::Unbounded => true,
+        };
+        let end_ok = match range.end_bound() {
+            Bound::Included(&e) => e < self.len,
+            Bound::Excluded(&e) => e <= self.len,
+            Bound::Unbounded => true,
+        };
+        if start_ok && end_ok {
+            Some(self.slice(range))
+        } else {
+            None
+        }
+    }
+
+    pub fn starts_with(&self, prefix: &Merse) -> bool {
+        prefix.len <= self.len && self.slice(..prefix.len) == *prefix
+    }
+
+    pub fn reversed(&self) -> Merse {
+        self.iter().rev().collect()
+    }
+
+    /// Dots turned into dashes and vice versa
+    pub fn complemented(&self) -> Merse {
+        let mut complement = self.clone();
+        if self.is_empty() {
+            return complement;
+        }
+        for word in complement.words_mut() {
+            *word = !*word;
+        }
+        if let Some(last) = complement.words_mut().last_mut() {
+            *last &= head_mask((self.len - 1) % WORD_BITS + 1);
+        }
+        complement
+    }
+
+    pub fn count_dashes(&self) -> usize {
+        self.words().iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn count_dots(&self) -> usize {
+        self.len - self.count_dashes()
+    }
+}
+
+impl Default for Merse {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PartialEq for Merse {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.words() == other.words()
+    }
+}
+
+impl Eq for Merse {}
+
+impl Hash for Merse {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len.hash(state);
+        self.words().hash(state);
+    }
+}
+
+/// Lexicographic by symbol, dot before dash, a prefix before its extensions
+impl Ord for Merse {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl PartialOrd for Merse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Merse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for symbol in self {
+            f.write_char(match symbol {
+                false => FALSE_CHAR,
+                true => TRUE_CHAR,
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Merse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Merse({})", self)
+    }
+}
+
+impl FromStr for Merse {
+    type Err = MorseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        morse_char_to_merse(s)
+    }
+}
+
+impl FromIterator<bool> for Merse {
+    fn from_iter<I: IntoIterator<Item = bool>>(iter: I) -> Self {
+        let mut merse = Merse::new();
+        merse.extend(iter);
+        merse
+    }
+}
+
+impl Extend<bool> for Merse {
+    fn extend<I: IntoIterator<Item = bool>>(&mut self, iter: I) {
+        for symbol in iter {
+            self.push(symbol);
+        }
+    }
+}
+
+impl From<&[bool]> for Merse {
+    fn from(symbols: &[bool]) -> Self {
+        symbols.iter().copied().collect()
+    }
+}
+
+impl From<Vec<bool>> for Merse {
+    fn from(symbols: Vec<bool>) -> Self {
+        symbols.into_iter().collect()
+    }
+}
+
+impl Add<&Merse> for Merse {
+    type Output = Merse;
+
+    fn add(mut self, other: &Merse) -> Merse {
+        self.extend_from_merse(other);
+        self
+    }
+}
+
+impl Add<&Merse> for &Merse {
+    type Output = Merse;
+
+    fn add(self, other: &Merse) -> Merse {
+        self.clone() + other
+    }
+}
+
+impl<'a> IntoIterator for &'a Merse {
+    type Item = bool;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+pub struct Iter<'a> {
+    merse: &'a Merse,
+    front: usize,
+    back: usize,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = bool;
+
+    fn next(&mut self) -> Option<bool> {
+        if self.front >= self.back {
+            return None;
+        }
+        self.front += 1;
+        self.merse.get(self.front - 1)
+    }
+
+    fn nth(&mut self, n: usize) -> Option<bool> {
+        self.front = self.back.min(self.front.saturating_add(n));
+        self.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.back - self.front;
+        (len, Some(len))
+    }
+}
+
+impl<'a> DoubleEndedIterator for Iter<'a> {
+    fn next_back(&mut self) -> Option<bool> {
+        if self.front >= self.back {
+            return None;
+        }
+        self.back -= 1;
+        self.merse.get(self.back)
+    }
+}
+
+impl<'a> ExactSizeIterator for Iter<'a> {}
+
+fn morse_char_to_merse(morse_char: &str) -> Result<Merse, MorseError> {
+    let mut merse_chars = Merse::new();
     for ch in morse_char.chars() {
         merse_chars.push(match ch {
             FALSE_CHAR => false,
@@ -31,54 +397,163 @@ fn morse_char_to_merse(morse_char: &str) -> Result<Vec<bool>, MorseError> {
     Ok(merse_chars)
 }
 
-pub fn merse_to_morse(merse: &[bool]) -> String {
-    let mut morse = String::new();
-    for ch in merse {
-        morse.push(match ch {
-            false => FALSE_CHAR,
-            true => TRUE_CHAR,
-        });
-    }
+pub fn merse_to_morse(merse: &Merse) -> String {
+    let morse = merse.to_string();
     trace!("{:?}->{}", merse, morse);
     morse
 }
 
-pub fn smooshedmorse_to_merse(word: &str) -> Result<Vec<bool>, MorseError> {
+pub fn smooshedmorse_to_merse(word: &str) -> Result<Merse, MorseError> {
     let merse = morse_char_to_merse(word)?;
     trace!("{}->{:?}", word, merse);
     Ok(merse)
 }
 
-pub fn merse_to_char(merse_ch: Vec<bool>) -> Result<Option<char>, MorseError> {
-    for (k, v) in &get_merse_code()? {
-        if *v == merse_ch {
-            trace!("{:?}->{}", merse_ch, k);
-            return Ok(Some(*k));
-        }
-    }
-    Ok(None)
+pub fn merse_to_char(merse_ch: &Merse) -> Result<Option<char>, MorseError> {
+    let ch = get_merse_trie().get(merse_ch);
+    trace!("{:?}->{:?}", merse_ch, ch);
+    Ok(ch)
 }
 
-pub fn char_to_merse(ch: char) -> Result<Vec<bool>, MorseError> {
-    let morse_ch = morses::char_to_morse(ch);
-    let merse_ch = morse_char_to_merse(&morse_ch)?;
-    trace!("{}->{}", ch, morse_ch);
+/// Merse of a letter (ASCII letters in any case, lowercase ITU extension letters)
+pub fn char_to_merse(ch: char) -> Result<Merse, MorseError> {
+    let merse_ch = match morses::letter_index(ch) {
+        Some(i) => get_merse_table()[i].clone(),
+        None => morses::extension_code(ch)
+            .ok_or(MorseError::NotInMorseCode(ch))?
+            .parse()?,
+    };
+    trace!("{}->{:?}", ch, merse_ch);
     Ok(merse_ch)
 }
 
-pub fn get_merse_code() -> Result<HashMap<char, Vec<bool>>, MorseError> {
-    let morse = morses::get_morse_code();
-    let mut merse: HashMap<char, Vec<bool>> = HashMap::new();
-    for (k, v) in &morse {
-        merse.insert(*k, morse_char_to_merse(v)?);
-    }
+pub fn get_merse_code() -> Result<HashMap<char, Merse>, MorseError> {
+    let merse: HashMap<char, Merse> = morses::ALPHABET
+        .iter()
+        .copied()
+        .zip(get_merse_table().iter().cloned())
+        .collect();
     trace!("Merse code: {:?}", merse);
     Ok(merse)
 }
 
+/// Merse code of each letter, indexed like ALPHABET (see `morses::letter_index`).
+/// Built on first use only.
+pub fn get_merse_table() -> &'static [Merse; 26] {
+    static MERSE_TABLE: OnceLock<[Merse; 26]> = OnceLock::new();
+    MERSE_TABLE.get_or_init(|| {
+        let morse_table = morses::get_morse_table();
+        std::array::from_fn(|i| {
+            morse_char_to_merse(morse_table[i]).expect("Invalid symbol in morse code table")
+        })
+    })
+}
+
+/// Binary trie of merse codes: walking it along a merse word finds every code that is a
+/// prefix of the word in a single pass
+#[derive(Debug, Clone)]
+pub struct MerseTrie {
+    nodes: Vec<MerseTrieNode>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct MerseTrieNode {
+    /// Child node indexes, [dot, dash]
+    children: [Option<usize>; 2],
+    symbol: Option<char>,
+}
+
+impl Default for MerseTrie {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MerseTrie {
+    pub fn new() -> Self {
+        MerseTrie {
+            nodes: vec![MerseTrieNode::default()],
+        }
+    }
+
+    pub fn insert(&mut self, merse: &Merse, symbol: char) {
+        let mut node = 0;
+        for m in merse {
+            node = match self.nodes[node].children[m as usize] {
+                Some(child) => child,
+                None => {
+                    self.nodes.push(MerseTrieNode::default());
+                    let child = self.nodes.len() - 1;
+                    self.nodes[node].children[m as usize] = Some(child);
+                    child
+                }
+            };
+        }
+        self.nodes[node].symbol = Some(symbol);
+    }
+
+    pub fn get(&self, merse: &Merse) -> Option<char> {
+        let mut node = 0;
+        for m in merse {
+            node = self.nodes[node].children[m as usize]?;
+        }
+        self.nodes[node].symbol
+    }
+
+    /// Every (length, symbol) whose code is a prefix of the given symbols, shortest first
+    pub fn prefixes<I: Iterator<Item = bool>>(&self, symbols: I) -> Prefixes<'_, I> {
+        Prefixes {
+            trie: self,
+            symbols,
+            node: Some(0),
+            depth: 0,
+        }
+    }
+}
+
+pub struct Prefixes<'a, I> {
+    trie: &'a MerseTrie,
+    symbols: I,
+    node: Option<usize>,
+    depth: usize,
+}
+
+impl<'a, I: Iterator<Item = bool>> Iterator for Prefixes<'a, I> {
+    type Item = (usize, char);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.node {
+            let m = self.symbols.next()?;
+            self.node = self.trie.nodes[node].children[m as usize];
+            self.depth += 1;
+            if let Some(symbol) = self.node.and_then(|n| self.trie.nodes[n].symbol) {
+                return Some((self.depth, symbol));
+            }
+        }
+        None
+    }
+}
+
+/// Trie of the merse code, to find letters from their code. Built on first use only.
+pub fn get_merse_trie() -> &'static MerseTrie {
+    static MERSE_TRIE: OnceLock<MerseTrie> = OnceLock::new();
+    MERSE_TRIE.get_or_init(|| {
+        let mut trie = MerseTrie::new();
+        for (ch, merse_ch) in morses::ALPHABET.iter().zip(get_merse_table().iter()) {
+            trie.insert(merse_ch, *ch);
+        }
+        trie
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashSet;
+
+    fn m(morse: &str) -> Merse {
+        morse.parse().unwrap()
+    }
 
     #[test]
     fn test_get_merse_code() {
@@ -87,33 +562,39 @@ mod tests {
 
     #[test]
     fn test_morse_char_to_merse() {
-        assert_eq!(morse_char_to_merse(".-").unwrap(), vec![false, true]);
+        assert_eq!(
+            morse_char_to_merse(".-").unwrap(),
+            Merse::from(vec![false, true])
+        );
         assert_eq!(
             morse_char_to_merse("..-.").unwrap(),
-            vec![false, false, true, false]
+            Merse::from(vec![false, false, true, false])
         );
         assert_eq!(
             morse_char_to_merse(".---..-.-.-.--").unwrap(),
-            vec![
+            Merse::from(vec![
                 false, true, true, true, false, false, true, false, true, false, true, false, true,
                 true
-            ]
+            ])
         );
     }
 
     #[test]
     fn test_smooshedmorse_to_merse() {
-        assert_eq!(smooshedmorse_to_merse(".-").unwrap(), vec![false, true]);
+        assert_eq!(
+            smooshedmorse_to_merse(".-").unwrap(),
+            Merse::from(vec![false, true])
+        );
         assert_eq!(
             smooshedmorse_to_merse("..-.").unwrap(),
-            vec![false, false, true, false]
+            Merse::from(vec![false, false, true, false])
         );
         assert_eq!(
             smooshedmorse_to_merse(".---..-.-.-.--").unwrap(),
-            vec![
+            Merse::from(vec![
                 false, true, true, true, false, false, true, false, true, false, true, false, true,
                 true
-            ]
+            ])
         );
     }
 
@@ -125,24 +606,158 @@ mod tests {
     #[test]
     fn test_merse_to_char() {
         assert_eq!(
-            merse_to_char(vec![true, false, false, false]).unwrap(),
+            merse_to_char(&Merse::from(vec![true, false, false, false])).unwrap(),
             Some('b')
         );
         assert_eq!(
-            merse_to_char(vec![true, false, false, false, false]).unwrap(),
+            merse_to_char(&Merse::from(vec![true, false, false, false, false])).unwrap(),
             None
         );
     }
 
     #[test]
     fn test_char_to_merse() {
-        assert_eq!(char_to_merse('b').unwrap(), vec![true, false, false, false]);
-        assert_eq!(char_to_merse('i').unwrap(), vec![false, false]);
+        assert_eq!(
+            char_to_merse('b').unwrap(),
+            Merse::from(vec![true, false, false, false])
+        );
+        assert_eq!(char_to_merse('i').unwrap(), Merse::from(vec![false, false]));
+        assert_eq!(char_to_merse('I').unwrap(), Merse::from(vec![false, false]));
+        assert_eq!(char_to_merse('à').unwrap(), ".--.-".parse().unwrap());
+        assert!(char_to_merse('ß').is_err());
+    }
+
+    #[test]
+    fn test_merse_trie() {
+        let trie = get_merse_trie();
+        assert_eq!(trie.get(&m("-...")), Some('b'));
+        assert_eq!(trie.get(&m("----")), None);
+        assert_eq!(trie.get(&Merse::new()), None);
+        assert_eq!(
+            trie.prefixes(m("-...-").iter())
+                .collect::<Vec<(usize, char)>>(),
+            vec![(1, 't'), (2, 'n'), (3, 'd'), (4, 'b')]
+        );
+        assert_eq!(trie.prefixes(m("----").iter()).count(), 3);
+        assert_eq!(trie.prefixes(m("-...-").iter().skip(4)).count(), 1);
+        assert_eq!(trie.prefixes(Merse::new().iter()).count(), 0);
     }
 
     #[test]
     fn test_merse_to_morse() {
-        assert_eq!(merse_to_morse(&vec![true, false, true]), "-.-".to_string());
-        assert_eq!(merse_to_morse(&vec![]), String::new());
+        assert_eq!(
+            merse_to_morse(&Merse::from(vec![true, false, true])),
+            "-.-".to_string()
+        );
+        assert_eq!(merse_to_morse(&Merse::new()), String::new());
+    }
+
+    #[test]
+    fn test_merse_push_get() {
+        let symbols: Vec<bool> = (0..150).map(|i| i % 3 == 0 || i % 7 == 0).collect();
+        let merse = Merse::from(symbols.clone());
+        assert_eq!(merse.len(), 150);
+        for (i, s) in symbols.iter().enumerate() {
+            assert_eq!(merse.get(i), Some(*s));
+        }
+        assert_eq!(merse.get(150), None);
+        assert_eq!(merse.iter().collect::<Vec<bool>>(), symbols);
+        assert_eq!(merse.iter().rev().count(), 150);
+        assert_eq!(merse.iter().nth(149), Some(symbols[149]));
+        assert_eq!(merse.iter().nth(150), None);
+        assert!(Merse::new().is_empty());
+    }
+
+    #[test]
+    fn test_merse_display_parse() {
+        let long = ".-".repeat(40) + "---";
+        assert_eq!(m(".--.").to_string(), ".--.".to_string());
+        assert_eq!(m(&long).to_string(), long);
+        assert_eq!(m("").to_string(), String::new());
+        assert_eq!(format!("{:?}", m("-.")), "Merse(-.)".to_string());
+        assert!("-.x".parse::<Merse>().is_err());
+    }
+
+    #[test]
+    fn test_merse_eq_hash() {
+        let long = "-..".repeat(30);
+        assert_eq!(m(&long), m(&long));
+        assert_ne!(m("."), m(".."));
+        assert_ne!(m(""), m("."));
+        assert_eq!(m(&long).slice(3..), m(&long[3..]));
+        let set: HashSet<Merse> = vec![m("."), m(".."), m("."), m(&long), m(&long)]
+            .into_iter()
+            .collect();
+        assert_eq!(set.len(), 3);
+    }
+
+    #[test]
+    fn test_merse_ord() {
+        let mut codes = vec![m("-"), m(".-"), m("."), m(""), m("-."), m("..")];
+        codes.sort();
+        assert_eq!(
+            codes,
+            vec![m(""), m("."), m(".."), m(".-"), m("-"), m("-.")]
+        );
+        assert!(m(&"-".repeat(70)) > m(&"-".repeat(69)));
+    }
+
+    #[test]
+    fn test_merse_slice() {
+        let long = "-.-..--.".repeat(20);
+        let merse = m(&long);
+        assert_eq!(merse.slice(..), merse);
+        assert_eq!(merse.slice(5..5), Merse::new());
+        assert_eq!(merse.slice(3..9), m(&long[3..9]));
+        assert_eq!(merse.slice(60..130), m(&long[60..130]));
+        assert_eq!(merse.slice(64..=127), m(&long[64..128]));
+        assert_eq!(merse.slice(100..), m(&long[100..]));
+        assert_eq!(merse.get_slice(150..161), None);
+        assert_eq!(merse.get_slice(150..160), Some(m(&long[150..160])));
+        assert!(merse.starts_with(&m("-.-")));
+        assert!(!merse.starts_with(&m("--")));
+        assert!(m("-").starts_with(&Merse::new()));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_merse_slice_out_of_bounds() {
+        m("-.-").slice(2..4);
+    }
+
+    #[test]
+    fn test_merse_concat() {
+        let long = "-.-..--.".repeat(10);
+        assert_eq!(&m(".-") + &m("-.."), m(".--.."));
+        assert_eq!(m("") + &m("-.."), m("-.."));
+        assert_eq!(&m(&long) + &m(&long), m(&(long.clone() + &long)));
+        assert_eq!(
+            &m(&long[..64]) + &m("-"),
+            m(&(long[..64].to_string() + "-"))
+        );
+        let mut merse = m(&long[..30]);
+        merse.extend_from_merse(&m(&long[30..]));
+        assert_eq!(merse, m(&long));
+    }
+
+    #[test]
+    fn test_merse_reversed_complemented() {
+        let long = "-.-..--.".repeat(10) + "-";
+        let reversed: String = long.chars().rev().collect();
+        let complemented: String = long
+            .chars()
+            .map(|c| if c == DOT { DASH } else { DOT })
+            .collect();
+        assert_eq!(m("-..").reversed(), m("..-"));
+        assert_eq!(m(&long).reversed(), m(&reversed));
+        assert_eq!(m("-..").complemented(), m(".--"));
+        assert_eq!(m(&long).complemented(), m(&complemented));
+        assert_eq!(m("").complemented(), m(""));
+        assert_eq!(m("-..").count_dashes(), 1);
+        assert_eq!(m("-..").count_dots(), 2);
+        assert_eq!(
+            m(&long).complemented().count_dashes(),
+            m(&long).count_dots()
+        );
     }
 }
@@ -1,6 +1,8 @@
+use crate::merses::{get_merse_trie, Merse};
 use color_eyre::{eyre::eyre, Report};
 use std::collections::HashMap;
-use tracing::{error, trace};
+use std::sync::OnceLock;
+use tracing::{error, trace, warn};
 
 pub const ALPHABET: [char; 26] = [
     'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's',
@@ -10,34 +12,161 @@ pub const DOT: char = '.';
 pub const DASH: char = '-';
 pub const MORSE: &str = ".- -... -.-. -.. . ..-. --. .... .. .--- -.- .-.. -- -. --- .--. --.- .-. ... - ..- ...- .-- -..- -.-- --..";
 
+/// ITU extensions for letters of other latin alphabets. Letters sharing a code follow the
+/// one it decodes to. `ch` is decoded only: ASCII words keep the codes of their letters.
+pub const ITU_EXTENSIONS: [(&str, &str); 31] = [
+    ("à", ".--.-"),
+    ("å", ".--.-"),
+    ("ä", ".-.-"),
+    ("æ", ".-.-"),
+    ("ą", ".-.-"),
+    ("ç", "-.-.."),
+    ("ć", "-.-.."),
+    ("ĉ", "-.-.."),
+    ("é", "..-.."),
+    ("đ", "..-.."),
+    ("ę", "..-.."),
+    ("ð", "..--."),
+    ("è", ".-..-"),
+    ("ł", ".-..-"),
+    ("ĝ", "--.-."),
+    ("ch", "----"),
+    ("ĥ", "----"),
+    ("š", "----"),
+    ("ĵ", ".---."),
+    ("ñ", "--.--"),
+    ("ń", "--.--"),
+    ("ö", "---."),
+    ("ó", "---."),
+    ("ø", "---."),
+    ("ś", "...-..."),
+    ("ŝ", "...-."),
+    ("þ", ".--.."),
+    ("ü", "..--"),
+    ("ŭ", "..--"),
+    ("ź", "--..-."),
+    ("ż", "--..-"),
+];
+
+static MORSE_TABLE: OnceLock<[&'static str; 26]> = OnceLock::new();
+
+/// Morse code of each letter, indexed like ALPHABET (see `letter_index`).
+/// Split from MORSE on first use only, unless a code book is set before.
+pub fn get_morse_table() -> &'static [&'static str; 26] {
+    MORSE_TABLE.get_or_init(|| {
+        let mut table = [""; 26];
+        for (code, morse_ch) in table.iter_mut().zip(MORSE.split(' ')) {
+            *code = morse_ch;
+        }
+        trace!("Morse table: {:?}", table);
+        table
+    })
+}
+
+/// Code of each letter of ALPHABET in a code book: the codes in letter order, separated
+/// by whitespace, like `morses::MORSE`
+pub fn parse_code_book(text: &str) -> Result<Vec<String>, Report> {
+    let codes: Vec<String> = text.split_whitespace().map(str::to_string).collect();
+    if codes.len() != ALPHABET.len() {
+        return Err(eyre!(
+            "A code book has {} codes, found {}",
+            ALPHABET.len(),
+            codes.len()
+        ));
+    }
+    for (i, code) in codes.iter().enumerate() {
+        validate_morse_str(code)?;
+        if codes[..i].contains(code) {
+            return Err(eyre!("Code `{}` given to two letters", code));
+        }
+    }
+    Ok(codes)
+}
+
+/// Use the codes of a code book instead of MORSE for the letters of ALPHABET. ITU
+/// extension letters keep theirs, except those now given to a letter, which are turned
+/// off. Must come before any use of the morse table.
+pub fn set_code_book(text: &str) -> Result<(), Report> {
+    let codes = parse_code_book(text)?;
+    // set once for the whole run, like the tables built from it
+    let table: [&'static str; 26] =
+        std::array::from_fn(|i| &*Box::leak(codes[i].clone().into_boxed_str()));
+    MORSE_TABLE
+        .set(table)
+        .map_err(|_| eyre!("Code book set after the morse table was used"))?;
+    trace!("Morse table: {:?}", table);
+    let off: Vec<&str> = ITU_EXTENSIONS
+        .iter()
+        .filter(|(_, code)| table.contains(code))
+        .map(|(letter, _)| *letter)
+        .collect();
+    if !off.is_empty() {
+        warn!(
+            "Extension letters with the code of a letter of the code book: {}",
+            off.join(" ")
+        );
+    }
+    Ok(())
+}
+
+/// ITU extensions in use: those whose code is no letter code
+fn extensions() -> impl Iterator<Item = &'static (&'static str, &'static str)> {
+    let table = get_morse_table();
+    ITU_EXTENSIONS
+        .iter()
+        .filter(move |(_, code)| !table.contains(code))
+}
+
+/// Position of a letter (any case) in ALPHABET
+pub fn letter_index(ch: char) -> Option<usize> {
+    let ch = ch.to_ascii_lowercase();
+    if ch.is_ascii_lowercase() {
+        Some(ch as usize - 'a' as usize)
+    } else {
+        None
+    }
+}
+
 pub fn get_morse_code() -> HashMap<char, &'static str> {
-    let morse_seq: Vec<&str> = MORSE.split(' ').collect();
     let mc: HashMap<char, &str> = ALPHABET
         .iter()
         .copied()
-        .zip(morse_seq.into_iter())
+        .zip(get_morse_table().iter().copied())
         .collect();
     trace!("Morse: {:?}", mc);
     mc
 }
 
 pub fn morse_to_char(morse_ch: &str) -> Option<char> {
-    for (k, v) in &get_morse_code() {
-        if *v == morse_ch {
-            trace!("{}->{}", morse_ch, k);
-            return Some(*k);
-        }
-    }
-    None
+    let ch = get_merse_trie().get(&morse_ch.parse::<Merse>().ok()?)?;
+    trace!("{}->{}", morse_ch, ch);
+    Some(ch)
 }
 
-pub fn char_to_morse(ch: char) -> String {
-    let morse_ch = get_morse_code()
-        .get(&ch.to_ascii_lowercase())
-        .expect("The character given is not present in morse code")
-        .to_string();
+/// ITU extension code of a lowercase letter
+pub fn extension_code(ch: char) -> Option<&'static str> {
+    let mut buf = [0; 4];
+    let symbol: &str = ch.encode_utf8(&mut buf);
+    extensions()
+        .find(|(letter, _)| *letter == symbol)
+        .map(|(_, code)| *code)
+}
+
+/// Letter an ITU extension code decodes to
+pub fn extension_letter(morse_ch: &str) -> Option<&'static str> {
+    extensions()
+        .find(|(_, code)| *code == morse_ch)
+        .map(|(letter, _)| *letter)
+}
+
+/// Code of a letter (ASCII letters in any case, lowercase ITU extension letters)
+pub fn char_to_morse(ch: char) -> Result<&'static str, Report> {
+    let morse_ch = letter_index(ch)
+        .map(|i| get_morse_table()[i])
+        .or_else(|| extension_code(ch))
+        .ok_or_else(|| eyre!("Character not present in morse code: `{}`", ch))?;
     trace!("{}->{}", ch, morse_ch);
-    morse_ch
+    Ok(morse_ch)
 }
 
 /// Valid morse string must contain only . and -
@@ -77,18 +206,64 @@ mod tests {
         assert!(get_morse_code().len() == 26);
     }
 
+    #[test]
+    fn test_get_morse_table() {
+        assert_eq!(get_morse_table()[0], ".-");
+        assert_eq!(get_morse_table()[25], "--..");
+        assert_eq!(get_morse_table().join(" "), MORSE);
+    }
+
+    #[test]
+    fn test_parse_code_book() {
+        assert_eq!(parse_code_book(MORSE).unwrap().join(" "), MORSE);
+        assert!(parse_code_book(".- -...").is_err());
+        assert!(parse_code_book(&MORSE.replace("--..", ".-")).is_err());
+        assert!(parse_code_book(&MORSE.replace("--..", "--.x")).is_err());
+    }
+
+    #[test]
+    fn test_letter_index() {
+        assert_eq!(letter_index('a'), Some(0));
+        assert_eq!(letter_index('Z'), Some(25));
+        assert_eq!(letter_index('à'), None);
+        assert_eq!(letter_index('3'), None);
+        for (i, ch) in ALPHABET.iter().enumerate() {
+            assert_eq!(letter_index(*ch), Some(i));
+        }
+    }
+
     #[test]
     fn test_char_to_morse() {
-        assert_eq!(char_to_morse('a'), ".-".to_string());
-        assert_eq!(char_to_morse('k'), "-.-".to_string());
-        assert_eq!(char_to_morse('z'), "--..".to_string());
-        assert_eq!(char_to_morse('S'), "...".to_string());
+        assert_eq!(char_to_morse('a').unwrap(), ".-");
+        assert_eq!(char_to_morse('k').unwrap(), "-.-");
+        assert_eq!(char_to_morse('z').unwrap(), "--..");
+        assert_eq!(char_to_morse('S').unwrap(), "...");
+        assert_eq!(char_to_morse('à').unwrap(), ".--.-");
+        assert_eq!(char_to_morse('ñ').unwrap(), "--.--");
     }
 
     #[test]
-    #[should_panic]
     fn test_invalid_char_to_morse() {
-        char_to_morse('à');
+        assert!(char_to_morse('3').is_err());
+        assert!(char_to_morse('ß').is_err());
+        // folding to lowercase is done before
+        assert!(char_to_morse('À').is_err());
+    }
+
+    #[test]
+    fn test_extensions() {
+        assert_eq!(extension_code('ü'), Some("..--"));
+        assert_eq!(extension_code('u'), None);
+        assert_eq!(extension_letter(".--.-"), Some("à"));
+        assert_eq!(extension_letter("----"), Some("ch"));
+        assert_eq!(extension_letter(".-"), None);
+        for (letter, code) in ITU_EXTENSIONS.iter() {
+            assert!(validate_morse_str(code).is_ok());
+            assert!(morse_to_char(code).is_none(), "{} is a letter code", code);
+            if letter.chars().count() == 1 {
+                assert_eq!(extension_code(letter.chars().next().unwrap()), Some(*code));
+            }
+        }
     }
 
     #[test]
@@ -97,5 +272,7 @@ mod tests {
         assert_eq!(morse_to_char("..."), Some('s'));
         assert_eq!(morse_to_char("---"), Some('o'));
         assert_eq!(morse_to_char("----"), None);
+        assert_eq!(morse_to_char(""), None);
+        assert_eq!(morse_to_char(".x"), None);
     }
 }
@@ -0,0 +1,374 @@
+//! Search for the letter codes making the fewest colliding words when smooshed: simulated
+//! annealing over the assignments of codes to letters, counting collisions like `extra1`.
+//! The codes are the 26 current ones permuted among the letters, or any codes up to 4
+//! symbols long. After each move only the words with a recoded letter are encoded again.
+
+use crate::merses::{get_merse_table, merse_to_morse, Merse};
+use crate::morses::{letter_index, ALPHABET};
+use crate::records::Record;
+use crate::wordlist::get_all_words;
+use color_eyre::{eyre::eyre, Report};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::str::FromStr;
+use tracing::info;
+
+pub const DEFAULT_ITERATIONS: usize = 1000;
+pub const DEFAULT_PROGRESS: usize = 100;
+/// Starting temperature, in colliding words: a move making that many more is accepted
+/// with probability 1/e at the start
+pub const DEFAULT_TEMPERATURE: f64 = 100.0;
+/// The temperature decreases geometrically to this fraction of the starting one
+const FINAL_TEMPERATURE_RATIO: f64 = 0.001;
+/// Longest code of the `all` code set
+const MAX_CODE_LEN: usize = 4;
+pub const CODE_SETS: [&str; 2] = ["permute", "all"];
+
+/// Codes to give to the letters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodeSet {
+    /// The current codes, permuted
+    Permute,
+    /// Any codes up to MAX_CODE_LEN symbols
+    All,
+}
+
+impl FromStr for CodeSet {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "permute" => Ok(CodeSet::Permute),
+            "all" => Ok(CodeSet::All),
+            _ => Err(eyre!(
+                "Unknown code set {}, expected one of {}",
+                s,
+                CODE_SETS.join(", ")
+            )),
+        }
+    }
+}
+
+impl CodeSet {
+    /// The codes of the table in letter order, then the unused ones
+    fn pool(self, table: &[Merse]) -> Vec<Merse> {
+        let mut pool = table.to_vec();
+        if self == CodeSet::All {
+            for len in 1..=MAX_CODE_LEN {
+                for bits in 0..1usize << len {
+                    let code: Merse = (0..len).map(|i| bits >> (len - 1 - i) & 1 == 1).collect();
+                    if !pool.contains(&code) {
+                        pool.push(code);
+                    }
+                }
+            }
+        }
+        pool
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub iterations: usize,
+    pub temperature: f64,
+    pub seed: u64,
+    pub code_set: CodeSet,
+    /// Report the search every so many iterations, never if 0
+    pub progress: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Progress {
+    pub iteration: usize,
+    pub temperature: f64,
+    pub colliding: usize,
+    pub best: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    /// Code of each letter, indexed like ALPHABET
+    pub table: Vec<Merse>,
+    pub colliding: usize,
+    pub largest: usize,
+    pub initial_colliding: usize,
+    pub initial_largest: usize,
+}
+
+/// Codes of the words under a letter table, and the number of codes of each group size
+struct Collisions {
+    /// Letters of each word, as ALPHABET positions
+    words: Vec<Vec<usize>>,
+    /// Words with each letter
+    with_letter: Vec<Vec<usize>>,
+    codes: Vec<Merse>,
+    groups: HashMap<Merse, usize>,
+    sizes: Vec<usize>,
+}
+
+impl Collisions {
+    fn new(all_words: &[String], table: &[Merse]) -> Self {
+        let words: Vec<Vec<usize>> = all_words
+            .iter()
+            .map(|word| word.chars().filter_map(letter_index).collect())
+            .collect();
+        let mut with_letter = vec![Vec::new(); ALPHABET.len()];
+        for (i, letters) in words.iter().enumerate() {
+            for letter in letters {
+                if with_letter[*letter].last() != Some(&i) {
+                    with_letter[*letter].push(i);
+                }
+            }
+        }
+        let mut collisions = Collisions {
+            codes: vec![Merse::new(); words.len()],
+            groups: HashMap::new(),
+            sizes: vec![0; words.len() + 1],
+            words,
+            with_letter,
+        };
+        for i in 0..collisions.words.len() {
+            let code = collisions.encode(i, table);
+            collisions.add(i, code);
+        }
+        collisions
+    }
+
+    fn encode(&self, word: usize, table: &[Merse]) -> Merse {
+        let mut code = Merse::new();
+        for letter in &self.words[word] {
+            code.extend_from_merse(&table[*letter]);
+        }
+        code
+    }
+
+    fn add(&mut self, word: usize, code: Merse) {
+        let size = self.groups.entry(code.clone()).or_insert(0);
+        if *size > 0 {
+            self.sizes[*size] -= 1;
+        }
+        *size += 1;
+        self.sizes[*size] += 1;
+        self.codes[word] = code;
+    }
+
+    /// Uncount the code of the word, returning it
+    fn remove(&mut self, word: usize) -> Merse {
+        let code = std::mem::take(&mut self.codes[word]);
+        let size = self.groups.get_mut(&code).expect("Word code not counted");
+        self.sizes[*size] -= 1;
+        *size -= 1;
+        if *size > 0 {
+            self.sizes[*size] += 1;
+        } else {
+            self.groups.remove(&code);
+        }
+        code
+    }
+
+    /// Set new codes to the words, returning the previous ones to undo it
+    fn replace(&mut self, changes: Vec<(usize, Merse)>) -> Vec<(usize, Merse)> {
+        changes
+            .into_iter()
+            .map(|(word, code)| {
+                let previous = self.remove(word);
+                self.add(word, code);
+                (word, previous)
+            })
+            .collect()
+    }
+
+    /// Encode again the words with any of the letters, returning their previous codes
+    fn recode(&mut self, table: &[Merse], letters: &[usize]) -> Vec<(usize, Merse)> {
+        let mut words: Vec<usize> = letters
+            .iter()
+            .flat_map(|letter| self.with_letter[*letter].iter().copied())
+            .collect();
+        words.sort_unstable();
+        words.dedup();
+        #[cfg(feature = "parallel")]
+        let recoded = words.par_iter();
+        #[cfg(not(feature = "parallel"))]
+        let recoded = words.iter();
+        let changes: Vec<(usize, Merse)> = recoded
+            .map(|word| (*word, self.encode(*word, table)))
+            .collect();
+        self.replace(changes)
+    }
+
+    /// Words sharing their code with another word
+    fn colliding(&self) -> usize {
+        self.words.len() - self.sizes.get(1).copied().unwrap_or(0)
+    }
+
+    /// Words of the largest group, the `extra1` count
+    fn largest(&self) -> usize {
+        self.sizes.iter().rposition(|codes| *codes > 0).unwrap_or(0)
+    }
+}
+
+/// Anneal the codes of the letters of the table, starting from it
+pub fn anneal(
+    all_words: &[String],
+    table: &[Merse],
+    options: &Options,
+    mut on_progress: impl FnMut(&Progress),
+) -> Outcome {
+    let mut rng = StdRng::seed_from_u64(options.seed);
+    let mut pool = options.code_set.pool(table);
+    let letters = table.len();
+    let mut collisions = Collisions::new(all_words, &pool[..letters]);
+    let (initial_colliding, initial_largest) = (collisions.colliding(), collisions.largest());
+    let mut colliding = initial_colliding;
+    let mut best = (pool[..letters].to_vec(), colliding, initial_largest);
+    for iteration in 0..options.iterations {
+        let progress = iteration as f64 / options.iterations as f64;
+        let temperature = options.temperature * FINAL_TEMPERATURE_RATIO.powf(progress);
+        if options.progress > 0 && iteration % options.progress == 0 {
+            on_progress(&Progress {
+                iteration,
+                temperature,
+                colliding,
+                best: best.1,
+            });
+        }
+        if pool.len() < 2 {
+            break;
+        }
+        // swap the codes of a letter and of another letter or an unused code
+        let i = rng.gen_range(0..letters);
+        let j = (i + rng.gen_range(1..pool.len())) % pool.len();
+        let recoded: Vec<usize> = [i, j].iter().copied().filter(|k| *k < letters).collect();
+        pool.swap(i, j);
+        let previous = collisions.recode(&pool[..letters], &recoded);
+        let delta = collisions.colliding() as f64 - colliding as f64;
+        if delta <= 0.0 || rng.gen::<f64>() < (-delta / temperature).exp() {
+            colliding = collisions.colliding();
+            if colliding < best.1 {
+                best = (pool[..letters].to_vec(), colliding, collisions.largest());
+            }
+        } else {
+            pool.swap(i, j);
+            collisions.replace(previous);
+        }
+    }
+    let (table, colliding, largest) = best;
+    Outcome {
+        table,
+        colliding,
+        largest,
+        initial_colliding,
+        initial_largest,
+    }
+}
+
+/// Anneal the current codes for the word list; the record gives the colliding words and
+/// the largest group before and after, and the code book found, for `--code-book`
+pub fn run(
+    words_file: Option<&str>,
+    options: &Options,
+    on_progress: impl FnMut(&Progress),
+) -> Result<Record, Report> {
+    let all_words: Vec<String> = get_all_words(words_file)?;
+    info!("Annealing the codes of {} words...", all_words.len());
+    let outcome = anneal(&all_words, get_merse_table(), options, on_progress);
+    info!("Annealing the codes of {} words: done", all_words.len());
+    let code_book: Vec<String> = outcome.table.iter().map(merse_to_morse).collect();
+    Ok(Record::new()
+        .with("seed", options.seed)
+        .with("iterations", options.iterations)
+        .with("initial colliding", outcome.initial_colliding)
+        .with("colliding", outcome.colliding)
+        .with("initial largest", outcome.initial_largest)
+        .with("largest", outcome.largest)
+        .with("code book", code_book.join(" ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::words;
+
+    fn options(code_set: CodeSet) -> Options {
+        Options {
+            iterations: 200,
+            temperature: 1.0,
+            seed: 380,
+            code_set,
+            progress: 50,
+        }
+    }
+
+    #[test]
+    fn test_pool() {
+        let table = get_merse_table();
+        assert_eq!(CodeSet::Permute.pool(table), table.to_vec());
+        let all = CodeSet::All.pool(table);
+        assert_eq!(all.len(), 30);
+        assert_eq!(&all[..26], &table[..]);
+        assert!(all.contains(&"----".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_collisions() {
+        let table = get_merse_table();
+        // et and a share .-, eee and s share ...
+        let mut collisions = Collisions::new(&words(&["et", "a", "eee", "s", "o"]), table);
+        assert_eq!(collisions.colliding(), 4);
+        assert_eq!(collisions.largest(), 2);
+        let mut swapped = table.to_vec();
+        swapped.swap(0, 14);
+        collisions.recode(&swapped, &[0, 14]);
+        // a is ---, o is .-
+        assert_eq!(collisions.colliding(), 4);
+        swapped.swap(4, 18);
+        collisions.recode(&swapped, &[4, 18]);
+        // e is ..., s is .: et is ...-, eee is .........
+        assert_eq!(collisions.colliding(), 0);
+        assert_eq!(collisions.largest(), 1);
+    }
+
+    #[test]
+    fn test_anneal() {
+        let all_words = words(&["et", "a", "eee", "s", "n", "te", "i", "ee"]);
+        for code_set in [CodeSet::Permute, CodeSet::All] {
+            let mut reports = 0;
+            let outcome = anneal(&all_words, get_merse_table(), &options(code_set), |_| {
+                reports += 1
+            });
+            assert_eq!(reports, 4);
+            assert_eq!(outcome.initial_colliding, 8);
+            assert_eq!(outcome.colliding, 0);
+            let collisions = Collisions::new(&all_words, &outcome.table);
+            assert_eq!(collisions.colliding(), 0);
+            let mut codes = outcome.table.clone();
+            codes.sort();
+            codes.dedup();
+            assert_eq!(codes.len(), 26);
+        }
+        // the seed makes the search reproducible
+        let first = anneal(
+            &all_words,
+            get_merse_table(),
+            &options(CodeSet::All),
+            |_| {},
+        );
+        let second = anneal(
+            &all_words,
+            get_merse_table(),
+            &options(CodeSet::All),
+            |_| {},
+        );
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_run() {
+        let record = run(Some("tests/wordlist"), &options(CodeSet::Permute), |_| {}).unwrap();
+        let colliding = record.get("colliding").unwrap().as_u64().unwrap();
+        assert!(colliding <= record.get("initial colliding").unwrap().as_u64().unwrap());
+    }
+}
@@ -0,0 +1,169 @@
+//! Word pairs related through their codes: the code of the first word reversed,
+//! complemented (dots and dashes swapped) or both is the code of the second word.
+
+use crate::decode::all_words_to_smooshedmerse;
+use crate::merses::merse_to_morse;
+use crate::merses::Merse;
+use crate::records::Record;
+use crate::wordlist::get_all_words;
+use color_eyre::{eyre::eyre, Report};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::str::FromStr;
+use tracing::info;
+
+pub const TRANSFORMS: [&str; 3] = ["reverse", "complement", "revcomp"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transform {
+    Reverse,
+    Complement,
+    ReverseComplement,
+}
+
+impl FromStr for Transform {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reverse" => Ok(Transform::Reverse),
+            "complement" => Ok(Transform::Complement),
+            "revcomp" => Ok(Transform::ReverseComplement),
+            _ => Err(eyre!(
+                "Unknown transform {}, expected one of {}",
+                s,
+                TRANSFORMS.join(", ")
+            )),
+        }
+    }
+}
+
+impl Transform {
+    pub fn apply(self, merse: &Merse) -> Merse {
+        match self {
+            Transform::Reverse => merse.reversed(),
+            Transform::Complement => merse.complemented(),
+            Transform::ReverseComplement => merse.reversed().complemented(),
+        }
+    }
+}
+
+/// Pairs of words of the word list whose codes are related by the transform
+pub fn run(words_file: Option<&str>, transform: Transform) -> Result<Vec<Record>, Report> {
+    let all_words: Vec<String> = get_all_words(words_file)?;
+
+    info!("Converting all words to smooshedmorse...");
+    let all_merse_words = all_words_to_smooshedmerse(&all_words)?;
+    info!("Converting all words to smooshedmorse: done");
+
+    let pairs = find_pairs(&all_merse_words, transform);
+    info!("Found {} pairs", pairs.len());
+    Ok(pairs
+        .into_iter()
+        .map(|(i, j)| {
+            Record::new()
+                .with("first", all_words[i].clone())
+                .with("second", all_words[j].clone())
+                .with("code", merse_to_morse(&all_merse_words[i]))
+                .with("transformed", merse_to_morse(&all_merse_words[j]))
+                .with("length", all_merse_words[i].len())
+        })
+        .collect())
+}
+
+/// Return a map of each code and the positions of its words
+fn index_smooshedmorse(all_merse_words: &[Merse]) -> HashMap<&Merse, Vec<usize>> {
+    let mut map: HashMap<&Merse, Vec<usize>> = HashMap::new();
+    for (i, c) in all_merse_words.iter().enumerate() {
+        map.entry(c).or_default().push(i);
+    }
+    map
+}
+
+/// Positions `(i, j)` of the codes such that the transform of `i` is `j`, with `i <= j`
+/// since the transforms are their own inverse: a word paired with itself has a code
+/// unchanged by the transform (a palindrome when reversed)
+fn find_pairs(all_merse_words: &[Merse], transform: Transform) -> Vec<(usize, usize)> {
+    let index = index_smooshedmorse(all_merse_words);
+    #[cfg(feature = "parallel")]
+    let codes = all_merse_words.par_iter();
+    #[cfg(not(feature = "parallel"))]
+    let codes = all_merse_words.iter();
+    let pairs: Vec<Vec<(usize, usize)>> = codes
+        .enumerate()
+        .map(|(i, code)| match index.get(&transform.apply(code)) {
+            Some(matches) => matches
+                .iter()
+                .filter(|j| i <= **j)
+                .map(|j| (i, *j))
+                .collect(),
+            None => Vec::new(),
+        })
+        .collect();
+    pairs.into_iter().flatten().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn m(code: &str) -> Merse {
+        code.parse().unwrap()
+    }
+
+    #[test]
+    fn test_transform() {
+        assert_eq!("reverse".parse::<Transform>().unwrap(), Transform::Reverse);
+        assert!("mirror".parse::<Transform>().is_err());
+        assert_eq!(Transform::Reverse.apply(&m("-..")), m("..-"));
+        assert_eq!(Transform::Complement.apply(&m("-..")), m(".--"));
+        assert_eq!(Transform::ReverseComplement.apply(&m("-..")), m("--."));
+        for name in TRANSFORMS.iter() {
+            let transform: Transform = name.parse().unwrap();
+            let code = m(".--.-...--");
+            assert_eq!(transform.apply(&transform.apply(&code)), code);
+        }
+    }
+
+    #[test]
+    fn test_find_pairs() {
+        let codes = [m("-.."), m("..-"), m(".-."), m(".--"), m("-.-"), m("..-")];
+        assert_eq!(
+            find_pairs(&codes, Transform::Reverse),
+            vec![(0, 1), (0, 5), (2, 2), (4, 4)]
+        );
+        assert_eq!(
+            find_pairs(&codes, Transform::Complement),
+            vec![(0, 3), (2, 4)]
+        );
+        assert_eq!(
+            find_pairs(&codes, Transform::ReverseComplement),
+            vec![(1, 3), (2, 4), (3, 5)]
+        );
+        assert!(find_pairs(&[], Transform::Reverse).is_empty());
+    }
+
+    #[test]
+    fn test_run() {
+        let records = run(Some("tests/wordlist"), Transform::Reverse).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].get("first").unwrap(), "catmint");
+        assert_eq!(records[0].get("second").unwrap(), "catmint");
+        let records = run(Some("tests/wordlist"), Transform::ReverseComplement).unwrap();
+        for record in records {
+            let code: Merse = record
+                .get("code")
+                .unwrap()
+                .as_str()
+                .unwrap()
+                .parse()
+                .unwrap();
+            let transformed = record.get("transformed").unwrap().as_str().unwrap();
+            assert_eq!(
+                merse_to_morse(&Transform::ReverseComplement.apply(&code)),
+                transformed
+            );
+        }
+    }
+}
@@ -5,13 +5,15 @@
 //! https://www.reddit.com/r/dailyprogrammer/comments/cn6gz5/20190807_challenge_380_intermediate_smooshed/
 
 use crate::encode::encode;
-use crate::merses::{merse_to_morse, smooshedmorse_to_merse};
+use crate::merses::{char_to_merse, merse_to_morse, smooshedmorse_to_merse, Merse};
 use crate::morses::validate_morse_str;
-use crate::morses::ALPHABET;
+use crate::morses::{ALPHABET, DOT};
 use color_eyre::{eyre::eyre, Report};
 use itertools::Itertools;
 use rand::seq::SliceRandom;
 use rand::thread_rng;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use tracing::{debug, error, info, trace};
@@ -19,7 +21,14 @@ use tracing::{debug, error, info, trace};
 const INCREMENT: u8 = 4;
 
 pub fn run(smooshed_alphabet_permutation: Option<&str>) -> Result<Vec<String>, Report> {
-    let smalpha: String = match smooshed_alphabet_permutation {
+    let smalpha = smalpha_or_random(smooshed_alphabet_permutation)?;
+    validate_smalpha(&smalpha)?;
+    solve(&smalpha, None)
+}
+
+/// The given smooshed alphabet permutation, or a random one
+pub fn smalpha_or_random(smooshed_alphabet_permutation: Option<&str>) -> Result<String, Report> {
+    Ok(match smooshed_alphabet_permutation {
         Some(smalpha) => smalpha.into(),
         None => {
             let random_alphabet = random_alphabet();
@@ -34,10 +43,49 @@ pub fn run(smooshed_alphabet_permutation: Option<&str>) -> Result<Vec<String>, R
             );
             smalpha
         }
-    };
-    validate_smalpha(&smalpha)?;
+    })
+}
+
+/// Read the alphabet permutations of the file (one per line) to solve with `run_batch`
+pub fn read_smalphas(file: &str) -> Result<Vec<String>, Report> {
+    let smalphas: Vec<String> = std::fs::read_to_string(file)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+        .collect();
+    info!(
+        "Loaded {} alphabet permutations from {}",
+        smalphas.len(),
+        file
+    );
+    Ok(smalphas)
+}
+
+/// Find a permutation for each of the given smooshed alphabet permutations, in the same
+/// order. With the parallel feature they are solved on all threads. Each search fails after
+/// `max_steps` permutations tried, if given.
+pub fn run_batch(smalphas: &[String], max_steps: Option<usize>) -> Result<Vec<String>, Report> {
+    for smalpha in smalphas {
+        validate_smalpha(smalpha)?;
+    }
+    #[cfg(feature = "parallel")]
+    let smalphas = smalphas.par_iter();
+    #[cfg(not(feature = "parallel"))]
+    let smalphas = smalphas.iter();
+    smalphas
+        .map(|smalpha| {
+            Ok(solve(smalpha, max_steps)?
+                .into_iter()
+                .next()
+                .expect("No permutation in solver output"))
+        })
+        .collect()
+}
+
+fn solve(smalpha: &str, max_steps: Option<usize>) -> Result<Vec<String>, Report> {
     let permutations_of_char: Vec<Vec<char>> =
-        find_permutations(&smooshedmorse_to_merse(&smalpha)?, INCREMENT)?;
+        find_permutations(&smooshedmorse_to_merse(smalpha)?, INCREMENT, max_steps)?;
     Ok(permutations_of_char
         .into_iter()
         .map(|alphchars| alphchars.into_iter().collect::<String>())
@@ -45,7 +93,7 @@ pub fn run(smooshed_alphabet_permutation: Option<&str>) -> Result<Vec<String>, R
 }
 
 fn validate_smalpha(smalpha: &str) -> Result<(), Report> {
-    validate_morse_str(&smalpha)?;
+    validate_morse_str(smalpha)?;
     if smalpha.len() != smalpha_right_len() {
         error!(
             "{} length is {}, must be {}",
@@ -55,34 +103,48 @@ fn validate_smalpha(smalpha: &str) -> Result<(), Report> {
         );
         return Err(eyre!("Wrong length of alphabet permutation"));
     }
+    // any permutation has the dots (and so the dashes) of the alphabet
+    let dots = smalpha.chars().filter(|ch| *ch == DOT).count();
+    if dots != smalpha_right_dots() {
+        return Err(eyre!(
+            "Not an alphabet permutation: {} dots, the alphabet has {}",
+            dots,
+            smalpha_right_dots()
+        ));
+    }
     Ok(())
 }
 
-fn smalpha_right_len() -> usize {
+fn smooshed_alphabet() -> String {
     encode(&ALPHABET.iter().collect::<String>())
         .expect("Unexpected error encoding alphabet")
         .first()
         .expect("Unexpected empty alphabet")
-        .len()
+        .clone()
+}
+
+fn smalpha_right_len() -> usize {
+    smooshed_alphabet().len()
+}
+
+fn smalpha_right_dots() -> usize {
+    smooshed_alphabet().chars().filter(|ch| *ch == DOT).count()
 }
 
-fn chars_to_smooshedmerse(chars: &[char]) -> Result<Vec<bool>, Report> {
-    let smerse = smooshedmorse_to_merse(
-        &encode(&chars.iter().collect::<String>())
-            .expect("No encoding output")
-            .first()
-            .expect("No encoding output"),
-    )?;
+fn chars_to_smooshedmerse(chars: &[char]) -> Result<Merse, Report> {
+    let mut smerse = Merse::new();
+    for ch in chars {
+        smerse.extend_from_merse(&char_to_merse(*ch)?);
+    }
     Ok(smerse)
 }
 
 #[derive(Debug)]
 struct SegmentChars {
     take: Vec<char>,
-    merse_take: Vec<bool>,
+    merse_take: Merse,
     left: Vec<char>,
     source: Vec<char>,
-    perm_size: usize,
     permutations: Vec<Vec<char>>,
 }
 
@@ -94,13 +156,12 @@ impl SegmentChars {
             perm_size
         };
         let permutations = chars
-            .to_vec()
-            .into_iter()
+            .iter()
+            .copied()
             .permutations(perm_size)
             .collect::<Vec<Vec<char>>>();
         SegmentChars {
             source: chars.to_owned(),
-            perm_size,
             take: vec![],
             merse_take: chars_to_smooshedmerse(&[])
                 .expect("An empty str shouldn't contain invalid characters..."),
@@ -112,8 +173,8 @@ impl SegmentChars {
         // source - taken
         let taken_set: HashSet<_> = taken.iter().collect();
         let difference: Vec<char> = source
-            .to_owned()
-            .into_iter()
+            .iter()
+            .copied()
             .filter(|item| !taken_set.contains(item))
             .collect();
         difference
@@ -140,7 +201,7 @@ fn random_alphabet() -> String {
 }
 
 /// Return true if all segchs match with input (in the correct order)
-fn check_for_match(input: &[bool], segchs: &HashMap<usize, SegmentChars>) -> bool {
+fn check_for_match(input: &Merse, segchs: &HashMap<usize, SegmentChars>) -> bool {
     trace!("Checking SegmentChars match on {:?}", input);
     let mut i = 0;
     let mut n = 0;
@@ -150,10 +211,9 @@ fn check_for_match(input: &[bool], segchs: &HashMap<usize, SegmentChars>) -> boo
             Some(c) => c,
             None => break,
         };
-        let islice: &[bool] = match input.get(i..(i + c.merse_take.len())) {
-            Some(e) => &e,
-            None => &[],
-        };
+        let islice: Merse = input
+            .get_slice(i..(i + c.merse_take.len()))
+            .unwrap_or_default();
         if c.merse_take != islice {
             trace!(
                 "Mismatch on {:?} vs. {:?} (#{}-{})",
@@ -186,10 +246,11 @@ fn get_taken(segchs: &mut HashMap<usize, SegmentChars>) -> String {
 }
 
 fn algo(
-    input: &[bool],
+    input: &Merse,
     increment: usize,
     mut i: usize,
     segchs: &mut HashMap<usize, SegmentChars>,
+    steps: &mut Steps,
 ) -> Result<Option<Vec<Vec<char>>>, Report> {
     debug!(
         "Entering algorithm level #{}. Matched: {}",
@@ -202,6 +263,7 @@ fn algo(
         //     i,
         //     segchs.keys().collect::<Vec<&usize>>()
         // );
+        steps.take()?;
         segchs.get_mut(&i).unwrap().new_perm()?;
         if segchs.get(&i).unwrap().take.is_empty() {
             let failing = segchs.remove(&i).unwrap();
@@ -235,7 +297,7 @@ fn algo(
             let segch_new = SegmentChars::init(left, increment);
             i += 1;
             segchs.insert(i, segch_new);
-            let step = algo(input, increment, i, segchs)?;
+            let step = algo(input, increment, i, segchs, steps)?;
             match step {
                 Some(res) => return Ok(Some(res)), // ascent after success
                 None => {
@@ -251,17 +313,48 @@ fn algo(
     }
 }
 
-fn find_permutations(merse_alpha_perm: &[bool], increment: u8) -> Result<Vec<Vec<char>>, Report> {
+/// Permutations tried by a search, failing it past the maximum if any
+#[derive(Debug, Clone, Copy, Default)]
+struct Steps {
+    done: usize,
+    max: Option<usize>,
+}
+
+impl Steps {
+    fn take(&mut self) -> Result<(), Report> {
+        self.done += 1;
+        match self.max {
+            Some(max) if self.done > max => Err(eyre!(
+                "No alphabet permutation found in {} steps, giving up",
+                max
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn find_permutations(
+    merse_alpha_perm: &Merse,
+    increment: u8,
+    max_steps: Option<usize>,
+) -> Result<Vec<Vec<char>>, Report> {
     let increment: usize = increment as usize;
     let mut segchs: HashMap<usize, SegmentChars> = HashMap::new();
     let i = 0;
-    let segch0 = SegmentChars::init(&random_alphabet().chars().collect::<Vec<char>>(), increment);
+    // alphabetical order rather than a random one, so that the same input always gives the
+    // same permutation
+    let segch0 = SegmentChars::init(&ALPHABET, increment);
     segchs.insert(i, segch0);
     info!(
         "Trying to find source alphabet permutation for '{}'",
-        merse_to_morse(&merse_alpha_perm),
+        merse_to_morse(merse_alpha_perm),
     );
-    let res = algo(merse_alpha_perm, increment, i, &mut segchs)?;
+    let mut steps = Steps {
+        max: max_steps,
+        ..Steps::default()
+    };
+    let res = algo(merse_alpha_perm, increment, i, &mut segchs, &mut steps)?;
+    debug!("Search done in {} steps", steps.done);
     let r = match res {
         None => {
             error!("FAILURE, no match for {}", merse_to_morse(merse_alpha_perm));
@@ -290,13 +383,13 @@ mod tests {
         let m0 = chars_to_smooshedmerse(&s0).unwrap();
         let m1 = chars_to_smooshedmerse(&s1).unwrap();
         let m2 = chars_to_smooshedmerse(&s2).unwrap();
-        let mut chain_m01 = Vec::new(); // same length
-        chain_m01.extend_from_slice(&m0); // longer
-        chain_m01.extend_from_slice(&m1);
-        let mut chain_m012 = Vec::new();
-        chain_m012.extend_from_slice(&m0);
-        chain_m012.extend_from_slice(&m1);
-        chain_m012.extend_from_slice(&m2);
+        let mut chain_m01 = Merse::new(); // same length
+        chain_m01.extend_from_merse(&m0); // longer
+        chain_m01.extend_from_merse(&m1);
+        let mut chain_m012 = Merse::new();
+        chain_m012.extend_from_merse(&m0);
+        chain_m012.extend_from_merse(&m1);
+        chain_m012.extend_from_merse(&m2);
 
         let mut segch0 = SegmentChars::init(&s0, 3);
         segch0.take = s0.into_iter().collect();
@@ -312,18 +405,23 @@ mod tests {
 
         println!("segch: {:?}", &segchs);
 
-        assert!(!check_for_match(&[], &segchs));
-        assert!(!check_for_match(&[true, false], &segchs));
+        assert!(!check_for_match(&Merse::new(), &segchs));
+        assert!(!check_for_match(&Merse::from(vec![true, false]), &segchs));
         assert!(check_for_match(&chain_m01, &segchs));
         assert!(check_for_match(&chain_m012, &segchs));
     }
 
     #[test]
     fn test_chars_to_smooshedmerse() {
-        assert_eq!(chars_to_smooshedmerse(&['a']).unwrap(), vec![false, true]);
+        assert_eq!(
+            chars_to_smooshedmerse(&['a']).unwrap(),
+            Merse::from(vec![false, true])
+        );
         assert_eq!(
             chars_to_smooshedmerse(&['a', 'b', 'c']).unwrap(),
-            vec![false, true, true, false, false, false, true, false, true, false]
+            Merse::from(vec![
+                false, true, true, false, false, false, true, false, true, false
+            ])
         );
     }
 
@@ -338,6 +436,8 @@ mod tests {
         assert!(validate_smalpha("-!.-").is_err());
         assert!(validate_smalpha("-abc-").is_err());
         assert!(validate_smalpha("-..-").is_err());
+        // the right length, but 42 dots instead of 44
+        assert!(validate_smalpha(&(".-".repeat(40) + "..")).is_err());
     }
 
     #[test]
@@ -349,6 +449,47 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_run_batch() {
+        assert_eq!(run_batch(&[], None).unwrap(), Vec::<String>::new());
+        assert!(run_batch(&[
+            ".--...-.-.-.....-.--........----.-.-..---.---.--.--.-.-....-..-...-.---..--.----.."
+                .to_string(),
+            "-..-".to_string()
+        ], None)
+        .is_err());
+    }
+
+    #[test]
+    fn test_max_steps() {
+        // solved in a few steps, the other one in millions
+        let quick = encode("zyxwvutsrqponmlkjihgfedcba").unwrap()[0].clone();
+        let slow = encode("qwertyuiopasdfghjklzxcvbnm").unwrap()[0].clone();
+        assert_eq!(
+            &encode(&solve(&quick, Some(100)).unwrap()[0]).unwrap()[0],
+            &quick
+        );
+        assert!(solve(&slow, Some(100)).is_err());
+    }
+
+    #[test]
+    fn test_run_batch_order() {
+        let smalphas: Vec<String> = ["wirnbfzehatqlojpgcvusyxkmd", "abcdefghijklmnopqrstuvwxyz"]
+            .iter()
+            .map(|alphabet| encode(alphabet).unwrap()[0].clone())
+            .collect();
+        let batch = run_batch(&smalphas, None).unwrap();
+        // in input order, and the same as solving the lines again one by one
+        let sequential: Vec<String> = smalphas
+            .iter()
+            .map(|smalpha| solve(smalpha, None).unwrap()[0].clone())
+            .collect();
+        assert_eq!(batch, sequential);
+        for (smalpha, permutation) in smalphas.iter().zip(batch.iter()) {
+            assert_eq!(&encode(permutation).unwrap()[0], smalpha);
+        }
+    }
+
     #[test]
     fn test_random_alphabet() {
         assert_eq!(random_alphabet().len(), 26);
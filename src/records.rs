@@ -0,0 +1,302 @@
+//! Structured results: each subcommand returns records (named fields in column order),
+//! written as plain text, a JSON array, line-delimited JSON, CSV or TSV.
+
+use color_eyre::{eyre::eyre, Report};
+use serde_json::{Map, Value};
+use std::convert::TryFrom;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+/// Text field standing for all the fields of the record, tab separated
+pub const ALL_FIELDS: &str = "*";
+
+/// Output format names, as accepted on the command line
+pub const FORMATS: [&str; 5] = ["text", "json", "ndjson", "csv", "tsv"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    /// Only the main field of each record, one value per line
+    #[default]
+    Text,
+    /// A JSON array of objects, one per line
+    Json,
+    /// One JSON object per line
+    Ndjson,
+    /// Comma separated values with a header line
+    Csv,
+    /// Tab separated values with a header line
+    Tsv,
+}
+
+impl FromStr for Format {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "ndjson" => Ok(Format::Ndjson),
+            "csv" => Ok(Format::Csv),
+            "tsv" => Ok(Format::Tsv),
+            _ => Err(eyre!(
+                "Unknown format `{}`, expected one of {}",
+                s,
+                FORMATS.join(", ")
+            )),
+        }
+    }
+}
+
+/// One result: named fields, kept in insertion order
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Record {
+    fields: Vec<(&'static str, Value)>,
+}
+
+impl Record {
+    pub fn new() -> Self {
+        Record::default()
+    }
+
+    /// Add a field, builder style
+    pub fn with<V: Into<Value>>(mut self, name: &'static str, value: V) -> Self {
+        self.fields.push((name, value.into()));
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.fields
+            .iter()
+            .find(|(field, _)| *field == name)
+            .map(|(_, value)| value)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.fields.iter().map(|(name, _)| *name)
+    }
+
+    pub fn to_json(&self) -> Value {
+        let map: Map<String, Value> = self
+            .fields
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect();
+        Value::Object(map)
+    }
+}
+
+/// A count as a JSON number, or as a string when too big for one
+pub fn count_value(count: u128) -> Value {
+    match u64::try_from(count) {
+        Ok(count) => Value::from(count),
+        Err(_) => Value::from(count.to_string()),
+    }
+}
+
+/// Write records one by one (streaming) in the given format.
+/// In text format only the `text_field` of each record is written, arrays one item per line
+/// (or all of them on one line with `ALL_FIELDS`).
+/// Table formats take their header from the first record.
+pub struct RecordWriter<W: Write> {
+    output: W,
+    format: Format,
+    text_field: &'static str,
+    written: usize,
+}
+
+impl<W: Write> RecordWriter<W> {
+    pub fn new(output: W, format: Format, text_field: &'static str) -> Self {
+        RecordWriter {
+            output,
+            format,
+            text_field,
+            written: 0,
+        }
+    }
+
+    pub fn write(&mut self, record: &Record) -> io::Result<()> {
+        match self.format {
+            Format::Text if self.text_field == ALL_FIELDS => {
+                let line: Vec<String> = record
+                    .fields
+                    .iter()
+                    .map(|(_, value)| table_value(value))
+                    .collect();
+                writeln!(self.output, "{}", line.join("\t"))?;
+            }
+            Format::Text => {
+                for line in text_lines(record.get(self.text_field)) {
+                    writeln!(self.output, "{}", line)?;
+                }
+            }
+            Format::Json => {
+                let sep = if self.written == 0 { "[" } else { "," };
+                writeln!(self.output, "{}", sep)?;
+                write!(self.output, "{}", record.to_json())?;
+            }
+            Format::Ndjson => writeln!(self.output, "{}", record.to_json())?,
+            Format::Csv | Format::Tsv => {
+                let delimiter = if self.format == Format::Csv {
+                    ','
+                } else {
+                    '\t'
+                };
+                if self.written == 0 {
+                    let header: Vec<String> = record
+                        .names()
+                        .map(|name| table_field(name, delimiter))
+                        .collect();
+                    writeln!(self.output, "{}", header.join(&delimiter.to_string()))?;
+                }
+                let row: Vec<String> = record
+                    .fields
+                    .iter()
+                    .map(|(_, value)| table_field(&table_value(value), delimiter))
+                    .collect();
+                writeln!(self.output, "{}", row.join(&delimiter.to_string()))?;
+            }
+        }
+        self.written += 1;
+        Ok(())
+    }
+
+    /// Close the output (the JSON array) and flush it
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.format == Format::Json {
+            match self.written {
+                0 => writeln!(self.output, "[]")?,
+                _ => writeln!(self.output, "\n]")?,
+            }
+        }
+        self.output.flush()?;
+        Ok(self.output)
+    }
+}
+
+/// Write all the records to stdout
+pub fn print_records(
+    records: &[Record],
+    format: Format,
+    text_field: &'static str,
+) -> Result<(), Report> {
+    let mut writer = RecordWriter::new(io::stdout().lock(), format, text_field);
+    for record in records {
+        writer.write(record)?;
+    }
+    writer.finish().map(drop)?;
+    Ok(())
+}
+
+fn text_lines(value: Option<&Value>) -> Vec<String> {
+    match value {
+        None | Some(Value::Null) => vec![],
+        Some(Value::Array(items)) => items.iter().map(plain_value).collect(),
+        Some(value) => vec![plain_value(value)],
+    }
+}
+
+fn plain_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        value => value.to_string(),
+    }
+}
+
+/// Arrays are space separated in table cells
+fn table_value(value: &Value) -> String {
+    match value {
+        Value::Array(items) => items.iter().map(plain_value).collect::<Vec<_>>().join(" "),
+        value => plain_value(value),
+    }
+}
+
+/// Quote a table cell if needed, doubling its quotes
+fn table_field(cell: &str, delimiter: char) -> String {
+    if cell.contains([delimiter, '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records() -> Vec<Record> {
+        vec![
+            Record::new()
+                .with("input", "-.")
+                .with("words", vec!["te", "n"])
+                .with("count", 2),
+            Record::new()
+                .with("input", "a,\"b\"")
+                .with("words", Vec::<String>::new())
+                .with("count", 0),
+        ]
+    }
+
+    fn written(format: Format) -> String {
+        let mut writer = RecordWriter::new(Vec::new(), format, "words");
+        for record in records() {
+            writer.write(&record).unwrap();
+        }
+        String::from_utf8(writer.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_format_from_str() {
+        for name in FORMATS.iter() {
+            assert!(name.parse::<Format>().is_ok());
+        }
+        assert_eq!("ndjson".parse::<Format>().unwrap(), Format::Ndjson);
+        assert!("xml".parse::<Format>().is_err());
+    }
+
+    #[test]
+    fn test_record() {
+        let record = Record::new().with("b", 1).with("a", "x");
+        assert_eq!(record.names().collect::<Vec<_>>(), vec!["b", "a"]);
+        assert_eq!(record.get("a"), Some(&Value::from("x")));
+        assert_eq!(record.get("c"), None);
+        assert_eq!(record.to_json().to_string(), "{\"b\":1,\"a\":\"x\"}");
+    }
+
+    #[test]
+    fn test_count_value() {
+        assert_eq!(count_value(3), Value::from(3));
+        assert_eq!(count_value(u128::MAX), Value::from(u128::MAX.to_string()));
+    }
+
+    #[test]
+    fn test_record_writer() {
+        assert_eq!(written(Format::Text), "te\nn\n");
+        assert_eq!(
+            written(Format::Json),
+            "[\n{\"input\":\"-.\",\"words\":[\"te\",\"n\"],\"count\":2},\n{\"input\":\"a,\\\"b\\\"\",\"words\":[],\"count\":0}\n]\n"
+        );
+        assert_eq!(
+            written(Format::Ndjson),
+            "{\"input\":\"-.\",\"words\":[\"te\",\"n\"],\"count\":2}\n{\"input\":\"a,\\\"b\\\"\",\"words\":[],\"count\":0}\n"
+        );
+        assert_eq!(
+            written(Format::Csv),
+            "input,words,count\n-.,te n,2\n\"a,\"\"b\"\"\",,0\n"
+        );
+        assert_eq!(
+            written(Format::Tsv),
+            "input\twords\tcount\n-.\tte n\t2\n\"a,\"\"b\"\"\"\t\t0\n"
+        );
+        let mut writer = RecordWriter::new(Vec::new(), Format::Text, ALL_FIELDS);
+        writer.write(&records()[0]).unwrap();
+        assert_eq!(
+            String::from_utf8(writer.finish().unwrap()).unwrap(),
+            "-.\tte n\t2\n"
+        );
+        let json = RecordWriter::new(Vec::new(), Format::Json, "words")
+            .finish()
+            .unwrap();
+        assert_eq!(String::from_utf8(json).unwrap(), "[]\n");
+    }
+}
@@ -0,0 +1,333 @@
+//! Interactive session: the word list is loaded, indexed and its statistics computed once,
+//! then commands are read
+//! with history and tab completion of commands and options.
+
+use crate::decode::{decode_with_index, DecodeIndex};
+use crate::encode::encode;
+use crate::letters::{count_letters, decode_letters};
+use crate::records::{count_value, print_records, Format, Record, ALL_FIELDS, FORMATS};
+use crate::resmoosh::unsmoosh_with_index;
+use crate::search::{search, SearchQuery, SEARCH_OPTIONS};
+use crate::spaced::Separators;
+use crate::stats::Stats;
+use crate::wordlist::get_all_words;
+use color_eyre::{eyre::eyre, Report};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use std::path::PathBuf;
+use tracing::{debug, info, warn};
+
+const PROMPT: &str = "smooshedmorse> ";
+/// History file, in the home directory
+const HISTORY_FILE: &str = ".smooshedmorse_history";
+
+pub const COMMANDS: [&str; 11] = [
+    "encode", "decode", "letters", "count", "unsmoosh", "search", "stats", "format", "help",
+    "exit", "quit",
+];
+
+const HELP: &str = "Commands:
+  encode WORD...          smooshed morse of the words
+  decode CODE...          words of the word list encoding to the codes
+  letters CODE            every letter string encoding to the code
+  count CODE              number of letter strings encoding to the code
+  unsmoosh CODE           split the code in the fewest words, to spaced morse
+  search [OPTIONS]        codes of the word list matching all the options:
+                          --contains PAT --starts-with PAT --ends-with PAT
+                          --length N --limit N (a bare PAT is --contains)
+  stats                   word list statistics, as the stats subcommand
+  format FORMAT           output format: text, json, ndjson, csv or tsv
+  help                    this help
+  exit, quit              leave (or Ctrl-D)";
+
+/// What a command asks the session to do
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    /// Print the records, with the text field used in text format
+    Print(Vec<Record>, &'static str),
+    SetFormat(Format),
+    Help,
+    Quit,
+    Nothing,
+}
+
+/// Run the interactive session until exit or end of input
+pub fn run(wordlist: Option<&str>, format: Format) -> Result<(), Report> {
+    let all_words: Vec<String> = get_all_words(wordlist)?;
+    info!("Indexing {} words...", all_words.len());
+    let index = DecodeIndex::new(&all_words)?;
+    let stats = Stats::new(&all_words)?;
+    info!("Indexing {} words: done", all_words.len());
+    let mut editor: Editor<ReplHelper, DefaultHistory> = Editor::new()?;
+    editor.set_helper(Some(ReplHelper));
+    let history = history_file();
+    if let Some(path) = &history {
+        if editor.load_history(path).is_err() {
+            debug!("No history loaded from {:?}", path);
+        }
+    }
+    println!("{} codes indexed, type help for the commands", index.len());
+    let mut format = format;
+    loop {
+        let line = match editor.readline(PROMPT) {
+            Ok(line) => line,
+            // Ctrl-C drops the current line only
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        editor.add_history_entry(line.as_str())?;
+        match execute(&line, &index, &stats) {
+            Ok(Action::Print(records, text_field)) => print_records(&records, format, text_field)?,
+            Ok(Action::SetFormat(new_format)) => format = new_format,
+            Ok(Action::Help) => println!("{}", HELP),
+            Ok(Action::Quit) => break,
+            Ok(Action::Nothing) => {}
+            Err(e) => eprintln!("Error: {}", e),
+        }
+    }
+    if let Some(path) = &history {
+        if let Err(e) = editor.save_history(path) {
+            warn!("Couldn't save history to {:?}: {}", path, e);
+        }
+    }
+    Ok(())
+}
+
+/// Execute a command line against the index and the statistics of its word list
+pub fn execute(line: &str, index: &DecodeIndex, stats: &Stats) -> Result<Action, Report> {
+    let mut args = line.split_whitespace();
+    let command = match args.next() {
+        Some(command) => command,
+        None => return Ok(Action::Nothing),
+    };
+    let args: Vec<&str> = args.collect();
+    info!("Command: {} {:?}", command, args);
+    let action = match command {
+        "encode" => {
+            let mut records: Vec<Record> = Vec::new();
+            for word in needed(&args)? {
+                let code = encode(word)?.remove(0);
+                records.push(
+                    Record::new()
+                        .with("input", *word)
+                        .with("length", code.len())
+                        .with("code", code),
+                );
+            }
+            Action::Print(records, "code")
+        }
+        "decode" => {
+            let mut records: Vec<Record> = Vec::new();
+            for code in needed(&args)? {
+                let words = decode_with_index(code, index)?;
+                records.push(
+                    Record::new()
+                        .with("input", *code)
+                        .with("count", words.len())
+                        .with("words", words),
+                );
+            }
+            Action::Print(records, "words")
+        }
+        "letters" => {
+            let code = single(&args)?;
+            let words = decode_letters(code)?;
+            let record = Record::new()
+                .with("input", code)
+                .with("count", words.len())
+                .with("words", words);
+            Action::Print(vec![record], "words")
+        }
+        "count" => {
+            let code = single(&args)?;
+            let record = Record::new()
+                .with("input", code)
+                .with("count", count_value(count_letters(code)?));
+            Action::Print(vec![record], "count")
+        }
+        "unsmoosh" => {
+            let code = single(&args)?;
+            let unsmooshed = unsmoosh_with_index(code, index, &Separators::default())?;
+            let record = Record::new()
+                .with("input", code)
+                .with("words", unsmooshed.words)
+                .with("morse", unsmooshed.morse);
+            Action::Print(vec![record], "morse")
+        }
+        "search" => {
+            let query = SearchQuery::parse(&args)?;
+            let records: Vec<Record> = search(index, &query)
+                .into_iter()
+                .map(|hit| {
+                    Record::new()
+                        .with("code", hit.code)
+                        .with("count", hit.words.len())
+                        .with("words", hit.words)
+                })
+                .collect();
+            Action::Print(records, ALL_FIELDS)
+        }
+        "stats" => Action::Print(stats.to_records(), ALL_FIELDS),
+        "format" => Action::SetFormat(single(&args)?.parse()?),
+        "help" => Action::Help,
+        "exit" | "quit" => Action::Quit,
+        _ => return Err(eyre!("Unknown command `{}`, type help", command)),
+    };
+    Ok(action)
+}
+
+fn needed<'a>(args: &'a [&'a str]) -> Result<&'a [&'a str], Report> {
+    match args.is_empty() {
+        true => Err(eyre!("Missing argument")),
+        false => Ok(args),
+    }
+}
+
+fn single<'a>(args: &[&'a str]) -> Result<&'a str, Report> {
+    match args {
+        [arg] => Ok(arg),
+        _ => Err(eyre!("Expected one argument, got {}", args.len())),
+    }
+}
+
+fn history_file() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE))
+}
+
+/// Completion candidates for the word ending the line: commands first, then search
+/// options and formats. Returns the start of the word and the candidates.
+pub fn complete(line: &str) -> (usize, Vec<String>) {
+    let start = line.rfind(' ').map_or(0, |i| i + 1);
+    let word = &line[start..];
+    let candidates: &[&str] = match line.split_whitespace().next() {
+        _ if start == 0 => &COMMANDS,
+        Some("search") if word.starts_with('-') => &SEARCH_OPTIONS,
+        Some("format") => &FORMATS,
+        _ => &[],
+    };
+    let candidates = candidates
+        .iter()
+        .filter(|candidate| candidate.starts_with(word))
+        .map(|candidate| candidate.to_string())
+        .collect();
+    (start, candidates)
+}
+
+struct ReplHelper;
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(complete(&line[..pos]))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{index, words};
+
+    const WORDS: [&str; 5] = ["hello", "world", "sos", "oo", "a"];
+
+    fn execute_line(line: &str) -> Result<Action, Report> {
+        execute(line, &index(&WORDS), &Stats::new(&words(&WORDS)).unwrap())
+    }
+
+    fn printed(line: &str) -> Vec<Record> {
+        match execute_line(line).unwrap() {
+            Action::Print(records, _) => records,
+            action => panic!("Unexpected action {:?}", action),
+        }
+    }
+
+    #[test]
+    fn test_execute() {
+        assert_eq!(
+            printed("encode sos a"),
+            vec![
+                Record::new()
+                    .with("input", "sos")
+                    .with("length", 9)
+                    .with("code", "...---..."),
+                Record::new()
+                    .with("input", "a")
+                    .with("length", 2)
+                    .with("code", ".-")
+            ]
+        );
+        assert_eq!(
+            printed("  decode   ...---... ")[0].get("words"),
+            Some(&serde_json::json!(["sos"]))
+        );
+        assert_eq!(
+            printed("unsmoosh ......-...-..---.-----.-..-..-..")[0].get("words"),
+            Some(&serde_json::json!(["hello", "world"]))
+        );
+        assert_eq!(printed("count ...")[0].get("count"), Some(&4.into()));
+        assert_eq!(printed("letters -.")[0].get("count"), Some(&2.into()));
+        assert_eq!(
+            printed("search --contains ---")
+                .iter()
+                .map(|record| record.get("code").unwrap().clone())
+                .collect::<Vec<_>>(),
+            vec![
+                serde_json::json!("------"),
+                serde_json::json!("...---..."),
+                serde_json::json!(".-----.-..-..-.."),
+                serde_json::json!("......-...-..---")
+            ]
+        );
+        let stats = printed("stats");
+        assert_eq!(stats, Stats::new(&words(&WORDS)).unwrap().to_records());
+        assert_eq!(stats[0].get("value"), Some(&5.into()));
+        assert_eq!(execute_line("").unwrap(), Action::Nothing);
+        assert_eq!(
+            execute_line("format csv").unwrap(),
+            Action::SetFormat(Format::Csv)
+        );
+        assert_eq!(execute_line("quit").unwrap(), Action::Quit);
+        assert!(execute_line("encode").is_err());
+        assert!(execute_line("count . .").is_err());
+        assert!(execute_line("decode -x").is_err());
+        assert!(execute_line("format xml").is_err());
+        assert!(execute_line("fly").is_err());
+    }
+
+    #[test]
+    fn test_complete() {
+        assert_eq!(
+            complete("e"),
+            (0, vec!["encode".to_string(), "exit".to_string()])
+        );
+        assert_eq!(complete("st"), (0, vec!["stats".to_string()]));
+        assert_eq!(
+            complete("search --s"),
+            (7, vec!["--starts-with".to_string()])
+        );
+        assert_eq!(complete("format n"), (7, vec!["ndjson".to_string()]));
+        assert!(complete("search .-").1.is_empty());
+        assert!(complete("decode ").1.is_empty());
+    }
+}
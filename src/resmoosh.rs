@@ -0,0 +1,159 @@
+//! Convert between smooshed morse and spaced (standard) morse.
+//! `unsmoosh` puts back letter and word gaps following the best dictionary segmentation of
+//! the smooshed code, `smoosh` strips them.
+
+use crate::decode::DecodeIndex;
+use crate::merses::{smooshedmorse_to_merse, Merse};
+use crate::morses::validate_morse_str;
+use crate::spaced::{encode_spaced, Separators};
+use color_eyre::{eyre::eyre, Report};
+use tracing::{debug, info, trace};
+
+/// Smooshed morse split in words
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unsmooshed {
+    pub words: Vec<String>,
+    /// The words in spaced morse
+    pub morse: String,
+}
+
+pub fn unsmoosh(
+    smooshedmorse: &str,
+    wordlist: Option<&str>,
+    separators: &Separators,
+) -> Result<Unsmooshed, Report> {
+    let index = DecodeIndex::load(wordlist)?;
+    unsmoosh_with_index(smooshedmorse, &index, separators)
+}
+
+pub fn unsmoosh_with_index(
+    smooshedmorse: &str,
+    index: &DecodeIndex,
+    separators: &Separators,
+) -> Result<Unsmooshed, Report> {
+    let smooshedmorse = smooshedmorse.trim();
+    debug!("Unsmooshing: {}", smooshedmorse);
+    validate_morse_str(smooshedmorse)?;
+    let merse = smooshedmorse_to_merse(smooshedmorse)?;
+    let words = segment(&merse, index).ok_or_else(|| {
+        eyre!(
+            "`{}` can't be split into words of the word list",
+            smooshedmorse
+        )
+    })?;
+    info!("Segmented {} as {:?}", smooshedmorse, words);
+    let morse = encode_spaced(&words.join(" "), separators)?;
+    Ok(Unsmooshed { words, morse })
+}
+
+/// Split a smooshedmerse sentence into the fewest words of the index.
+/// When a code matches several words, the first one of the word list is taken.
+pub fn segment(smooshedmerse: &Merse, index: &DecodeIndex) -> Option<Vec<String>> {
+    if smooshedmerse.is_empty() {
+        return None;
+    }
+    // best[j]: (fewest words covering smooshedmerse[..j], start of the last one)
+    let mut best: Vec<Option<(usize, usize)>> = vec![None; smooshedmerse.len() + 1];
+    best[0] = Some((0, 0));
+    for i in 0..smooshedmerse.len() {
+        let words_count = match best[i] {
+            Some((words_count, _)) => words_count,
+            None => continue,
+        };
+        for len in 1..=index.max_code_len() {
+            let j = i + len;
+            match smooshedmerse.get_slice(i..j) {
+                Some(code) if !index.get(&code).is_empty() => {}
+                Some(_) => continue,
+                None => break,
+            }
+            if best[j].is_none_or(|(c, _)| words_count + 1 < c) {
+                trace!("{} words up to {}", words_count + 1, j);
+                best[j] = Some((words_count + 1, i));
+            }
+        }
+    }
+    let mut words: Vec<String> = Vec::new();
+    let mut j = smooshedmerse.len();
+    while j > 0 {
+        let (_, i) = best[j]?;
+        words.push(index.get(&smooshedmerse.slice(i..j))[0].clone());
+        j = i;
+    }
+    words.reverse();
+    Some(words)
+}
+
+/// Strip letter and word gaps from spaced morse
+pub fn smoosh(spaced_morse: &str, separators: &Separators) -> Result<Vec<String>, Report> {
+    debug!("Smooshing: {}", spaced_morse);
+    separators.validate()?;
+    let smooshed: String = spaced_morse
+        .split(separators.word.as_str())
+        .flat_map(|word| word.split(separators.letter.as_str()))
+        .map(str::trim)
+        .collect();
+    validate_morse_str(&smooshed)?;
+    Ok(vec![smooshed])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode::word_to_smooshedmorse;
+    use crate::test_utils::index;
+
+    fn test_index() -> DecodeIndex {
+        index(&["hello", "world", "hell", "low", "or", "ld", "a"])
+    }
+
+    #[test]
+    fn test_segment() {
+        let index = test_index();
+        let merse = smooshedmorse_to_merse(
+            &(word_to_smooshedmorse("hello").unwrap() + &word_to_smooshedmorse("world").unwrap()),
+        )
+        .unwrap();
+        assert_eq!(
+            segment(&merse, &index),
+            Some(vec!["hello".to_string(), "world".to_string()])
+        );
+        assert_eq!(segment(&Merse::new(), &index), None);
+        assert_eq!(segment(&"----".parse().unwrap(), &index), None);
+    }
+
+    #[test]
+    fn test_unsmoosh() {
+        assert_eq!(
+            unsmoosh_with_index(
+                "......-...-..---.-----.-..-..-..",
+                &test_index(),
+                &Separators::default()
+            )
+            .unwrap(),
+            Unsmooshed {
+                words: vec!["hello".to_string(), "world".to_string()],
+                morse: ".... . .-.. .-.. --- / .-- --- .-. .-.. -..".to_string()
+            }
+        );
+        assert!(unsmoosh_with_index("----", &test_index(), &Separators::default()).is_err());
+        assert!(unsmoosh_with_index("-x-", &test_index(), &Separators::default()).is_err());
+    }
+
+    #[test]
+    fn test_smoosh() {
+        assert_eq!(
+            smoosh(
+                ".... . .-.. .-.. --- / .-- --- .-. .-.. -..",
+                &Separators::default()
+            )
+            .unwrap(),
+            vec!["......-...-..---.-----.-..-..-..".to_string()]
+        );
+        assert_eq!(
+            smoosh("...|---||...", &Separators::new("|", "||").unwrap()).unwrap(),
+            vec!["...---...".to_string()]
+        );
+        assert!(smoosh("... x ...", &Separators::default()).is_err());
+    }
+}
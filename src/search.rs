@@ -0,0 +1,177 @@
+//! Search the codes of the word list index by morse pattern
+
+use crate::decode::DecodeIndex;
+use crate::merses::merse_to_morse;
+use crate::morses::validate_morse_str;
+use color_eyre::{eyre::eyre, Report};
+use tracing::debug;
+
+/// Conditions on the codes searched, all of them must hold
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchQuery {
+    pub contains: Option<String>,
+    pub starts_with: Option<String>,
+    pub ends_with: Option<String>,
+    /// Code length (dots and dashes)
+    pub length: Option<usize>,
+    /// Maximum number of codes returned
+    pub limit: Option<usize>,
+}
+
+/// Search options, as accepted by `SearchQuery::parse`
+pub const SEARCH_OPTIONS: [&str; 5] = [
+    "--contains",
+    "--starts-with",
+    "--ends-with",
+    "--length",
+    "--limit",
+];
+
+impl SearchQuery {
+    /// Parse `--option value` pairs, a bare pattern is a `--contains` one. A single contains
+    /// pattern is allowed.
+    pub fn parse(args: &[&str]) -> Result<Self, Report> {
+        let mut query = SearchQuery::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            // patterns may start with dashes too
+            if !SEARCH_OPTIONS.contains(arg) {
+                query.set_contains(arg)?;
+                continue;
+            }
+            let value = args
+                .next()
+                .ok_or_else(|| eyre!("Missing value for {}", arg))?;
+            match *arg {
+                "--contains" => query.set_contains(value)?,
+                "--starts-with" => query.starts_with = Some(morse_pattern(value)?),
+                "--ends-with" => query.ends_with = Some(morse_pattern(value)?),
+                "--length" => query.length = Some(value.parse()?),
+                "--limit" => query.limit = Some(value.parse()?),
+                _ => unreachable!(),
+            }
+        }
+        Ok(query)
+    }
+
+    fn set_contains(&mut self, pattern: &str) -> Result<(), Report> {
+        if let Some(contains) = &self.contains {
+            return Err(eyre!(
+                "A single contains pattern is allowed: `{}` then `{}`",
+                contains,
+                pattern
+            ));
+        }
+        self.contains = Some(morse_pattern(pattern)?);
+        Ok(())
+    }
+
+    pub fn matches(&self, code: &str) -> bool {
+        self.contains
+            .as_ref()
+            .is_none_or(|p| code.contains(p.as_str()))
+            && self
+                .starts_with
+                .as_ref()
+                .is_none_or(|p| code.starts_with(p.as_str()))
+            && self
+                .ends_with
+                .as_ref()
+                .is_none_or(|p| code.ends_with(p.as_str()))
+            && self.length.is_none_or(|len| code.len() == len)
+    }
+}
+
+fn morse_pattern(pattern: &str) -> Result<String, Report> {
+    validate_morse_str(pattern)?;
+    Ok(pattern.to_string())
+}
+
+/// A code of the index matching a query
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    pub code: String,
+    pub words: Vec<String>,
+}
+
+/// Codes of the index matching the query, shortest first then in lexicographic order
+pub fn search(index: &DecodeIndex, query: &SearchQuery) -> Vec<SearchHit> {
+    debug!("Searching: {:?}", query);
+    let mut hits: Vec<SearchHit> = index
+        .iter()
+        .filter(|(code, _)| query.length.is_none_or(|len| code.len() == len))
+        .map(|(code, words)| (merse_to_morse(code), words))
+        .filter(|(code, _)| query.matches(code))
+        .map(|(code, words)| SearchHit {
+            code,
+            words: words.to_vec(),
+        })
+        .collect();
+    hits.sort_by(|a, b| (a.code.len(), &a.code).cmp(&(b.code.len(), &b.code)));
+    if let Some(limit) = query.limit {
+        hits.truncate(limit);
+    }
+    debug!("Found {} codes", hits.len());
+    hits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::index;
+
+    fn test_index() -> DecodeIndex {
+        index(&["sos", "oo", "eel", "ied", "a", "n"])
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            SearchQuery::parse(&["--starts-with", ".-", "--limit", "3", "---"]).unwrap(),
+            SearchQuery {
+                contains: Some("---".to_string()),
+                starts_with: Some(".-".to_string()),
+                limit: Some(3),
+                ..SearchQuery::default()
+            }
+        );
+        assert!(SearchQuery::parse(&["--contains"]).is_err());
+        assert!(SearchQuery::parse(&["--contains", "-x"]).is_err());
+        assert!(SearchQuery::parse(&["--length", "a"]).is_err());
+        assert!(SearchQuery::parse(&["--sideways", "-"]).is_err());
+        // a bare pattern is a contains one, given once
+        assert!(SearchQuery::parse(&["--", "--contains", "-.-"]).is_err());
+        assert!(SearchQuery::parse(&["--contains", "-.-", "--"]).is_err());
+        assert!(SearchQuery::parse(&["--", "..."]).is_err());
+    }
+
+    #[test]
+    fn test_search() {
+        let index = test_index();
+        let query = SearchQuery::parse(&["--contains", "---"]).unwrap();
+        assert_eq!(
+            search(&index, &query),
+            vec![
+                SearchHit {
+                    code: "------".to_string(),
+                    words: vec!["oo".to_string()]
+                },
+                SearchHit {
+                    code: "...---...".to_string(),
+                    words: vec!["sos".to_string()]
+                },
+            ]
+        );
+        let query = SearchQuery::parse(&["--ends-with", "-", "--limit", "1"]).unwrap();
+        assert_eq!(search(&index, &query)[0].code, ".-");
+        let query = SearchQuery::parse(&["--starts-with", "..", "--length", "6"]).unwrap();
+        assert_eq!(
+            search(&index, &query),
+            vec![SearchHit {
+                code: "...-..".to_string(),
+                words: vec!["eel".to_string(), "ied".to_string()]
+            }]
+        );
+        assert!(search(&index, &SearchQuery::parse(&["-.-.-."]).unwrap()).is_empty());
+    }
+}
@@ -0,0 +1,445 @@
+//! Local HTTP JSON API (`server` feature), backed by a decode index built once at startup
+//! and shared by the worker threads.
+//!
+//! - `GET /health`
+//! - `POST /encode` `{"words": [..], "spaced": false}`
+//! - `POST /decode` `{"codes": [..], "mode": "words" | "letters" | "count"}`
+//! - `POST /permutations` `{"alphabets": [..]}`
+//! - `GET /search?contains=..&starts-with=..&ends-with=..&length=..&limit=..`
+
+use crate::decode::{decode_with_index, DecodeIndex};
+use crate::encode::encode;
+use crate::glyphs::Glyphs;
+use crate::letters::{count_letters, decode_letters};
+use crate::permutations::run_batch;
+use crate::records::{count_value, Record};
+use crate::search::{search, SearchQuery};
+use crate::spaced::{encode_spaced, Separators};
+use color_eyre::{eyre::eyre, Report};
+use serde_json::{json, Value};
+use std::io::Read;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::thread;
+use tiny_http::{Header, Request, Response, Server};
+use tracing::{debug, info, warn};
+
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:8080";
+pub const DEFAULT_MAX_BODY: usize = 64 * 1024;
+pub const DEFAULT_WORKERS: usize = 4;
+/// Search results returned when the request gives no limit
+const DEFAULT_SEARCH_LIMIT: usize = 1000;
+/// Alphabet permutations solved per request
+const MAX_PERMUTATIONS: usize = 4;
+/// Permutations tried by the search of each alphabet (about a second), before failing it
+const MAX_PERMUTATION_STEPS: usize = 1_000_000;
+
+/// Status code and JSON body of a response
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reply {
+    pub status: u16,
+    pub body: Value,
+}
+
+impl Reply {
+    fn ok(body: Value) -> Self {
+        Reply { status: 200, body }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Reply {
+            status,
+            body: json!({ "error": message }),
+        }
+    }
+}
+
+/// Load the word list, then serve the API on the address until the process is stopped
+pub fn run(
+    address: &str,
+    wordlist: Option<&str>,
+    max_body: usize,
+    workers: usize,
+) -> Result<(), Report> {
+    let index = Arc::new(DecodeIndex::load(wordlist)?);
+    let server =
+        Server::http(address).map_err(|e| eyre!("Couldn't listen on {}: {}", address, e))?;
+    info!("Listening on http://{}", server.server_addr());
+    serve(Arc::new(server), index, max_body, workers)
+}
+
+/// Answer the requests of the server on `workers` threads, until it is unblocked
+pub fn serve(
+    server: Arc<Server>,
+    index: Arc<DecodeIndex>,
+    max_body: usize,
+    workers: usize,
+) -> Result<(), Report> {
+    let handles: Vec<thread::JoinHandle<()>> = (0..workers.max(1))
+        .map(|_| {
+            let server = Arc::clone(&server);
+            let index = Arc::clone(&index);
+            thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    respond(request, &index, max_body);
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle
+            .join()
+            .map_err(|_| eyre!("A server worker panicked"))?;
+    }
+    Ok(())
+}
+
+fn respond(mut request: Request, index: &DecodeIndex, max_body: usize) {
+    let method = request.method().to_string();
+    let url = request.url().to_string();
+    let reply = match read_body(&mut request, max_body) {
+        Ok(body) => catch_panic(|| handle(&method, &url, &body, index)),
+        Err(reply) => reply,
+    };
+    debug!("{} {} -> {}", method, url, reply.status);
+    let content_type = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+        .expect("Invalid static header");
+    let response = Response::from_string(reply.body.to_string())
+        .with_status_code(reply.status)
+        .with_header(content_type);
+    if let Err(e) = request.respond(response) {
+        warn!("Couldn't answer {} {}: {}", method, url, e);
+    }
+}
+
+/// The reply of the handler, or an internal error if it panicked: a request must not take
+/// its worker down
+fn catch_panic<F: FnOnce() -> Reply>(handler: F) -> Reply {
+    panic::catch_unwind(AssertUnwindSafe(handler)).unwrap_or_else(|_| {
+        warn!("Request handler panicked");
+        Reply::error(500, "Internal error")
+    })
+}
+
+fn read_body(request: &mut Request, max_body: usize) -> Result<String, Reply> {
+    let too_large = Reply::error(413, &format!("Body larger than {} bytes", max_body));
+    if request.body_length().is_some_and(|len| len > max_body) {
+        return Err(too_large);
+    }
+    let mut body = String::new();
+    request
+        .as_reader()
+        .take(max_body as u64 + 1)
+        .read_to_string(&mut body)
+        .map_err(|_| Reply::error(400, "Body is not UTF-8 text"))?;
+    if body.len() > max_body {
+        return Err(too_large);
+    }
+    Ok(body)
+}
+
+/// Answer a request, given its method, url (path and query string) and body
+pub fn handle(method: &str, url: &str, body: &str, index: &DecodeIndex) -> Reply {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let res = match (method, path) {
+        ("GET", "/health") => Ok(json!({ "status": "ok", "codes": index.len() })),
+        ("POST", "/encode") => parse_body(body).and_then(|body| encode_route(&body)),
+        ("POST", "/decode") => parse_body(body).and_then(|body| decode_route(&body, index)),
+        ("POST", "/permutations") => parse_body(body).and_then(|body| permutations_route(&body)),
+        ("GET", "/search") => search_route(query, index),
+        (_, "/health" | "/encode" | "/decode" | "/permutations" | "/search") => {
+            return Reply::error(405, "Method not allowed")
+        }
+        _ => return Reply::error(404, "Not found"),
+    };
+    match res {
+        Ok(body) => Reply::ok(body),
+        Err(e) => Reply::error(400, &e.to_string()),
+    }
+}
+
+fn parse_body(body: &str) -> Result<Value, Report> {
+    serde_json::from_str(body).map_err(|e| eyre!("Invalid JSON body: {}", e))
+}
+
+/// The list of strings under key, a single string is taken as a list of one
+fn string_list(body: &Value, key: &str) -> Result<Vec<String>, Report> {
+    let strings = match body.get(key) {
+        Some(Value::String(s)) => vec![s.clone()],
+        Some(Value::Array(items)) => items
+            .iter()
+            .map(|item| item.as_str().map(String::from))
+            .collect::<Option<Vec<String>>>()
+            .ok_or_else(|| eyre!("`{}` must only contain strings", key))?,
+        _ => return Err(eyre!("Missing `{}` list of strings", key)),
+    };
+    Ok(strings)
+}
+
+fn results(records: Vec<Record>) -> Value {
+    let records: Vec<Value> = records.iter().map(Record::to_json).collect();
+    json!({ "results": records })
+}
+
+fn encode_route(body: &Value) -> Result<Value, Report> {
+    let spaced = body.get("spaced").and_then(Value::as_bool).unwrap_or(false);
+    let mut records: Vec<Record> = Vec::new();
+    for word in string_list(body, "words")? {
+        let code = match spaced {
+            true => encode_spaced(&word, &Separators::default())?,
+            false => encode(&word)?.remove(0),
+        };
+        records.push(
+            Record::new()
+                .with("input", word)
+                .with("length", code.chars().count())
+                .with("code", code),
+        );
+    }
+    Ok(results(records))
+}
+
+fn decode_route(body: &Value, index: &DecodeIndex) -> Result<Value, Report> {
+    let mode = body.get("mode").and_then(Value::as_str).unwrap_or("words");
+    let mut records: Vec<Record> = Vec::new();
+    for code in string_list(body, "codes")? {
+        let record = Record::new().with("input", code.trim());
+        let record = match mode {
+            "words" => {
+                let words = decode_with_index(&code, index)?;
+                record.with("count", words.len()).with("words", words)
+            }
+            "letters" => {
+                let words = decode_letters(&code)?;
+                record.with("count", words.len()).with("words", words)
+            }
+            "count" => record.with("count", count_value(count_letters(&code)?)),
+            _ => return Err(eyre!("Unknown decode mode `{}`", mode)),
+        };
+        records.push(record);
+    }
+    Ok(results(records))
+}
+
+fn permutations_route(body: &Value) -> Result<Value, Report> {
+    let smalphas = string_list(body, "alphabets")?;
+    if smalphas.len() > MAX_PERMUTATIONS {
+        return Err(eyre!(
+            "At most {} alphabet permutations per request",
+            MAX_PERMUTATIONS
+        ));
+    }
+    let alphabets = run_batch(&smalphas, Some(MAX_PERMUTATION_STEPS))?;
+    let records: Vec<Record> = smalphas
+        .into_iter()
+        .zip(alphabets)
+        .map(|(smalpha, alphabet)| {
+            Record::new()
+                .with("input", smalpha)
+                .with("alphabet", alphabet)
+        })
+        .collect();
+    Ok(results(records))
+}
+
+/// Decode a query string component: `+` is a space and `%XX` a byte of UTF-8 text
+fn percent_decode(component: &str) -> Result<String, Report> {
+    let invalid = || eyre!("Invalid percent encoding `{}`", component);
+    let mut bytes: Vec<u8> = Vec::with_capacity(component.len());
+    let mut rest = component.bytes();
+    while let Some(byte) = rest.next() {
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex = [rest.next(), rest.next()];
+                let hex = match hex {
+                    [Some(high), Some(low)] => [high, low],
+                    _ => return Err(invalid()),
+                };
+                let hex = std::str::from_utf8(&hex).map_err(|_| invalid())?;
+                bytes.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+            }
+            _ => bytes.push(byte),
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| invalid())
+}
+
+fn search_route(query: &str, index: &DecodeIndex) -> Result<Value, Report> {
+    let glyphs = Glyphs::default();
+    let mut args: Vec<String> = Vec::new();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair
+            .split_once('=')
+            .ok_or_else(|| eyre!("Missing value for `{}`", pair))?;
+        let key = percent_decode(key)?;
+        match key.as_str() {
+            "contains" | "starts-with" | "ends-with" | "length" | "limit" => {
+                args.push(format!("--{}", key));
+                // patterns pasted with other dot and dash glyphs
+                args.push(glyphs.normalize(&percent_decode(value)?));
+            }
+            _ => return Err(eyre!("Unknown search parameter `{}`", key)),
+        }
+    }
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let mut query = SearchQuery::parse(&args)?;
+    query.limit = query.limit.or(Some(DEFAULT_SEARCH_LIMIT));
+    let records: Vec<Record> = search(index, &query)
+        .into_iter()
+        .map(|hit| {
+            Record::new()
+                .with("code", hit.code)
+                .with("count", hit.words.len())
+                .with("words", hit.words)
+        })
+        .collect();
+    Ok(results(records))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::index;
+    use std::io::Write;
+    use std::net::TcpStream;
+
+    fn test_index() -> DecodeIndex {
+        index(&["sos", "oo", "caret", "ceca", "a"])
+    }
+
+    #[test]
+    fn test_handle() {
+        let index = test_index();
+        assert_eq!(
+            handle("GET", "/health", "", &index),
+            Reply::ok(json!({"status": "ok", "codes": 4}))
+        );
+        assert_eq!(
+            handle("POST", "/encode", r#"{"words": ["sos", "a"]}"#, &index).body,
+            json!({"results": [
+                {"input": "sos", "length": 9, "code": "...---..."},
+                {"input": "a", "length": 2, "code": ".-"}
+            ]})
+        );
+        assert_eq!(
+            handle("POST", "/decode", r#"{"codes": "-.-..-.-..-"}"#, &index).body,
+            json!({"results": [
+                {"input": "-.-..-.-..-", "count": 2, "words": ["caret", "ceca"]}
+            ]})
+        );
+        assert_eq!(
+            handle(
+                "POST",
+                "/decode",
+                r#"{"codes": ["..."], "mode": "count"}"#,
+                &index
+            )
+            .body,
+            json!({"results": [{"input": "...", "count": 4}]})
+        );
+        assert_eq!(
+            handle("GET", "/search?contains=---&limit=1", "", &index).body,
+            json!({"results": [{"code": "------", "count": 1, "words": ["oo"]}]})
+        );
+        assert_eq!(
+            handle(
+                "GET",
+                "/search?contains=%E2%88%92%E2%88%92%2D&limit=+1",
+                "",
+                &index
+            )
+            .body,
+            handle("GET", "/search?contains=---&limit=1", "", &index).body
+        );
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("-.-").unwrap(), "-.-");
+        assert_eq!(percent_decode("a+b%2Fc%e2%88%92").unwrap(), "a b/c\u{2212}");
+        assert!(percent_decode("%2").is_err());
+        assert!(percent_decode("%zz").is_err());
+        assert!(percent_decode("%E2%88").is_err());
+    }
+
+    #[test]
+    fn test_catch_panic() {
+        assert_eq!(catch_panic(|| Reply::ok(json!(1))), Reply::ok(json!(1)));
+        assert_eq!(catch_panic(|| panic!("boom")).status, 500);
+    }
+
+    #[test]
+    fn test_handle_errors() {
+        let index = test_index();
+        assert_eq!(handle("GET", "/nowhere", "", &index).status, 404);
+        assert_eq!(handle("GET", "/encode", "", &index).status, 405);
+        assert_eq!(handle("POST", "/encode", "{", &index).status, 400);
+        assert_eq!(
+            handle("POST", "/encode", r#"{"words": [1]}"#, &index).status,
+            400
+        );
+        assert_eq!(
+            handle("POST", "/encode", r#"{"words": ["h3"]}"#, &index).status,
+            400
+        );
+        assert_eq!(
+            handle(
+                "POST",
+                "/decode",
+                r#"{"codes": ["-"], "mode": "x"}"#,
+                &index
+            )
+            .status,
+            400
+        );
+        let long = json!({"codes": [".".repeat(40)], "mode": "letters"}).to_string();
+        assert_eq!(handle("POST", "/decode", &long, &index).status, 400);
+        let many = json!({ "alphabets": vec![".-"; MAX_PERMUTATIONS + 1] }).to_string();
+        assert_eq!(handle("POST", "/permutations", &many, &index).status, 400);
+        let not_alphabet = json!({ "alphabets": [".-".repeat(40) + ".."] }).to_string();
+        assert_eq!(
+            handle("POST", "/permutations", &not_alphabet, &index).status,
+            400
+        );
+        assert_eq!(handle("GET", "/search?colour=-", "", &index).status, 400);
+        assert_eq!(handle("GET", "/search?contains", "", &index).status, 400);
+        assert_eq!(handle("GET", "/search?contains=%-", "", &index).status, 400);
+    }
+
+    fn post(address: &str, path: &str, body: &str) -> String {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(
+            stream,
+            "POST {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: {}\r\n\r\n{}",
+            path,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_serve_localhost() {
+        let server = Arc::new(Server::http("127.0.0.1:0").unwrap());
+        let address = server.server_addr().to_ip().unwrap().to_string();
+        let serving = Arc::clone(&server);
+        let worker = thread::spawn(move || serve(serving, Arc::new(test_index()), 64, 1));
+
+        let response = post(&address, "/encode", r#"{"words": "sos"}"#);
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.contains("application/json"));
+        assert!(
+            response.ends_with(r#"{"results":[{"input":"sos","length":9,"code":"...---..."}]}"#)
+        );
+
+        let body = format!("{{\"words\": [\"{}\"]}}", "a".repeat(64));
+        assert!(post(&address, "/encode", &body).starts_with("HTTP/1.1 413"));
+
+        server.unblock();
+        worker.join().unwrap().unwrap();
+    }
+}
@@ -0,0 +1,195 @@
+//! Standard (spaced) morse: letters separated by a letter gap and words by a word gap,
+//! e.g. `.... . .-.. .-.. --- / .-- --- .-. .-.. -..`
+
+use crate::encode::word_codes;
+use crate::morses::{extension_letter, morse_to_char, validate_morse_str, DASH, DOT};
+use color_eyre::{eyre::eyre, Report};
+use tracing::{debug, trace, warn};
+
+pub const LETTER_SEPARATOR: &str = " ";
+pub const WORD_SEPARATOR: &str = " / ";
+/// Placeholder for code groups not present in morse code
+pub const UNKNOWN_CHAR: char = '?';
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Separators {
+    pub letter: String,
+    pub word: String,
+}
+
+impl Default for Separators {
+    fn default() -> Self {
+        Separators {
+            letter: LETTER_SEPARATOR.to_string(),
+            word: WORD_SEPARATOR.to_string(),
+        }
+    }
+}
+
+impl Separators {
+    pub fn new(letter: &str, word: &str) -> Result<Self, Report> {
+        let separators = Separators {
+            letter: letter.to_string(),
+            word: word.to_string(),
+        };
+        separators.validate()?;
+        Ok(separators)
+    }
+
+    /// Separators must be non empty, different from each other and free of dots and dashes.
+    /// Words are split first, so the word separator may contain the letter separator but
+    /// not the other way around.
+    pub fn validate(&self) -> Result<(), Report> {
+        for sep in [&self.letter, &self.word].iter() {
+            if sep.is_empty() || sep.contains(DOT) || sep.contains(DASH) {
+                return Err(eyre!("Not a valid separator: `{}`", sep));
+            }
+        }
+        if self.letter == self.word {
+            return Err(eyre!(
+                "Letter and word separators must differ: `{}`",
+                self.letter
+            ));
+        }
+        if self.letter.contains(self.word.as_str()) {
+            return Err(eyre!(
+                "Letter separator `{}` must not contain the word separator `{}`",
+                self.letter,
+                self.word
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpacedDecoding {
+    pub text: String,
+    /// Code groups not present in morse code, replaced by `UNKNOWN_CHAR` in text
+    pub unknown: Vec<String>,
+}
+
+/// Encode a sentence (words separated by whitespace) to spaced morse
+pub fn encode_spaced(sentence: &str, separators: &Separators) -> Result<String, Report> {
+    debug!("Encoding spaced: {}", sentence);
+    separators.validate()?;
+    let mut morse_words: Vec<String> = Vec::new();
+    for word in sentence.split_whitespace() {
+        morse_words.push(word_codes(word)?.join(&separators.letter));
+    }
+    let encoded = morse_words.join(&separators.word);
+    trace!("{}->{}", sentence, encoded);
+    Ok(encoded)
+}
+
+/// Decode spaced morse, reporting code groups that aren't part of morse code
+pub fn decode_spaced(
+    spaced_morse: &str,
+    separators: &Separators,
+) -> Result<SpacedDecoding, Report> {
+    debug!("Decoding spaced: {}", spaced_morse);
+    separators.validate()?;
+    let mut words: Vec<String> = Vec::new();
+    let mut unknown: Vec<String> = Vec::new();
+    for morse_word in split_nonempty(spaced_morse.trim(), &separators.word) {
+        let mut word = String::new();
+        for group in split_nonempty(morse_word, &separators.letter) {
+            validate_morse_str(group)?;
+            match (morse_to_char(group), extension_letter(group)) {
+                (Some(ch), _) => word.push(ch),
+                (None, Some(letter)) => word.push_str(letter),
+                (None, None) => {
+                    warn!("Unknown morse code group: {}", group);
+                    unknown.push(group.to_string());
+                    word.push(UNKNOWN_CHAR);
+                }
+            }
+        }
+        words.push(word);
+    }
+    let text = words.join(" ");
+    trace!("{}->{}", spaced_morse, text);
+    Ok(SpacedDecoding { text, unknown })
+}
+
+/// Split ignoring empty items, so that repeated or leading separators are harmless
+fn split_nonempty<'a>(s: &'a str, separator: &'a str) -> impl Iterator<Item = &'a str> {
+    s.split(separator)
+        .filter(|item| !item.trim().is_empty())
+        .map(str::trim)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_separators() {
+        assert!(Separators::new(" ", " / ").is_ok());
+        assert!(Separators::new("|", "||").is_ok());
+        assert!(Separators::new("", " / ").is_err());
+        assert!(Separators::new(" ", " ").is_err());
+        assert!(Separators::new("||", "|").is_err());
+        assert!(Separators::new(" / ", "/").is_err());
+        assert!(Separators::new(" ", "-").is_err());
+        assert!(Separators::new(". ", "/").is_err());
+    }
+
+    #[test]
+    fn test_encode_spaced() {
+        let seps = Separators::default();
+        assert_eq!(
+            encode_spaced("Hello world", &seps).unwrap(),
+            ".... . .-.. .-.. --- / .-- --- .-. .-.. -..".to_string()
+        );
+        assert_eq!(
+            encode_spaced("  sos ", &Separators::new("|", "  ").unwrap()).unwrap(),
+            "...|---|...".to_string()
+        );
+        assert_eq!(encode_spaced("", &seps).unwrap(), String::new());
+        // û is not an ITU letter
+        assert!(encode_spaced("Crème brûlée", &seps).is_err());
+        assert_eq!(
+            encode_spaced("Señor Müller", &seps).unwrap(),
+            "... . --.-- --- .-. / -- ..-- .-.. .-.. . .-.".to_string()
+        );
+        assert!(encode_spaced("h3llo", &seps).is_err());
+        assert!(encode_spaced("hello, world", &seps).is_err());
+    }
+
+    #[test]
+    fn test_decode_spaced() {
+        let seps = Separators::default();
+        assert_eq!(
+            decode_spaced(".... . .-.. .-.. --- / .-- --- .-. .-.. -..\n", &seps).unwrap(),
+            SpacedDecoding {
+                text: "hello world".to_string(),
+                unknown: vec![]
+            }
+        );
+        assert_eq!(
+            decode_spaced("...  ---   ...", &seps).unwrap().text,
+            "sos".to_string()
+        );
+        assert_eq!(
+            decode_spaced("... ------ ... / ........", &seps).unwrap(),
+            SpacedDecoding {
+                text: "s?s ?".to_string(),
+                unknown: vec!["------".to_string(), "........".to_string()]
+            }
+        );
+        assert_eq!(
+            decode_spaced("...|---|...||.-", &Separators::new("|", "||").unwrap())
+                .unwrap()
+                .text,
+            "sos a".to_string()
+        );
+        assert_eq!(
+            decode_spaced("---- .- - . .- ..- / ..-.. - ..-.. / ------", &seps)
+                .unwrap()
+                .text,
+            "chateau été ?".to_string()
+        );
+        assert!(decode_spaced("... -x- ...", &seps).is_err());
+    }
+}
@@ -0,0 +1,260 @@
+//! Statistics of the word list under the code book: code lengths, dots and dashes, letters
+//! and their share of the code length, collisions, and the information lost by smooshing
+//! (the entropy of a word drawn uniformly from the list, minus the entropy of its code).
+
+use crate::collisions::{groups, histogram, Filter};
+use crate::morses::char_to_morse;
+use crate::records::Record;
+use crate::unicode::fold;
+use crate::wordlist::get_all_words;
+use color_eyre::Report;
+use serde_json::Value;
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use tracing::info;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    pub words: usize,
+    /// Distinct codes
+    pub codes: usize,
+    /// Codes of a single word
+    pub unique_codes: usize,
+    /// Number of words of each code length
+    pub code_lengths: BTreeMap<usize, usize>,
+    pub dots: usize,
+    pub dashes: usize,
+    /// Number of words of each difference of dots and dashes (0 for balanced codes)
+    pub balances: BTreeMap<i64, usize>,
+    /// Number of codes of each collision group size
+    pub group_sizes: BTreeMap<usize, usize>,
+    /// Occurrences of each letter
+    pub letters: BTreeMap<char, usize>,
+    /// Code length of each letter
+    pub letter_code_lengths: BTreeMap<char, usize>,
+}
+
+fn ratio(numerator: usize, denominator: usize) -> f64 {
+    match denominator {
+        0 => 0.0,
+        _ => numerator as f64 / denominator as f64,
+    }
+}
+
+impl Stats {
+    pub fn new(all_words: &[String]) -> Result<Self, Report> {
+        let mut stats = Stats {
+            words: all_words.len(),
+            ..Stats::default()
+        };
+        let groups = groups(all_words, &Filter::default())?;
+        stats.codes = groups.len();
+        stats.group_sizes = histogram(&groups).into_iter().collect();
+        stats.unique_codes = stats.group_sizes.get(&1).copied().unwrap_or(0);
+        for group in &groups {
+            let words = group.words.len();
+            let (dots, dashes) = (group.code.count_dots(), group.code.count_dashes());
+            *stats.code_lengths.entry(group.code.len()).or_insert(0) += words;
+            *stats
+                .balances
+                .entry(dots as i64 - dashes as i64)
+                .or_insert(0) += words;
+            stats.dots += dots * words;
+            stats.dashes += dashes * words;
+        }
+        for word in all_words {
+            for ch in fold(word).chars() {
+                *stats.letters.entry(ch).or_insert(0) += 1;
+                if let Entry::Vacant(entry) = stats.letter_code_lengths.entry(ch) {
+                    entry.insert(char_to_morse(ch)?.len());
+                }
+            }
+        }
+        Ok(stats)
+    }
+
+    pub fn mean_code_length(&self) -> f64 {
+        ratio(self.dots + self.dashes, self.words)
+    }
+
+    pub fn dot_dash_ratio(&self) -> f64 {
+        ratio(self.dots, self.dashes)
+    }
+
+    /// Fraction of the words that are the only one with their code
+    pub fn unique_word_fraction(&self) -> f64 {
+        ratio(self.unique_codes, self.words)
+    }
+
+    pub fn mean_group_size(&self) -> f64 {
+        ratio(self.words, self.codes)
+    }
+
+    /// Bits of a word drawn uniformly from the list
+    pub fn word_entropy(&self) -> f64 {
+        match self.words {
+            0 => 0.0,
+            words => (words as f64).log2(),
+        }
+    }
+
+    /// Bits of the code of a word drawn uniformly from the list
+    pub fn code_entropy(&self) -> f64 {
+        let words = self.words as f64;
+        // from +0.0: an empty sum, or -p log p for p = 1, would give -0.0
+        self.group_sizes.iter().fold(0.0, |entropy, (size, codes)| {
+            let p = *size as f64 / words;
+            entropy + *codes as f64 * p * (1.0 / p).log2()
+        })
+    }
+
+    /// Bits still needed to tell the word once its code is known
+    pub fn information_lost(&self) -> f64 {
+        self.word_entropy() - self.code_entropy()
+    }
+
+    /// Records of `section`, `name` and `value`, the summary first
+    pub fn to_records(&self) -> Vec<Record> {
+        let round = |x: f64| (x * 1e4).round() / 1e4;
+        let record = |section: &'static str, name: String, value: Value| {
+            Record::new()
+                .with("section", section)
+                .with("name", name)
+                .with("value", value)
+        };
+        let summary: Vec<(&str, Value)> = vec![
+            ("words", self.words.into()),
+            ("codes", self.codes.into()),
+            ("unique codes", self.unique_codes.into()),
+            (
+                "unique word fraction",
+                round(self.unique_word_fraction()).into(),
+            ),
+            ("mean group size", round(self.mean_group_size()).into()),
+            ("mean code length", round(self.mean_code_length()).into()),
+            ("dots", self.dots.into()),
+            ("dashes", self.dashes.into()),
+            ("dot dash ratio", round(self.dot_dash_ratio()).into()),
+            ("word entropy", round(self.word_entropy()).into()),
+            ("code entropy", round(self.code_entropy()).into()),
+            ("information lost", round(self.information_lost()).into()),
+        ];
+        let mut records: Vec<Record> = summary
+            .into_iter()
+            .map(|(name, value)| record("summary", name.to_string(), value))
+            .collect();
+        for (length, words) in &self.code_lengths {
+            records.push(record("code length", length.to_string(), (*words).into()));
+        }
+        for (balance, words) in &self.balances {
+            records.push(record(
+                "dots minus dashes",
+                balance.to_string(),
+                (*words).into(),
+            ));
+        }
+        for (size, codes) in &self.group_sizes {
+            records.push(record("group size", size.to_string(), (*codes).into()));
+        }
+        let letters: usize = self.letters.values().sum();
+        let code_length = self.dots + self.dashes;
+        for (letter, count) in &self.letters {
+            let name = letter.to_string();
+            let share = ratio(count * self.letter_code_lengths[letter], code_length);
+            records.push(record("letter", name.clone(), (*count).into()));
+            records.push(record(
+                "letter frequency",
+                name.clone(),
+                round(ratio(*count, letters)).into(),
+            ));
+            records.push(record("letter code share", name, round(share).into()));
+        }
+        records
+    }
+}
+
+pub fn run(words_file: Option<&str>) -> Result<Vec<Record>, Report> {
+    let all_words: Vec<String> = get_all_words(words_file)?;
+    info!("Computing statistics of {} words...", all_words.len());
+    let stats = Stats::new(&all_words)?;
+    info!("Computing statistics of {} words: done", all_words.len());
+    Ok(stats.to_records())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::words;
+
+    #[test]
+    fn test_stats() {
+        // et and a share .-, the other codes are unique
+        let stats = Stats::new(&words(&["et", "a", "s", "o"])).unwrap();
+        assert_eq!(stats.words, 4);
+        assert_eq!(stats.codes, 3);
+        assert_eq!(stats.unique_codes, 2);
+        assert_eq!(
+            stats.code_lengths,
+            [(2, 2), (3, 2)].iter().copied().collect()
+        );
+        assert_eq!((stats.dots, stats.dashes), (2 + 3, 2 + 3));
+        assert_eq!(
+            stats.balances,
+            [(-3, 1), (0, 2), (3, 1)].iter().copied().collect()
+        );
+        assert_eq!(
+            stats.group_sizes,
+            [(1, 2), (2, 1)].iter().copied().collect()
+        );
+        assert_eq!(stats.letters[&'e'], 1);
+        assert_eq!(stats.letter_code_lengths[&'o'], 3);
+        assert!((stats.mean_code_length() - 2.5).abs() < 1e-9);
+        assert!((stats.dot_dash_ratio() - 1.0).abs() < 1e-9);
+        assert!((stats.unique_word_fraction() - 0.5).abs() < 1e-9);
+        assert!((stats.word_entropy() - 2.0).abs() < 1e-9);
+        assert!((stats.code_entropy() - 1.5).abs() < 1e-9);
+        // half of the words need a bit more
+        assert!((stats.information_lost() - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_empty() {
+        let stats = Stats::new(&[]).unwrap();
+        assert_eq!(stats.information_lost(), 0.0);
+        assert!(stats.code_entropy().is_sign_positive());
+        let one = Stats::new(&words(&["sos"])).unwrap();
+        assert!(one.code_entropy().is_sign_positive());
+        assert!(one.information_lost().is_sign_positive());
+        assert_eq!(stats.mean_group_size(), 0.0);
+        assert_eq!(stats.to_records().len(), 12);
+    }
+
+    #[test]
+    fn test_records() {
+        let stats = Stats::new(&words(&["et", "a", "s", "o"])).unwrap();
+        let records = stats.to_records();
+        assert_eq!(records[0].get("section").unwrap(), "summary");
+        assert_eq!(records[0].get("name").unwrap(), "words");
+        assert_eq!(records[0].get("value").unwrap(), 4);
+        let share = |letter: &str| {
+            records
+                .iter()
+                .find(|r| {
+                    r.get("section").unwrap() == "letter code share"
+                        && r.get("name").unwrap() == letter
+                })
+                .and_then(|r| r.get("value").unwrap().as_f64())
+                .unwrap()
+        };
+        assert!((share("o") - 0.3).abs() < 1e-9);
+        assert!(
+            (share("a") + share("e") + share("o") + share("s") + share("t") - 1.0).abs() < 1e-9
+        );
+    }
+
+    #[test]
+    fn test_run() {
+        let records = run(Some("tests/wordlist")).unwrap();
+        assert_eq!(records[0].get("value").unwrap(), 1166);
+    }
+}
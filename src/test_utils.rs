@@ -0,0 +1,12 @@
+//! Fixtures shared by the unit tests
+
+use crate::decode::DecodeIndex;
+
+pub fn words(words: &[&str]) -> Vec<String> {
+    words.iter().map(|word| word.to_string()).collect()
+}
+
+/// Index of the words, which must all encode
+pub fn index(list: &[&str]) -> DecodeIndex {
+    DecodeIndex::new(&words(list)).unwrap()
+}
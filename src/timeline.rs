@@ -0,0 +1,291 @@
+//! Keying schedule, for lights, buzzers and GPIO: every on and off period with its start
+//! time. Exported as JSON (readable back as `keying` timings) or as a compact binary, and
+//! played in real time on a terminal as a blinking block.
+//!
+//! Binary layout, little endian: magic `MKT1`, tick length in microseconds (u32), number
+//! of periods (u32), then a u16 per period: the state in the high bit, the duration in
+//! ticks below. Longer periods are split in several of the same state.
+
+use crate::audio::Key;
+use crate::keying::parse_timings;
+use color_eyre::{eyre::eyre, Report};
+use std::convert::TryInto;
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+use tracing::info;
+
+pub const TIMELINE_FORMATS: [&str; 2] = ["json", "binary"];
+/// Binary time resolution, microseconds
+pub const TICK_US: u32 = 100;
+const MAGIC: &[u8; 4] = b"MKT1";
+const HEADER_LEN: usize = 12;
+const STATE_BIT: u16 = 0x8000;
+const MAX_TICKS: u16 = 0x7fff;
+const LAMP_ON: &str = "\u{2588}\u{2588}";
+const LAMP_OFF: &str = "  ";
+
+/// A period of key on or off, times in seconds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Event {
+    pub start: f64,
+    pub duration: f64,
+    pub on: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimelineFormat {
+    Json,
+    Binary,
+}
+
+impl FromStr for TimelineFormat {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(TimelineFormat::Json),
+            "binary" => Ok(TimelineFormat::Binary),
+            _ => Err(eyre!(
+                "Unknown timeline format {}, expected one of {}",
+                s,
+                TIMELINE_FORMATS.join(", ")
+            )),
+        }
+    }
+}
+
+/// Schedule of the keying, starting at 0
+pub fn timeline(keys: &[Key]) -> Vec<Event> {
+    let mut start = 0.0;
+    keys.iter()
+        .map(|key| {
+            let event = Event {
+                start,
+                duration: key.duration,
+                on: key.on,
+            };
+            start += key.duration;
+            event
+        })
+        .collect()
+}
+
+/// One object per line, times in milliseconds
+pub fn to_json(events: &[Event]) -> String {
+    let ms = |seconds: f64| (seconds * 1e6).round() / 1e3;
+    let lines: Vec<String> = events
+        .iter()
+        .map(|event| {
+            serde_json::json!({
+                "start": ms(event.start),
+                "duration": ms(event.duration),
+                "state": if event.on { "on" } else { "off" },
+            })
+            .to_string()
+        })
+        .collect();
+    format!("[\n{}\n]\n", lines.join(",\n"))
+}
+
+pub fn to_binary(events: &[Event]) -> Vec<u8> {
+    let ticks = |seconds: f64| (seconds * 1e6 / f64::from(TICK_US)).round() as u64;
+    let mut words: Vec<u16> = Vec::new();
+    for event in events {
+        // tick boundaries from the start times, so that rounding errors don't add up
+        let mut len = ticks(event.start + event.duration) - ticks(event.start);
+        while len > 0 {
+            let chunk = len.min(u64::from(MAX_TICKS));
+            let state = if event.on { STATE_BIT } else { 0 };
+            words.push(state | chunk as u16);
+            len -= chunk;
+        }
+    }
+    let mut bytes = Vec::with_capacity(HEADER_LEN + 2 * words.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&TICK_US.to_le_bytes());
+    bytes.extend_from_slice(&(words.len() as u32).to_le_bytes());
+    for word in words {
+        bytes.extend_from_slice(&word.to_le_bytes());
+    }
+    bytes
+}
+
+pub fn from_binary(bytes: &[u8]) -> Result<Vec<Event>, Report> {
+    if bytes.len() < HEADER_LEN || &bytes[..4] != MAGIC {
+        return Err(eyre!("Not a binary timeline"));
+    }
+    let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().expect("4 bytes"));
+    let tick = f64::from(u32_at(4)) / 1e6;
+    let count = u32_at(8) as usize;
+    if bytes.len() != HEADER_LEN + 2 * count {
+        return Err(eyre!(
+            "Binary timeline of {} periods should be {} bytes, not {}",
+            count,
+            HEADER_LEN + 2 * count,
+            bytes.len()
+        ));
+    }
+    let keys: Vec<Key> = bytes[HEADER_LEN..]
+        .chunks(2)
+        .map(|word| {
+            let word = u16::from_le_bytes([word[0], word[1]]);
+            Key {
+                on: word & STATE_BIT != 0,
+                duration: f64::from(word & MAX_TICKS) * tick,
+            }
+        })
+        .collect();
+    // join the periods split when written
+    let mut joined: Vec<Key> = Vec::new();
+    for key in keys {
+        match joined.last_mut() {
+            Some(last) if last.on == key.on => last.duration += key.duration,
+            _ => joined.push(key),
+        }
+    }
+    Ok(timeline(&joined))
+}
+
+pub fn write_timeline<P: AsRef<Path>>(
+    path: P,
+    events: &[Event],
+    format: TimelineFormat,
+) -> Result<(), Report> {
+    match format {
+        TimelineFormat::Json => std::fs::write(&path, to_json(events))?,
+        TimelineFormat::Binary => std::fs::write(&path, to_binary(events))?,
+    }
+    info!(
+        "Wrote a timeline of {} periods to {}",
+        events.len(),
+        path.as_ref().display()
+    );
+    Ok(())
+}
+
+/// Read a binary timeline, or a JSON one (or any timings `keying` reads)
+pub fn read_timeline<P: AsRef<Path>>(path: P) -> Result<Vec<Event>, Report> {
+    let bytes = std::fs::read(path)?;
+    if bytes.starts_with(MAGIC) {
+        return from_binary(&bytes);
+    }
+    Ok(timeline(&parse_timings(std::str::from_utf8(&bytes)?)?))
+}
+
+/// Show the events as a blinking block with the elapsed time. `wait_until` is called with
+/// the end time of each event, in seconds from the start.
+pub fn play<W: Write, F: FnMut(f64)>(
+    events: &[Event],
+    output: &mut W,
+    mut wait_until: F,
+) -> io::Result<()> {
+    for event in events {
+        let lamp = if event.on { LAMP_ON } else { LAMP_OFF };
+        write!(output, "\r[{}] {:6.2} s", lamp, event.start)?;
+        output.flush()?;
+        wait_until(event.start + event.duration);
+    }
+    let end = events
+        .last()
+        .map_or(0.0, |event| event.start + event.duration);
+    writeln!(output, "\r[{}] {:6.2} s", LAMP_OFF, end)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{keying, AudioConfig, Spacing};
+
+    fn events(sentence: &str) -> Vec<Event> {
+        timeline(&keying(sentence, Spacing::Standard, &AudioConfig::default()).unwrap())
+    }
+
+    #[test]
+    fn test_timeline() {
+        let events = events("et");
+        assert_eq!(
+            events,
+            vec![
+                Event {
+                    start: 0.0,
+                    duration: 0.06,
+                    on: true
+                },
+                Event {
+                    start: 0.06,
+                    duration: 0.18,
+                    on: false
+                },
+                Event {
+                    start: 0.24,
+                    duration: 0.18,
+                    on: true
+                },
+            ]
+        );
+        assert_eq!(
+            to_json(&events),
+            "[\n{\"start\":0.0,\"duration\":60.0,\"state\":\"on\"},\n\
+             {\"start\":60.0,\"duration\":180.0,\"state\":\"off\"},\n\
+             {\"start\":240.0,\"duration\":180.0,\"state\":\"on\"}\n]\n"
+        );
+        // JSON timelines read back as timings
+        let keys = parse_timings(&to_json(&events)).unwrap();
+        assert_eq!(keys.len(), 3);
+        assert!((keys[1].duration - 0.18).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_binary() {
+        let mut events = events("sos");
+        let bytes = to_binary(&events);
+        assert_eq!(&bytes[..4], MAGIC);
+        assert_eq!(bytes.len(), HEADER_LEN + 2 * events.len());
+        let read = from_binary(&bytes).unwrap();
+        assert_eq!(read.len(), events.len());
+        for (a, b) in read.iter().zip(&events) {
+            assert_eq!(a.on, b.on);
+            assert!((a.start - b.start).abs() < 1e-6 && (a.duration - b.duration).abs() < 1e-6);
+        }
+        // a period longer than a word holds is split, and joined back
+        events[1].duration = 5.0;
+        let bytes = to_binary(&events);
+        assert_eq!(bytes.len(), HEADER_LEN + 2 * (events.len() + 1));
+        assert!((from_binary(&bytes).unwrap()[1].duration - 5.0).abs() < 1e-6);
+        assert!(from_binary(b"MKT1").is_err());
+        assert!(from_binary(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_read_write_timeline() {
+        let events = events("hi");
+        for format in TIMELINE_FORMATS.iter() {
+            let path = std::env::temp_dir().join(format!(
+                "smooshedmorse-timeline-{}.{}",
+                std::process::id(),
+                format
+            ));
+            write_timeline(&path, &events, format.parse().unwrap()).unwrap();
+            let read = read_timeline(&path).unwrap();
+            assert_eq!(read.len(), events.len());
+            assert!((read[2].start - events[2].start).abs() < 1e-6);
+            std::fs::remove_file(&path).unwrap();
+        }
+        assert!("xml".parse::<TimelineFormat>().is_err());
+    }
+
+    #[test]
+    fn test_play() {
+        let events = events("e e");
+        let mut output: Vec<u8> = Vec::new();
+        let mut waits: Vec<f64> = Vec::new();
+        play(&events, &mut output, |t| waits.push(t)).unwrap();
+        assert_eq!(waits.len(), 3);
+        assert!((waits[2] - 0.54).abs() < 1e-9);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("\r[\u{2588}\u{2588}]   0.00 s\r[  ]   0.06 s"));
+        assert!(output.ends_with("\r[  ]   0.54 s\n"));
+    }
+}
@@ -0,0 +1,334 @@
+//! Transmission duration of morse, in dit units: a dot is 1 unit, and by default a dash 3,
+//! the gap inside a letter 1, between letters 3 and between words 7. Speeds follow the
+//! PARIS standard (a dit lasts 1.2 / wpm seconds), with optional Farnsworth spacing: the
+//! letter and word gaps stretched for an overall `farnsworth_wpm`. The audio and timeline
+//! keying is timed by this model too.
+
+use crate::audio::Spacing;
+use crate::encode::word_codes;
+use crate::morses::DASH;
+use crate::records::Record;
+use crate::wordlist::get_all_words;
+use color_eyre::{eyre::eyre, Report};
+use tracing::info;
+
+pub const DEFAULT_WPM: f64 = 20.0;
+pub const DEFAULT_DASH: f64 = 3.0;
+pub const DEFAULT_ELEMENT_GAP: f64 = 1.0;
+pub const DEFAULT_LETTER_GAP: f64 = 3.0;
+pub const DEFAULT_WORD_GAP: f64 = 7.0;
+pub const DEFAULT_TOP: usize = 10;
+/// Units of PARIS with its word gap, the word of the wpm speeds
+const PARIS_UNITS: f64 = 50.0;
+const PARIS: [&str; 5] = [".--.", ".-", ".-.", "..", "..."];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timing {
+    /// Dash length, in dots
+    pub dash: f64,
+    /// Gap between the dots and dashes of a letter, in dots
+    pub element_gap: f64,
+    pub letter_gap: f64,
+    pub word_gap: f64,
+    /// Character speed, words per minute
+    pub wpm: f64,
+    /// Overall (slower) speed reached by stretching the letter and word gaps
+    pub farnsworth_wpm: Option<f64>,
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Timing {
+            dash: DEFAULT_DASH,
+            element_gap: DEFAULT_ELEMENT_GAP,
+            letter_gap: DEFAULT_LETTER_GAP,
+            word_gap: DEFAULT_WORD_GAP,
+            wpm: DEFAULT_WPM,
+            farnsworth_wpm: None,
+        }
+    }
+}
+
+impl Timing {
+    pub fn validate(&self) -> Result<(), Report> {
+        let lengths = [
+            ("Dash", self.dash),
+            ("Element gap", self.element_gap),
+            ("Letter gap", self.letter_gap),
+            ("Word gap", self.word_gap),
+        ];
+        for (name, length) in lengths.iter() {
+            if !(length.is_finite() && *length > 0.0) {
+                return Err(eyre!("{} must be positive: {} units", name, length));
+            }
+        }
+        if !(self.wpm.is_finite() && self.wpm > 0.0) {
+            return Err(eyre!("Speed must be positive: {} wpm", self.wpm));
+        }
+        if let Some(farnsworth_wpm) = self.farnsworth_wpm {
+            if !(farnsworth_wpm > 0.0 && farnsworth_wpm <= self.wpm) {
+                return Err(eyre!(
+                    "Farnsworth speed must be positive and at most the character speed ({} wpm): {} wpm",
+                    self.wpm,
+                    farnsworth_wpm
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Dot length, seconds
+    pub fn unit(&self) -> f64 {
+        1.2 / self.wpm
+    }
+
+    pub fn seconds(&self, units: f64) -> f64 {
+        units * self.unit()
+    }
+
+    /// Factor of the letter and word gaps: PARIS with its word gap lasts 50 units at the
+    /// Farnsworth speed, only its gaps taking the extra time (the ARRL formula)
+    pub fn gap_stretch(&self) -> f64 {
+        match self.farnsworth_wpm {
+            None => 1.0,
+            Some(farnsworth_wpm) => {
+                let letters: f64 = PARIS.iter().map(|code| self.letter_units(code)).sum();
+                let gaps = (PARIS.len() - 1) as f64 * self.letter_gap + self.word_gap;
+                let total = PARIS_UNITS * self.wpm / farnsworth_wpm;
+                // the gaps never shrink, whatever the dash and gap lengths
+                ((total - letters) / gaps).max(1.0)
+            }
+        }
+    }
+
+    /// Duration of a dot or a dash
+    pub fn mark_units(&self, symbol: char) -> f64 {
+        if symbol == DASH {
+            self.dash
+        } else {
+            1.0
+        }
+    }
+
+    /// Gap between the letters of a word. Smooshed letters are separated by element gaps
+    /// only.
+    pub fn letter_gap_units(&self, spacing: Spacing) -> f64 {
+        match spacing {
+            Spacing::Standard => self.letter_gap * self.gap_stretch(),
+            Spacing::Smooshed => self.element_gap,
+        }
+    }
+
+    pub fn word_gap_units(&self) -> f64 {
+        self.word_gap * self.gap_stretch()
+    }
+
+    /// Duration of a letter code, without the gap after it
+    pub fn letter_units(&self, code: &str) -> f64 {
+        let elements = code.chars().count();
+        let marks: f64 = code.chars().map(|c| self.mark_units(c)).sum();
+        marks + elements.saturating_sub(1) as f64 * self.element_gap
+    }
+
+    /// Duration of a word of the letter codes, without the gap after it
+    pub fn word_units(&self, codes: &[&str], spacing: Spacing) -> f64 {
+        let letters: f64 = codes.iter().map(|code| self.letter_units(code)).sum();
+        letters + codes.len().saturating_sub(1) as f64 * self.letter_gap_units(spacing)
+    }
+
+    /// Duration of a text (words separated by whitespace), without a gap after it
+    pub fn text_units(&self, text: &str, spacing: Spacing) -> Result<f64, Report> {
+        let mut units = 0.0;
+        for (i, word) in text.split_whitespace().enumerate() {
+            if i > 0 {
+                units += self.word_gap_units();
+            }
+            units += self.word_units(&word_codes(word)?, spacing);
+        }
+        Ok(units)
+    }
+}
+
+/// The `top` fastest words of the word list then its `top` slowest, of `letters` letters
+/// if given, ranked
+pub fn run(
+    words_file: Option<&str>,
+    letters: Option<usize>,
+    top: usize,
+    timing: &Timing,
+    spacing: Spacing,
+) -> Result<Vec<Record>, Report> {
+    timing.validate()?;
+    let all_words: Vec<String> = get_all_words(words_file)?;
+    info!("Timing {} words...", all_words.len());
+    let mut timed: Vec<(f64, &String)> = Vec::new();
+    for word in all_words.iter() {
+        if letters.is_none_or(|letters| word.chars().count() == letters) {
+            timed.push((timing.text_units(word, spacing)?, word));
+        }
+    }
+    // stable: ties in word list order
+    timed.sort_by(|a, b| a.0.total_cmp(&b.0));
+    info!("Timing {} words: done", all_words.len());
+    let round = |x: f64| (x * 1e4).round() / 1e4;
+    let record = |speed: &str, rank: usize, (units, word): &(f64, &String)| {
+        Record::new()
+            .with("speed", speed)
+            .with("rank", rank + 1)
+            .with("word", word.as_str())
+            .with("units", round(*units))
+            .with("seconds", round(timing.seconds(*units)))
+    };
+    let fastest = timed
+        .iter()
+        .take(top)
+        .enumerate()
+        .map(|(i, timed)| record("fastest", i, timed));
+    let slowest = timed
+        .iter()
+        .rev()
+        .take(top)
+        .enumerate()
+        .map(|(i, timed)| record("slowest", i, timed));
+    Ok(fastest.chain(slowest).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{duration, keying, AudioConfig};
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(Timing::default().validate().is_ok());
+        let bad = [
+            Timing {
+                dash: 0.0,
+                ..Timing::default()
+            },
+            Timing {
+                word_gap: f64::NAN,
+                ..Timing::default()
+            },
+            Timing {
+                wpm: -5.0,
+                ..Timing::default()
+            },
+            Timing {
+                farnsworth_wpm: Some(25.0),
+                ..Timing::default()
+            },
+        ];
+        for timing in bad.iter() {
+            assert!(timing.validate().is_err(), "{:?}", timing);
+        }
+    }
+
+    #[test]
+    fn test_paris() {
+        let timing = Timing::default();
+        // 50 units with the word gap after it
+        assert_close(timing.text_units("paris", Spacing::Standard).unwrap(), 43.0);
+        assert_close(timing.seconds(50.0), 3.0);
+        assert_close(timing.gap_stretch(), 1.0);
+        let farnsworth = Timing {
+            farnsworth_wpm: Some(10.0),
+            ..Timing::default()
+        };
+        // 31 units of letters, the 19 units of gaps stretched to 69
+        assert_close(farnsworth.gap_stretch(), 69.0 / 19.0);
+        assert_close(
+            farnsworth.text_units("paris", Spacing::Standard).unwrap() + 7.0 * 69.0 / 19.0,
+            100.0,
+        );
+    }
+
+    #[test]
+    fn test_units() {
+        let timing = Timing::default();
+        assert_close(timing.letter_units("."), 1.0);
+        assert_close(timing.letter_units("-.-"), 9.0);
+        assert_close(
+            timing.word_units(&["...", "---", "..."], Spacing::Standard),
+            27.0,
+        );
+        assert_close(
+            timing.word_units(&["...", "---", "..."], Spacing::Smooshed),
+            23.0,
+        );
+        assert_close(timing.text_units("e e", Spacing::Standard).unwrap(), 9.0);
+        assert_close(timing.text_units("", Spacing::Standard).unwrap(), 0.0);
+        let slow_dashes = Timing {
+            dash: 4.0,
+            letter_gap: 4.0,
+            ..Timing::default()
+        };
+        assert_close(
+            slow_dashes.text_units("et", Spacing::Standard).unwrap(),
+            9.0,
+        );
+        assert!(timing.text_units("3", Spacing::Standard).is_err());
+    }
+
+    #[test]
+    fn test_keying() {
+        // the audio keying lasts as long, whatever the lengths
+        let text = "the quick brown fox";
+        let slow_dashes = Timing {
+            dash: 5.0,
+            element_gap: 1.5,
+            letter_gap: 4.0,
+            word_gap: 10.0,
+            ..Timing::default()
+        };
+        for (timing, spacing) in [
+            (Timing::default(), Spacing::Standard),
+            (Timing::default(), Spacing::Smooshed),
+            (slow_dashes.clone(), Spacing::Standard),
+            (slow_dashes.clone(), Spacing::Smooshed),
+            (
+                Timing {
+                    wpm: 25.0,
+                    farnsworth_wpm: Some(12.0),
+                    ..slow_dashes
+                },
+                Spacing::Standard,
+            ),
+        ] {
+            let config = AudioConfig {
+                timing: timing.clone(),
+                ..AudioConfig::default()
+            };
+            let keys = keying(text, spacing, &config).unwrap();
+            let units = timing.text_units(text, spacing).unwrap();
+            assert_close(timing.seconds(units), duration(&keys));
+        }
+    }
+
+    #[test]
+    fn test_run() {
+        let records = run(
+            Some("tests/wordlist"),
+            Some(5),
+            3,
+            &Timing::default(),
+            Spacing::Standard,
+        )
+        .unwrap();
+        assert_eq!(records.len(), 6);
+        assert_eq!(records[0].get("speed").unwrap(), "fastest");
+        assert_eq!(records[3].get("speed").unwrap(), "slowest");
+        let units: Vec<f64> = records
+            .iter()
+            .map(|r| r.get("units").unwrap().as_f64().unwrap())
+            .collect();
+        assert!(units[0] <= units[2] && units[2] <= units[5] && units[5] <= units[3]);
+        for record in records {
+            assert_eq!(record.get("word").unwrap().as_str().unwrap().len(), 5);
+        }
+    }
+}
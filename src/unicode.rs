@@ -0,0 +1,164 @@
+//! Unicode input: words are composed (NFC, so that `e` and a combining accent are `é`) and
+//! lowercased with the full Unicode case mapping, or the Turkish and Azeri one of the dotted
+//! and dotless i if that locale is set. Letters without a code, not even an ITU extension
+//! one, may be transliterated to ASCII letters.
+
+use crate::morses::extension_code;
+use color_eyre::{eyre::eyre, Report};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use tracing::trace;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// Letters transliterated other than by dropping their accents
+const TRANSLITERATIONS: [(char, &str); 9] = [
+    ('ß', "ss"),
+    ('æ', "ae"),
+    ('œ', "oe"),
+    ('ø', "o"),
+    ('đ', "d"),
+    ('ð', "d"),
+    ('þ', "th"),
+    ('ł', "l"),
+    ('ı', "i"),
+];
+
+/// Locale names, as accepted on the command line
+pub const LOCALES: [&str; 3] = ["default", "tr", "az"];
+
+/// Case mapping rules of a locale
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Locale {
+    /// Full Unicode lowercasing, for any language
+    #[default]
+    Default,
+    /// Turkish and Azeri: `I` lowercases to `ı`, and `İ` to `i`
+    Turkic,
+}
+
+impl FromStr for Locale {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(Locale::Default),
+            "tr" | "az" => Ok(Locale::Turkic),
+            _ => Err(eyre!(
+                "Unknown locale {}, expected one of {}",
+                s,
+                LOCALES.join(", ")
+            )),
+        }
+    }
+}
+
+static LOCALE: OnceLock<Locale> = OnceLock::new();
+
+/// Lowercase with the rules of the locale instead of the default ones. Must come before
+/// any folding.
+pub fn set_locale(locale: Locale) -> Result<(), Report> {
+    LOCALE
+        .set(locale)
+        .map_err(|_| eyre!("Locale set after text was folded"))?;
+    trace!("Locale: {:?}", locale);
+    Ok(())
+}
+
+/// Composed and lowercase text, in the locale set if any
+pub fn fold(text: &str) -> String {
+    fold_in(text, *LOCALE.get_or_init(Locale::default))
+}
+
+/// Composed and lowercase text, in the given locale
+pub fn fold_in(text: &str, locale: Locale) -> String {
+    if text.is_ascii() && !(locale == Locale::Turkic && text.contains('I')) {
+        return text.to_ascii_lowercase();
+    }
+    let composed: String = text.nfc().collect();
+    let folded = match locale {
+        Locale::Default => composed.to_lowercase(),
+        Locale::Turkic => composed
+            .chars()
+            .map(|ch| match ch {
+                'I' => 'ı',
+                'İ' => 'i',
+                _ => ch,
+            })
+            .collect::<String>()
+            .to_lowercase(),
+    };
+    // lowercasing may decompose (İ to i and a combining dot)
+    let folded: String = folded.nfc().collect();
+    trace!("{}->{}", text, folded);
+    folded
+}
+
+/// Folded text with every letter without a code turned to ASCII letters: accents are
+/// dropped, and some letters are spelled out (`ß` to `ss`). ITU extension letters and other
+/// characters are kept.
+pub fn transliterate(text: &str) -> Result<String, Report> {
+    let mut transliterated = String::new();
+    for ch in fold(text).chars() {
+        if is_combining_mark(ch) {
+            continue;
+        } else if ch.is_ascii() || !ch.is_alphabetic() || extension_code(ch).is_some() {
+            transliterated.push(ch);
+        } else if let Some((_, ascii)) = TRANSLITERATIONS.iter().find(|(letter, _)| *letter == ch) {
+            transliterated.push_str(ascii);
+        } else {
+            let base: String = ch
+                .to_string()
+                .nfd()
+                .filter(|c| !is_combining_mark(*c))
+                .collect();
+            if !base.chars().all(|c| c.is_ascii_alphabetic()) {
+                return Err(eyre!("No transliteration to ASCII letters for `{}`", ch));
+            }
+            transliterated.push_str(&base);
+        }
+    }
+    trace!("{}->{}", text, transliterated);
+    Ok(transliterated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fold() {
+        assert_eq!(fold("Horse"), "horse");
+        assert_eq!(fold("ÉTÉ"), "été");
+        assert_eq!(fold("e\u{301}t\u{301}"), "ét\u{301}");
+        assert_eq!(fold("ÑANDÚ"), "ñandú");
+        assert_eq!(fold("ΣΟΦΟΣ"), "σοφος");
+        assert_eq!(fold("İ"), "i\u{307}");
+    }
+
+    #[test]
+    fn test_fold_in() {
+        assert_eq!(fold_in("İstanbul", Locale::Turkic), "istanbul");
+        assert_eq!(fold_in("ISPARTA", Locale::Turkic), "ısparta");
+        // I and a combining dot above compose to İ first
+        assert_eq!(fold_in("I\u{307}ZMIR", Locale::Turkic), "izmır");
+        assert_eq!(fold_in("ISPARTA", Locale::Default), "isparta");
+        assert_eq!(fold_in("İstanbul", Locale::Default), "i\u{307}stanbul");
+        assert_eq!("az".parse::<Locale>().unwrap(), Locale::Turkic);
+        assert_eq!("default".parse::<Locale>().unwrap(), Locale::Default);
+        assert!("fr".parse::<Locale>().is_err());
+    }
+
+    #[test]
+    fn test_transliterate() {
+        assert_eq!(transliterate("Crème brûlée").unwrap(), "crème brulée");
+        assert_eq!(transliterate("Straße").unwrap(), "strasse");
+        assert_eq!(transliterate("Ærøskøbing").unwrap(), "ærøskøbing");
+        assert_eq!(transliterate("Łódź").unwrap(), "łódź");
+        assert_eq!(transliterate("Œuvre, Þórr").unwrap(), "oeuvre, þórr");
+        assert_eq!(transliterate("İstanbul").unwrap(), "istanbul");
+        assert_eq!(transliterate("r2-d2!").unwrap(), "r2-d2!");
+        assert!(transliterate("σοφος").is_err());
+        assert!(transliterate("東京").is_err());
+    }
+}
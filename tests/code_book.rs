@@ -0,0 +1,32 @@
+use smooshedmorse::design;
+use smooshedmorse::morses::{extension_code, extension_letter, set_code_book, ITU_EXTENSIONS};
+use smooshedmorse::spaced::{decode_spaced, encode_spaced, Separators};
+use smooshedmorse::timing::Timing;
+
+// the code book is set once per process, so a single test
+#[test]
+fn test_code_book_extensions() {
+    let (_, code_book) = design::run(Some("tests/wordlist"), &Timing::default()).unwrap();
+    set_code_book(&code_book).unwrap();
+    let letter_codes: Vec<&str> = code_book.split(' ').collect();
+    let seps = Separators::default();
+    let (kept, off): (Vec<_>, Vec<_>) = ITU_EXTENSIONS
+        .iter()
+        .filter(|(letter, _)| letter.chars().count() == 1)
+        .partition(|(_, code)| !letter_codes.contains(code));
+    assert!(!kept.is_empty() && !off.is_empty());
+    // accented letters round trip under their ITU code, to the first letter of the code
+    for (letter, code) in kept {
+        let encoded = encode_spaced(&format!("{} horse", letter), &seps).unwrap();
+        assert!(encoded.starts_with(&format!("{} / ", code)));
+        assert_eq!(
+            decode_spaced(&encoded, &seps).unwrap().text,
+            format!("{} horse", extension_letter(code).unwrap())
+        );
+    }
+    // or are refused when their code is a letter's, rather than decoded as that letter
+    for (letter, _) in off {
+        assert_eq!(extension_code(letter.chars().next().unwrap()), None);
+        assert!(encode_spaced(letter, &seps).is_err());
+    }
+}
@@ -1,5 +1,7 @@
 use smooshedmorse::decode::decode;
 use smooshedmorse::decode::decode_merse;
+use smooshedmorse::glyphs::Glyphs;
+use smooshedmorse::merses::Merse;
 
 #[test]
 fn test_decode() {
@@ -13,10 +15,22 @@ fn test_decode() {
 fn test_decode_merse() {
     assert_eq!(
         decode_merse(
-            vec![true, false, true, false, false, true, false, true, false, false, true],
+            Merse::from(vec![
+                true, false, true, false, false, true, false, true, false, false, true
+            ]),
             Some("tests/wordlist")
         )
         .unwrap(),
         vec![String::from("caret"), String::from("ceca")]
     );
 }
+
+#[test]
+fn test_decode_pasted() {
+    let pasted = "\u{2212}\u{b7}\u{2212}\u{b7}\u{b7}\u{2212}\u{b7}\u{2212}\u{b7}\u{b7}\u{2212}\n";
+    assert!(decode(pasted, Some("tests/wordlist")).is_err());
+    assert_eq!(
+        decode(&Glyphs::default().normalize(pasted), Some("tests/wordlist")).unwrap(),
+        vec![String::from("caret"), String::from("ceca")]
+    );
+}
@@ -0,0 +1,14 @@
+use smooshedmorse::spaced::{decode_spaced, encode_spaced, Separators};
+
+#[test]
+fn test_spaced_round_trip() {
+    let seps = Separators::default();
+    let encoded = encode_spaced("The quick brown fox", &seps).unwrap();
+    assert_eq!(
+        encoded,
+        "- .... . / --.- ..- .. -.-. -.- / -... .-. --- .-- -. / ..-. --- -..-".to_string()
+    );
+    let decoded = decode_spaced(&encoded, &seps).unwrap();
+    assert_eq!(decoded.text, "the quick brown fox".to_string());
+    assert!(decoded.unknown.is_empty());
+}